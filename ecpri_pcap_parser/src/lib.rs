@@ -4,4 +4,5 @@ extern crate hex_literal;
 // extern crate num_traits;
 
 mod protocols;
-pub use protocols::*;
\ No newline at end of file
+pub use protocols::*;
+pub mod utility;
\ No newline at end of file
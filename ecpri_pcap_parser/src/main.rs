@@ -1,16 +1,29 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
 use std::env;
 use std::fs::File;
 use std::io::{BufWriter, prelude::*};
 use pcap_parser::*;
 use pcap_parser::traits::PcapReaderIterator;
-mod protocols;
-use crate::protocols::{ethernet::Ethernet, ethernet::PacketDataType, ethernet::SUPPORTED, bip::BIPHeader};
-use crate::protocols::{ecpri::ecpri_parse, EcpriType};
-use chrono::{DateTime, TimeZone, NaiveDateTime, Utc};
-mod utility;
-use utility::ecpri_analysis::{EcpriDataVec, EcpriData};
-
-const MANTISSA: u16 = 9;
+use ecpri_pcap_parser::{ethernet::Ethernet, ethernet::PacketDataType, ethernet::SUPPORTED, bip::BIPHeader};
+use ecpri_pcap_parser::{ecpri::ecpri_split, ecpri::ecpri_parse_payload, ParseConfig};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use ecpri_pcap_parser::utility::ecpri_analysis::{EcpriDataVec, EcpriData};
+use ecpri_pcap_parser::utility::fragment_reassembly::FragmentReassembler;
+use ecpri_pcap_parser::utility::sequence_analysis::SequenceAnalysis;
+use ecpri_pcap_parser::utility::air_alignment::{AirAlignment, EpochConfig};
+use ecpri_pcap_parser::utility::delay_management::{DelayAnalysis, DelayWindows};
+use ecpri_pcap_parser::utility::cu_correlation::CuCorrelator;
+use ecpri_pcap_parser::utility::resource_grid::GridAssembler;
+use ecpri_pcap_parser::utility::power_measurement::PowerAnalysis;
+use ecpri_pcap_parser::utility::bfp_statistics::BfpStatistics;
+use ecpri_pcap_parser::utility::modulation_analysis::{analyse_grid, write_evm_csv};
+use ecpri_pcap_parser::utility::carrier_config::CarrierConfig;
+use ecpri_pcap_parser::utility::tbs_estimation::TbsEstimator;
+use ecpri_pcap_parser::utility::ofdm::{OfdmModulator, write_iq_i16};
+use ecpri_pcap_parser::utility::ssb_detection::SsbSearch;
+use ecpri_pcap_parser::utility::pbch_decoding::{l_max, MibDecoding};
+
 const MAX_PACKET_COUNT: u16 = 10000;
 
 fn main() ->std::io::Result<()> {
@@ -38,6 +51,18 @@ fn main() ->std::io::Result<()> {
 
     let mut ecpri_data = EcpriDataVec::new();
 
+    // 9 bits BFP for every eAxC, streams configured differently over the M-Plane need their own entry, e.g.
+    // parse_config.comp_config.insert(pcid, EaxcCompConfig::Static(UdCompHdr::new(16, UdCompMeth::NoCompression)));
+    // The eAxC ID split follows the M-Plane eaxc-id bitmasks, e.g.
+    // parse_config.eaxc_config = EaxcIdConfig::new(4, 4, 4, 4);
+    // Section type 6 needs the number of TRX of the RU, 64 by default, e.g.
+    // parse_config.num_trx = 32;
+    let parse_config = ParseConfig::default();
+    let mut reassembler = FragmentReassembler::default();
+    let mut last_timestamp = None;
+    // every message is counted, also the fragments and the ones which can't be parsed
+    let mut seq_analysis = SequenceAnalysis::new(parse_config.eaxc_config, Duration::milliseconds(10));
+
     loop {
         match reader.next() {
             Ok((offset, block)) => {
@@ -66,15 +91,25 @@ fn main() ->std::io::Result<()> {
                             PacketDataType::SUPPORTED(SUPPORTED::PTP) => {
                                 unimplemented!()
                             },
-                            PacketDataType::SUPPORTED(SUPPORTED::ECPRI) => {
-                                let data = ecpri_parse(&ether_data, MANTISSA);
-                                // use hex_slice::AsHex;
-                                // println!("data:\n {:02X}", data.as_hex()); // will cause stack overflow
-                                ecpri_data.append(EcpriData {
-                                    timestamp: date_time,
-                                    header: 
-                                    data,
-                                });
+                            PacketDataType::SUPPORTED(SUPPORTED::ECPRI) | PacketDataType::SUPPORTED(SUPPORTED::ECPRI_UNTAGGED) => match ecpri_split(ether_header.ether_type, ether_data) {
+                                Ok((header, payload)) => {
+                                    last_timestamp = Some(date_time);
+                                    seq_analysis.push(date_time, &header);
+                                    // the fragments are kept until the message is complete
+                                    if let Some((header, payload)) = reassembler.push(date_time, &header, payload) {
+                                        match ecpri_parse_payload(&header, &payload, &parse_config) {
+                                            // use hex_slice::AsHex;
+                                            // println!("data:\n {:02X}", data.as_hex()); // will cause stack overflow
+                                            Ok(data) => ecpri_data.append(EcpriData {
+                                                timestamp: date_time,
+                                                header,
+                                                data,
+                                            }),
+                                            Err(e) => println!("Skipped: {}", e),
+                                        }
+                                    }
+                                },
+                                Err(e) => println!("Skipped: {}", e),
                             },
                             PacketDataType::UNKNOWN(unknown_type) => println!("Unknown data type: {:?}", unknown_type),
                         }
@@ -97,24 +132,120 @@ fn main() ->std::io::Result<()> {
         }
     }
     println!("num_blocks: {}", num_blocks);
+    if let Some(timestamp) = last_timestamp {
+        reassembler.flush(timestamp);
+    }
+    println!("reassembled messages: {}, fragment events: {}", reassembler.reassembled, reassembler.events.len());
+    for (timestamp, event) in reassembler.events.iter() {
+        println!("{}: {:?}", timestamp, event);
+    }
+
+    // sequence continuity of every stream, 10 ms time series
+    println!("{}", seq_analysis);
+    seq_analysis.write_summary_csv(&mut BufWriter::new(File::create("sequence_summary.csv")?))?;
+    seq_analysis.write_time_series_csv(&mut BufWriter::new(File::create("sequence_time_series.csv")?))?;
+
+    // the carrier from the TOML file given after the pcap file, the frameStructure of the
+    // section type 0/3 messages or 100 MHz at 30 kHz
+    let carrier = match args.get(2) {
+        Some(path) => CarrierConfig::from_toml_file(path).expect("Can't load the carrier config."),
+        None => CarrierConfig::infer(&ecpri_data).unwrap_or_default(),
+    };
+    println!("carrier: {:?}", carrier);
+
+    // packet time against the air time of its symbol, the capture clock is UTC
+    let alignment = AirAlignment::from_data(&ecpri_data, EpochConfig::default(), carrier.numerology);
+    alignment.write_csv(&mut BufWriter::new(File::create("air_alignment.csv")?), &parse_config.eaxc_config)?;
+
+    // reception windows of the O-RU delay profile, 10 us histogram bins
+    let delay_analysis = DelayAnalysis::from_alignment(&alignment, &parse_config.eaxc_config, DelayWindows::default(), 10_000);
+    println!("{}", delay_analysis);
+    delay_analysis.write_histogram_csv(&mut BufWriter::new(File::create("delay_histogram.csv")?))?;
+    delay_analysis.write_violations_csv(&mut BufWriter::new(File::create("delay_violations.csv")?))?;
+
+    // U-Plane sections against the C-Plane sections scheduling them
+    let correlator = CuCorrelator::from_data(&ecpri_data, parse_config.eaxc_config, carrier.n_rb);
+    println!("{}", correlator);
+    correlator.write_issues_csv(&mut BufWriter::new(File::create("cu_correlation.csv")?))?;
+
+    // BFP exponents and saturated mantissas of every stream
+    let bfp_statistics = BfpStatistics::from_data(&ecpri_data, parse_config.eaxc_config);
+    println!("{}", bfp_statistics);
+    bfp_statistics.write_summary_csv(&mut BufWriter::new(File::create("bfp_summary.csv")?))?;
+    bfp_statistics.write_time_series_csv(&mut BufWriter::new(File::create("bfp_time_series.csv")?))?;
+
+    // resource grid of every slot, symbol x subcarrier x eAxC
+    let grids = GridAssembler::from_carrier(parse_config.eaxc_config, &carrier).assemble(&ecpri_data);
+    for (key, slot) in grids.iter() {
+        println!("slot {:?}: grid {:?}, filled REs: {}", key, slot.grid.shape(), slot.filled_count());
+    }
+
+    // SS/PBCH blocks of the DL grids: PCI, SSB index, location and power
+    let ssb = SsbSearch::from_grids(&grids);
+    println!("{}", ssb);
+    ssb.write_csv(&mut BufWriter::new(File::create("ssb.csv")?))?;
+
+    // MIB of every SSB, SFN against frameId
+    let mib = MibDecoding::from_search(&ssb, &grids, l_max(&carrier));
+    println!("{}", mib);
+    mib.write_csv(&mut BufWriter::new(File::create("mib.csv")?))?;
+
+    // power per PRB, symbol and slot in dBFS, UL RSSI per symbol
+    let power = PowerAnalysis::from_grids(&grids);
+    println!("{}", power);
+    power.write_prb_csv(&mut BufWriter::new(File::create("power_prb.csv")?))?;
+    power.write_symbol_csv(&mut BufWriter::new(File::create("power_symbol.csv")?))?;
+    power.write_slot_csv(&mut BufWriter::new(File::create("power_slot.csv")?))?;
+
+    // modulation order and EVM of every allocation of the grids
+    let evm: Vec<_> = grids.values().map(|slot| (slot, analyse_grid(slot))).collect();
+    write_evm_csv(&mut BufWriter::new(File::create("evm.csv")?), &evm)?;
+
+    // candidate TBS of every allocation and implied throughput per slot
+    let tbs_estimation = TbsEstimator::new(parse_config.eaxc_config, carrier.n_rb, carrier.numerology).estimate(&ecpri_data, &evm);
+    println!("{}", tbs_estimation);
+    tbs_estimation.write_allocations_csv(&mut BufWriter::new(File::create("tbs_allocations.csv")?))?;
+    tbs_estimation.write_throughput_csv(&mut BufWriter::new(File::create("tbs_throughput.csv")?))?;
+
+    // baseband waveform of every stream, the slots one after the other, interleaved 16 bits I/Q,
+    // the slots between the first and the last one without U-Plane data of the stream are zeros
+    let modulator = OfdmModulator::new(&carrier).expect("Can't modulate the carrier.");
+    let mut waveforms = HashMap::new();
+    for (key, slot) in grids.iter() {
+        let slot_number = modulator.slot_number(*key);
+        for (antenna, eaxc_id) in slot.eaxc_ids.iter().enumerate() {
+            let (writer, last_slot) = match waveforms.entry((slot.dir as u8, *eaxc_id)) {
+                Entry::Occupied(e) => e.into_mut(),
+                Entry::Vacant(e) => {
+                    let name = format!("waveform_{}_du{}_bs{}_cc{}_ru{}.iq", slot.dir, eaxc_id.du_port_id,
+                                       eaxc_id.band_sector_id, eaxc_id.cc_id, eaxc_id.ru_port_id);
+                    e.insert((BufWriter::new(File::create(name)?), slot_number))
+                }
+            };
+            write_iq_i16(writer, &modulator.silence(*last_slot, slot_number))?;
+            write_iq_i16(writer, &modulator.modulate(slot, antenna))?;
+            *last_slot = slot_number;
+        }
+    }
+    println!("waveforms at {} Hz: {} streams", carrier.sample_rate_hz(), waveforms.len());
 
     // save iq data to file
-    let frame_data = ecpri_data.parse_iq_data(MANTISSA);
+    let frame_data = ecpri_data.parse_iq_data(&parse_config.eaxc_config);
     let mut previous_frame = 0;
     let mut frame_start = false;
     for (info, frame) in &frame_data {
-        println!("frame id: {}, subframe_id: {}, slot_id: {}, symbol_id: {}", info.0, info.1, info.2, frame.symb_id);
-        if !frame_start && info.1 == 0 && info.2 == 0 && frame.symb_id == 0 {
+        println!("eAxC: {}, frame id: {}, subframe_id: {}, slot_id: {}, symbol_id: {}", info.0, frame.frame_id, frame.subframe_id, frame.slot_id, frame.symb_id);
+        if !frame_start && frame.subframe_id == 0 && frame.slot_id == 0 && frame.symb_id == 0 {
             frame_start = true;
-            previous_frame = info.0;
+            previous_frame = frame.frame_id;
         }
         if frame_start {
-            if info.0 != previous_frame {
+            if frame.frame_id != previous_frame {
                 break;
             }
             buf_writer.write_fmt(format_args!(
-                "frame_id: {}, subframe_id: {}, slot_id: {}, slot_dir: {}, symbol_id: {}, iq: \n",
-                info.0, info.1, info.2, frame.slot_dir, frame.symb_id
+                "eaxc: {}, frame_id: {}, subframe_id: {}, slot_id: {}, slot_dir: {}, symbol_id: {}, iq: \n",
+                info.0, frame.frame_id, frame.subframe_id, frame.slot_id, frame.slot_dir, frame.symb_id
             )).expect("Failed to write iq head info to file.");
             for iq in frame.iq.iter() {
                 buf_writer.write_fmt(format_args!(
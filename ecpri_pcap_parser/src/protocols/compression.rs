@@ -0,0 +1,757 @@
+use std::collections::HashMap;
+use std::fmt;
+use nom::{
+    number::complete::be_u8,
+    combinator::map as nom_map,
+    sequence::tuple as nom_tuple,
+    multi::count as nom_count,
+    bits::{complete::take as nom_bit_take, bits as nom_bits},
+    IResult,
+};
+use num::complex::Complex;
+use crate::protocols::types;
+use crate::protocols::ecpri::IQPrbuData;
+
+// udCompHdr (8 bits), ORAN-WG4.CUS.0-v02.00 6.3.3.13:
+//      o udIqWidth (4bit). 0’d = 16bit I and Q, 1’d = 1bit I and Q, …,15’d = 15bit I and Q
+//      o udCompMeth (4bit). Compression method applied to the PRBs of the section.
+//
+// The udCompHdr is only carried in the U-Plane section header when the eAxC is configured
+// for dynamic compression (M-Plane), otherwise the RU and DU use a static configuration.
+#[derive(Clone, Copy, PartialEq)]
+pub struct UdCompHdr {
+    pub ud_iq_width: u8,          // 4 bits, 0 means 16 bits
+    pub ud_comp_meth: UdCompMeth, // 4 bits
+}
+
+impl UdCompHdr {
+    pub fn new(iq_width: u8, ud_comp_meth: UdCompMeth) -> Self {
+        Self {
+            ud_iq_width: iq_width & 0x0F,
+            ud_comp_meth,
+        }
+    }
+
+    pub fn parse(data: types::Input) -> types::Result<Self> {
+        nom_map(be_u8, Self::from)(data)
+    }
+
+    // The real bit width of I and Q, 1..16
+    pub fn iq_width(&self) -> u16 {
+        if self.ud_iq_width == 0 { 16 } else { self.ud_iq_width as u16 }
+    }
+
+    // The udCompParam byte exists in front of every PRB for these methods only.
+    pub fn has_comp_param(&self) -> bool {
+        matches!(
+            self.ud_comp_meth,
+            UdCompMeth::BlockFloatingPoint
+            | UdCompMeth::BlockScaling
+            | UdCompMeth::MuLaw
+            | UdCompMeth::BfpSelectiveRe
+        )
+    }
+
+    // Bytes of I/Q samples of one PRB (12 REs)
+    pub fn prb_sample_size(&self) -> usize {
+        self.iq_width() as usize * 2 * 12 / 8
+    }
+}
+
+impl From<u8> for UdCompHdr {
+    fn from(item: u8) -> Self {
+        Self {
+            ud_iq_width: item >> 4,
+            ud_comp_meth: UdCompMeth::from(item & 0x0F),
+        }
+    }
+}
+
+impl fmt::Display for UdCompHdr {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        write!(w, "{}bit {:?}", self.iq_width(), self.ud_comp_meth)
+    }
+}
+
+impl fmt::Debug for UdCompHdr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+// Take 4 bits
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UdCompMeth {
+    NoCompression = 0,          // no compression (no udCompParam)
+    BlockFloatingPoint = 1,     // block floating point (1-byte udCompParam: reserved + exponent)
+    BlockScaling = 2,           // block scaling (1-byte udCompParam: sblockScaler)
+    MuLaw = 3,                  // μ-law (1-byte udCompParam: compBitWidth + compShift)
+    ModulationCompression = 4,  // modulation compression (no udCompParam)
+    BfpSelectiveRe = 5,         // BFP + selective RE sending
+    ModCompSelectiveRe = 6,     // modulation compression + selective RE sending
+    Reserved = 7,               // 7..15 reserved
+}
+
+impl From<u8> for UdCompMeth {
+    fn from(item: u8) -> Self {
+        match item {
+            _ if item == Self::NoCompression as u8 => Self::NoCompression,
+            _ if item == Self::BlockFloatingPoint as u8 => Self::BlockFloatingPoint,
+            _ if item == Self::BlockScaling as u8 => Self::BlockScaling,
+            _ if item == Self::MuLaw as u8 => Self::MuLaw,
+            _ if item == Self::ModulationCompression as u8 => Self::ModulationCompression,
+            _ if item == Self::BfpSelectiveRe as u8 => Self::BfpSelectiveRe,
+            _ if item == Self::ModCompSelectiveRe as u8 => Self::ModCompSelectiveRe,
+            _ if (7..=15).contains(&item) => Self::Reserved,
+            _ => { panic!("UdCompMeth error: Shouldn't come here."); },
+        }
+    }
+}
+
+// How the compression of one eAxC is configured over the M-Plane.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EaxcCompConfig {
+    Static(UdCompHdr),  // udCompHdr is not present in the U-Plane section header
+    Dynamic,            // udCompHdr (and 1 reserved byte) follows numPrbu in every section header
+}
+
+// Per-eAxC (ecpriPcid) compression configuration used when parsing U-Plane messages.
+// Streams without their own entry use the default one.
+#[derive(Clone, Debug)]
+pub struct UPlaneCompConfig {
+    pub default: EaxcCompConfig,
+    pub per_eaxc: HashMap<u16, EaxcCompConfig>,
+}
+
+impl UPlaneCompConfig {
+    pub fn new(default: EaxcCompConfig) -> Self {
+        Self {
+            default,
+            per_eaxc: HashMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, pcid: u16, config: EaxcCompConfig) {
+        self.per_eaxc.insert(pcid, config);
+    }
+
+    pub fn get(&self, pcid: u16) -> EaxcCompConfig {
+        *self.per_eaxc.get(&pcid).unwrap_or(&self.default)
+    }
+}
+
+impl Default for UPlaneCompConfig {
+    // 9 bits block floating point, which is what the DU sends by default.
+    fn default() -> Self {
+        Self::new(EaxcCompConfig::Static(UdCompHdr::new(9, UdCompMeth::BlockFloatingPoint)))
+    }
+}
+
+// Number of REs (subcarriers) in one PRB
+pub const RE_PER_PRB: usize = 12;
+// Full scale of a 16 bits sample, all decompressors output in LSBs of this scale
+pub const FULL_SCALE_16: f32 = 32768.0;
+
+// Read `count` pairs of `width` bits signed (2's complement) I/Q samples,
+// none if the data is shorter than that.
+type BitInput<'a> = (&'a [u8], usize);
+
+pub fn unpack_samples(samples: &[u8], width: u16, count: usize) -> Option<Vec<(i32, i32)>> {
+    fn take_pairs(data: BitInput, width: u16, count: usize) -> IResult<BitInput, Vec<(u32, u32)>> {
+        nom_count(nom_tuple((nom_bit_take(width), nom_bit_take(width))), count)(data)
+    }
+    let parser = move |data| take_pairs(data, width, count);
+    let result: IResult<&[u8], Vec<(u32, u32)>> = nom_bits(parser)(samples);
+    let (_, raw) = result.ok()?;
+    let shift = 32 - width as u32;
+    let fix_two_complement = |i: u32| ((i << shift) as i32) >> shift;
+    Some(raw.iter()
+        .map(|&(i, q)| (fix_two_complement(i), fix_two_complement(q)))
+        .collect())
+}
+
+// Decompression of one compressed block (one PRB for the U-Plane, one bundle of weights
+// for beamforming) back to linear samples.
+// The output is in LSBs of a 16 bits uncompressed sample, so streams with different
+// compression methods and widths can be compared directly.
+pub trait Decompressor {
+    // comp_param: the compression parameter bytes in front of the samples (udCompParam/bfwCompParam),
+    //             empty for methods without parameter.
+    // samples: the compressed I/Q bytes, count: number of complex samples to decode.
+    // None if the samples are cut short.
+    fn decompress(&self, comp_param: &[u8], samples: &[u8], count: usize) -> Option<Vec<Complex<f32>>>;
+
+    fn decompress_prb(&self, prb: &IQPrbuData) -> Option<Vec<Complex<f32>>> {
+        let comp_param = prb.ud_comp_param().map(|p| vec![p]).unwrap_or_default();
+        self.decompress(&comp_param, &prb.iq_sample, RE_PER_PRB)
+    }
+}
+
+// udCompMeth = 0, the samples are udIqWidth bits (8..16 in practice) linear values.
+pub struct NoCompression {
+    pub iq_width: u16,
+}
+
+impl Decompressor for NoCompression {
+    fn decompress(&self, _comp_param: &[u8], samples: &[u8], count: usize) -> Option<Vec<Complex<f32>>> {
+        let scale = (1 << (16 - self.iq_width)) as f32;
+        Some(unpack_samples(samples, self.iq_width, count)?.iter()
+            .map(|&(i, q)| Complex::new(i as f32 * scale, q as f32 * scale))
+            .collect())
+    }
+}
+
+// udCompMeth = 1 (and 5), udCompParam = reserved (4 bits) + exponent (4 bits),
+// sample = mantissa << exponent (ORAN-WG4.CUS.0 Annex A.1.2).
+// A 16 bits mantissa shifted by exponent 15 takes 31 bits, so the exact value always fits in i32.
+pub struct BlockFloatingPoint {
+    pub iq_width: u16,
+}
+
+impl BlockFloatingPoint {
+    // Exact decompressed samples
+    pub fn decode(&self, exponent: u8, samples: &[u8], count: usize) -> Option<Vec<(i32, i32)>> {
+        let exponent = exponent & 0x0F;
+        Some(unpack_samples(samples, self.iq_width, count)?.iter()
+            .map(|&(i, q)| (i << exponent, q << exponent))
+            .collect())
+    }
+
+    // Decompressed samples relative to the 16 bits full scale (1.0 = 2^15),
+    // exponents above 16 - udIqWidth give values beyond full scale.
+    pub fn decode_normalised(&self, exponent: u8, samples: &[u8], count: usize) -> Option<Vec<Complex<f32>>> {
+        Some(self.decode(exponent, samples, count)?.iter()
+            .map(|&(i, q)| Complex::new(i as f32 / FULL_SCALE_16, q as f32 / FULL_SCALE_16))
+            .collect())
+    }
+
+    pub fn decode_prb(&self, prb: &IQPrbuData) -> Option<Vec<(i32, i32)>> {
+        self.decode(prb.exponent, &prb.iq_sample, RE_PER_PRB)
+    }
+}
+
+impl Decompressor for BlockFloatingPoint {
+    fn decompress(&self, comp_param: &[u8], samples: &[u8], count: usize) -> Option<Vec<Complex<f32>>> {
+        let exponent = comp_param.first().cloned().unwrap_or(0);
+        Some(self.decode(exponent, samples, count)?.iter()
+            .map(|&(i, q)| Complex::new(i as f32, q as f32))
+            .collect())
+    }
+}
+
+// udCompMeth = 2, udCompParam = sblockScaler, unsigned Q1.7 fixed point (0 ~ 2).
+// sample = compressed * sblockScaler, where the compressed value is a fraction of full scale.
+pub struct BlockScaling {
+    pub iq_width: u16,
+}
+
+impl Decompressor for BlockScaling {
+    fn decompress(&self, comp_param: &[u8], samples: &[u8], count: usize) -> Option<Vec<Complex<f32>>> {
+        let scaler = comp_param.first().cloned().unwrap_or(0x80) as f32 / 128.0;
+        let scale = scaler * (1 << (16 - self.iq_width)) as f32;
+        Some(unpack_samples(samples, self.iq_width, count)?.iter()
+            .map(|&(i, q)| Complex::new(i as f32 * scale, q as f32 * scale))
+            .collect())
+    }
+}
+
+// udCompMeth = 3, udCompParam = compBitWidth (4 bits) + compShift (4 bits).
+// Every sample is a sign bit + 3 bits segment + (udIqWidth - 4) bits step, the segments are
+// piecewise linear with doubling step size (as G.711 μ-law). After expansion to 16 bits the
+// value is shifted right by compShift, which was applied before the compression.
+// The sign and segment take 4 bits, so udIqWidth must be 4 at least.
+pub struct MuLaw {
+    pub iq_width: u16,
+}
+
+impl MuLaw {
+    pub fn expand(&self, code: i32) -> i32 {
+        let step_bits = self.iq_width as i32 - 4;
+        let magnitude = code.abs() & ((1 << (self.iq_width - 1)) - 1);
+        let segment = magnitude >> step_bits;
+        let step = magnitude & ((1 << step_bits) - 1);
+        let linear = if segment == 0 {
+            step << 1
+        } else {
+            (step | (1 << step_bits)) << segment
+        };
+        // segment 0 .. 7 takes (step_bits + 8) bits, scale it to the 15 bits magnitude of 16 bits
+        let linear = match 7 - step_bits {
+            shift if shift >= 0 => linear << shift,
+            shift => linear >> -shift,
+        };
+        if code < 0 { -linear } else { linear }
+    }
+}
+
+impl Decompressor for MuLaw {
+    fn decompress(&self, comp_param: &[u8], samples: &[u8], count: usize) -> Option<Vec<Complex<f32>>> {
+        let comp_shift = comp_param.first().map(|p| p & 0x0F).unwrap_or(0);
+        let scale = 1.0 / (1u32 << comp_shift) as f32;
+        Some(unpack_samples(samples, self.iq_width, count)?.iter()
+            .map(|&(i, q)| Complex::new(self.expand(i) as f32 * scale, self.expand(q) as f32 * scale))
+            .collect())
+    }
+}
+
+// Constellation parameters of modulation compression, from C-Plane section extension 4
+// (csf + modCompScaler) or 5 (mcScaleReMask + csf + mcScaleOffset per RE set).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ModCompParams {
+    pub csf: bool,               // constellation shift flag, 1 bit
+    pub mod_comp_scaler: u16,    // 15 bits, 11 bits mantissa + 4 bits exponent
+}
+
+impl ModCompParams {
+    // modCompScaler = mantissa / 2^11 * 2^-exponent
+    pub fn scaler(&self) -> f32 {
+        let mantissa = (self.mod_comp_scaler >> 4) & 0x7FF;
+        let exponent = self.mod_comp_scaler & 0x0F;
+        mantissa as f32 / 2048.0 / (1u32 << exponent) as f32
+    }
+}
+
+impl Default for ModCompParams {
+    // Shifted constellation without scaling (scaler = 1.0)
+    fn default() -> Self {
+        Self {
+            csf: true,
+            mod_comp_scaler: 0x7FF << 4,
+        }
+    }
+}
+
+// udCompMeth = 4 (and 6), every I/Q is udIqWidth bits (1 for QPSK, 2 for 16QAM, ...) which
+// addresses a constellation point, the point is shifted by half a step if csf is set and
+// then multiplied by modCompScaler.
+pub struct ModulationCompression {
+    pub iq_width: u16,
+    pub params: ModCompParams,
+}
+
+impl Decompressor for ModulationCompression {
+    fn decompress(&self, _comp_param: &[u8], samples: &[u8], count: usize) -> Option<Vec<Complex<f32>>> {
+        let shift = if self.params.csf { 0.5 } else { 0.0 };
+        let scale = self.params.scaler() * FULL_SCALE_16 / (1u32 << (self.iq_width - 1)) as f32;
+        Some(unpack_samples(samples, self.iq_width, count)?.iter()
+            .map(|&(i, q)| Complex::new((i as f32 + shift) * scale, (q as f32 + shift) * scale))
+            .collect())
+    }
+}
+
+// bfwCompMeth = 4, beamspace compression of beamforming weights.
+// bfwCompParam = activeBeamspaceCoefficientMask (K bits, padded to bytes) + reserved (4 bits) +
+// exponent (4 bits), only the active coefficients are sent as BFP mantissas, the others are zero.
+pub struct Beamspace {
+    pub iq_width: u16,
+}
+
+impl Decompressor for Beamspace {
+    // count is K, the number of TRX (beamspace coefficients)
+    fn decompress(&self, comp_param: &[u8], samples: &[u8], count: usize) -> Option<Vec<Complex<f32>>> {
+        let mask_len = count.div_ceil(8);
+        let exponent = comp_param.get(mask_len).map(|p| p & 0x0F).unwrap_or(0);
+        // coefficients beyond a truncated mask are inactive
+        let is_active = |k: usize| comp_param.get(k / 8).is_some_and(|m| m & (0x80 >> (k % 8)) != 0);
+        let active = (0..count).filter(|&k| is_active(k)).count();
+        let scale = (1u32 << exponent) as f32;
+        let mut values = unpack_samples(samples, self.iq_width, active)?.into_iter();
+        (0..count)
+            .map(|k| if is_active(k) {
+                values.next().map(|(i, q)| Complex::new(i as f32 * scale, q as f32 * scale))
+            } else {
+                Some(Complex::new(0.0, 0.0))
+            })
+            .collect()
+    }
+}
+
+// Decompressor of the U-Plane samples described by udCompHdr, None for the reserved methods
+// and μ-law below 4 bits since the udCompHdr comes from the wire.
+// Modulation compression needs the constellation parameters from the C-Plane message
+// (section extension 4 or 5), the default (shifted, unscaled) constellation is used if they are unknown.
+pub fn decompressor(ud_comp_hdr: &UdCompHdr, mod_comp: Option<ModCompParams>) -> Option<Box<dyn Decompressor>> {
+    let iq_width = ud_comp_hdr.iq_width();
+    let decompressor: Box<dyn Decompressor> = match ud_comp_hdr.ud_comp_meth {
+        UdCompMeth::NoCompression => Box::new(NoCompression { iq_width }),
+        UdCompMeth::BlockFloatingPoint | UdCompMeth::BfpSelectiveRe => Box::new(BlockFloatingPoint { iq_width }),
+        UdCompMeth::BlockScaling => Box::new(BlockScaling { iq_width }),
+        UdCompMeth::MuLaw if iq_width >= 4 => Box::new(MuLaw { iq_width }),
+        UdCompMeth::ModulationCompression | UdCompMeth::ModCompSelectiveRe => Box::new(ModulationCompression {
+            iq_width,
+            params: mod_comp.unwrap_or_default(),
+        }),
+        UdCompMeth::MuLaw | UdCompMeth::Reserved => return None,
+    };
+    Some(decompressor)
+}
+
+// Write (I, Q) pairs as `width` bits 2's complement values, MSB first, padded to bytes.
+pub fn pack_samples(values: &[(i32, i32)], width: u16) -> Vec<u8> {
+    let mut data = Vec::with_capacity((values.len() * 2 * width as usize).div_ceil(8));
+    let mut acc: u32 = 0;
+    let mut acc_bits = 0;
+    let mask = (1u32 << width) - 1;
+    for &(i, q) in values {
+        for &v in [i, q].iter() {
+            acc = (acc << width) | (v as u32 & mask);
+            acc_bits += width;
+            while acc_bits >= 8 {
+                acc_bits -= 8;
+                data.push((acc >> acc_bits) as u8);
+            }
+        }
+    }
+    if acc_bits > 0 {
+        data.push((acc << (8 - acc_bits)) as u8);
+    }
+    data
+}
+
+// Saturate to the range of `width` bits signed value
+fn saturate(v: i32, width: u16) -> i32 {
+    let max = (1i32 << (width - 1)) - 1;
+    v.clamp(-max - 1, max)
+}
+
+// Compression of one block of samples (16 bits LSBs, like the Decompressor output),
+// the reverse direction of Decompressor, used to build U-Plane payloads.
+pub trait Compressor {
+    // Return the compression parameter bytes (empty if none) and the compressed samples.
+    fn compress(&self, samples: &[Complex<f32>]) -> (Vec<u8>, Vec<u8>);
+
+    // One PRB as it is sent in the U-Plane message: udCompParam + I/Q samples.
+    fn compress_prb(&self, res: &[Complex<f32>]) -> Vec<u8> {
+        assert_eq!(res.len(), RE_PER_PRB, "One PRB has 12 REs.");
+        let (mut data, samples) = self.compress(res);
+        data.extend(samples);
+        data
+    }
+}
+
+impl Compressor for NoCompression {
+    fn compress(&self, samples: &[Complex<f32>]) -> (Vec<u8>, Vec<u8>) {
+        let scale = (1 << (16 - self.iq_width)) as f32;
+        let values = samples.iter()
+            .map(|s| (
+                saturate((s.re / scale).round() as i32, self.iq_width),
+                saturate((s.im / scale).round() as i32, self.iq_width),
+            ))
+            .collect::<Vec<_>>();
+        (Vec::new(), pack_samples(&values, self.iq_width))
+    }
+}
+
+impl BlockFloatingPoint {
+    // The smallest exponent which keeps every rounded mantissa inside udIqWidth bits,
+    // that is the one with the least quantization error.
+    pub fn optimal_exponent(&self, samples: &[Complex<f32>]) -> u8 {
+        let (lowest, highest) = samples.iter()
+            .flat_map(|s| vec![s.re, s.im])
+            .fold((0.0f32, 0.0f32), |(l, h), v| (l.min(v), h.max(v)));
+        let max = ((1i32 << (self.iq_width - 1)) - 1) as f32;
+        (0..15u8)
+            .find(|&e| {
+                let scale = (1u32 << e) as f32;
+                (highest / scale).round() <= max && (lowest / scale).round() >= -max - 1.0
+            })
+            .unwrap_or(15)
+    }
+
+    pub fn encode(&self, exponent: u8, samples: &[Complex<f32>]) -> Vec<u8> {
+        let scale = (1u32 << exponent) as f32;
+        let values = samples.iter()
+            .map(|s| (
+                saturate((s.re / scale).round() as i32, self.iq_width),
+                saturate((s.im / scale).round() as i32, self.iq_width),
+            ))
+            .collect::<Vec<_>>();
+        pack_samples(&values, self.iq_width)
+    }
+}
+
+impl Compressor for BlockFloatingPoint {
+    fn compress(&self, samples: &[Complex<f32>]) -> (Vec<u8>, Vec<u8>) {
+        let exponent = self.optimal_exponent(samples);
+        (vec![exponent], self.encode(exponent, samples))
+    }
+}
+
+impl Compressor for BlockScaling {
+    // sblockScaler is chosen so the largest sample maps to the largest compressed value.
+    fn compress(&self, samples: &[Complex<f32>]) -> (Vec<u8>, Vec<u8>) {
+        let max_abs = samples.iter()
+            .flat_map(|s| vec![s.re, s.im])
+            .fold(0.0f32, |m, v| m.max(v.abs()));
+        let max = ((1i32 << (self.iq_width - 1)) - 1) as f32;
+        let unit = (1 << (16 - self.iq_width)) as f32;
+        let scaler = ((max_abs / (max * unit) * 128.0).ceil() as i32).clamp(1, 255) as u8;
+        let scale = scaler as f32 / 128.0 * unit;
+        let values = samples.iter()
+            .map(|s| (
+                saturate((s.re / scale).round() as i32, self.iq_width),
+                saturate((s.im / scale).round() as i32, self.iq_width),
+            ))
+            .collect::<Vec<_>>();
+        (vec![scaler], pack_samples(&values, self.iq_width))
+    }
+}
+
+impl MuLaw {
+    // The code whose expansion is the nearest to the value, expand() is monotonic so
+    // the code is found with a binary search over the magnitudes.
+    pub fn compress_value(&self, value: i32) -> i32 {
+        let magnitude = value.abs();
+        let (mut low, mut high) = (0, (1 << (self.iq_width - 1)) - 1);
+        while low < high {
+            let mid = (low + high + 1) / 2;
+            if self.expand(mid) <= magnitude { low = mid; } else { high = mid - 1; }
+        }
+        if low < (1 << (self.iq_width - 1)) - 1
+            && self.expand(low + 1) - magnitude < magnitude - self.expand(low) {
+            low += 1;
+        }
+        if value < 0 { -low } else { low }
+    }
+}
+
+impl Compressor for MuLaw {
+    // compShift = 0 and compBitWidth = udIqWidth
+    fn compress(&self, samples: &[Complex<f32>]) -> (Vec<u8>, Vec<u8>) {
+        let values = samples.iter()
+            .map(|s| (
+                self.compress_value((s.re.round() as i32).clamp(-0x7FFF, 0x7FFF)),
+                self.compress_value((s.im.round() as i32).clamp(-0x7FFF, 0x7FFF)),
+            ))
+            .collect::<Vec<_>>();
+        let comp_param = (self.iq_width as u8 & 0x0F) << 4;
+        (vec![comp_param], pack_samples(&values, self.iq_width))
+    }
+}
+
+// Compressor for the udCompHdr, only the methods with a self-contained block are supported,
+// None for the others (modulation compression, selective RE and reserved methods).
+pub fn compressor(ud_comp_hdr: &UdCompHdr) -> Option<Box<dyn Compressor>> {
+    let iq_width = ud_comp_hdr.iq_width();
+    let compressor: Box<dyn Compressor> = match ud_comp_hdr.ud_comp_meth {
+        UdCompMeth::NoCompression => Box::new(NoCompression { iq_width }),
+        UdCompMeth::BlockFloatingPoint => Box::new(BlockFloatingPoint { iq_width }),
+        UdCompMeth::BlockScaling => Box::new(BlockScaling { iq_width }),
+        UdCompMeth::MuLaw if iq_width >= 4 => Box::new(MuLaw { iq_width }),
+        _ => return None,
+    };
+    Some(compressor)
+}
+
+// Signal to quantization noise ratio in dB of the decoded samples against the reference.
+pub fn sqnr_db(reference: &[Complex<f32>], decoded: &[Complex<f32>]) -> f32 {
+    let signal: f32 = reference.iter().map(|s| s.norm_sqr()).sum();
+    let noise: f32 = reference.iter().zip(decoded.iter()).map(|(r, d)| (r - d).norm_sqr()).sum();
+    10.0 * (signal / noise).log10()
+}
+
+// Compress and decompress every PRB (12 REs each) with the udCompHdr and return the SQNR,
+// to compare the udIqWidth/udCompMeth candidates on the same IQ data.
+// None if there is no compressor for the udCompHdr.
+pub fn round_trip_sqnr(ud_comp_hdr: &UdCompHdr, prbs: &[Vec<Complex<f32>>]) -> Option<f32> {
+    let comp = compressor(ud_comp_hdr)?;
+    let decomp = decompressor(ud_comp_hdr, None)?;
+    let mut reference = Vec::new();
+    let mut decoded = Vec::new();
+    for prb in prbs.iter() {
+        let (comp_param, samples) = comp.compress(prb);
+        decoded.extend(decomp.decompress(&comp_param, &samples, prb.len())?);
+        reference.extend(prb.iter().cloned());
+    }
+    Some(sqnr_db(&reference, &decoded))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ud_comp_hdr() {
+        let (_, hdr) = UdCompHdr::parse(&[0x91]).unwrap();
+        assert_eq!(hdr.iq_width(), 9);
+        assert_eq!(hdr.ud_comp_meth, UdCompMeth::BlockFloatingPoint);
+        assert_eq!(hdr.prb_sample_size(), 27);
+
+        let (_, hdr) = UdCompHdr::parse(&[0x00]).unwrap();
+        assert_eq!(hdr.iq_width(), 16);
+        assert_eq!(hdr.ud_comp_meth, UdCompMeth::NoCompression);
+        assert!(!hdr.has_comp_param());
+        assert_eq!(hdr.prb_sample_size(), 48);
+    }
+
+    #[test]
+    fn per_eaxc_fallback() {
+        let mut config = UPlaneCompConfig::default();
+        config.insert(0x80, EaxcCompConfig::Static(UdCompHdr::new(16, UdCompMeth::NoCompression)));
+        assert_eq!(config.get(0x80), EaxcCompConfig::Static(UdCompHdr::new(0, UdCompMeth::NoCompression)));
+        assert_eq!(config.get(0x00), EaxcCompConfig::Static(UdCompHdr::new(9, UdCompMeth::BlockFloatingPoint)));
+    }
+
+    #[test]
+    fn unpack_9bit_samples() {
+        // I = 0x0FF (255), Q = 0x100 (-256), then I = -1, Q = 1, padded to 5 bytes
+        let samples = [0x7F, 0xC0, 0x3F, 0xE0, 0x10];
+        assert_eq!(unpack_samples(&samples, 9, 2), Some(vec![(255, -256), (-1, 1)]));
+        // a third pair would need 2 more bytes
+        assert_eq!(unpack_samples(&samples, 9, 3), None);
+    }
+
+    #[test]
+    fn decompress_methods() {
+        let bfp = BlockFloatingPoint { iq_width: 9 };
+        let out = bfp.decompress(&[0x03], &[0x7F, 0xC0, 0x3F, 0xE0, 0x10], 2).unwrap();
+        assert_eq!(out, vec![Complex::new(2040.0, -2048.0), Complex::new(-8.0, 8.0)]);
+
+        let none = NoCompression { iq_width: 16 };
+        let out = none.decompress(&[], &[0x80, 0x00, 0x7F, 0xFF], 1).unwrap();
+        assert_eq!(out, vec![Complex::new(-32768.0, 32767.0)]);
+
+        // 16QAM, I = 1 (0.75), Q = -2 (-0.75) with csf
+        let modcomp = ModulationCompression { iq_width: 2, params: ModCompParams::default() };
+        let out = modcomp.decompress(&[], &[0x60], 1).unwrap();
+        let scaler = ModCompParams::default().scaler() * 32768.0;
+        assert_eq!(out, vec![Complex::new(0.75 * scaler, -0.75 * scaler)]);
+
+        let mu_law = MuLaw { iq_width: 8 };
+        assert_eq!(mu_law.expand(0), 0);
+        assert!(mu_law.expand(0x7F) > mu_law.expand(0x70));
+        assert_eq!(mu_law.expand(-0x35), -mu_law.expand(0x35));
+        assert_eq!(mu_law.expand(0x7F), 0x7C00);
+        assert_eq!(MuLaw { iq_width: 16 }.expand(0x7FFF), 0x7FFC);
+    }
+
+    #[test]
+    fn bfp_decode_all_widths_and_exponents() {
+        for width in 1..=16u16 {
+            let min = -(1i32 << (width - 1));
+            let max = (1i32 << (width - 1)) - 1;
+            let values = vec![(min, max), (max, min), (-1, 0), (0, -1), (min / 2, max / 2)];
+            let samples = pack_samples(&values, width);
+            let bfp = BlockFloatingPoint { iq_width: width };
+            for exponent in 0..=15u8 {
+                let decoded = bfp.decode(exponent, &samples, values.len()).unwrap();
+                let expected = values.iter()
+                    .map(|&(i, q)| (i * (1 << exponent), q * (1 << exponent)))
+                    .collect::<Vec<_>>();
+                assert_eq!(decoded, expected, "width {}, exponent {}", width, exponent);
+
+                let normalised = bfp.decode_normalised(exponent, &samples, values.len()).unwrap();
+                for (n, &(i, q)) in normalised.iter().zip(expected.iter()) {
+                    assert_eq!(n.re, i as f32 / 32768.0);
+                    assert_eq!(n.im, q as f32 / 32768.0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bfp_decode_prb() {
+        let values = (0..12).map(|n| (n * 20 - 256, 255 - n * 20)).collect::<Vec<_>>();
+        let ud_comp_hdr = UdCompHdr::new(9, UdCompMeth::BlockFloatingPoint);
+        let mut data = vec![0x0F];
+        data.extend(pack_samples(&values, 9));
+        let (remain, prb) = IQPrbuData::parse(&data, &ud_comp_hdr).unwrap();
+        assert!(remain.is_empty());
+        let decoded = BlockFloatingPoint { iq_width: 9 }.decode_prb(&prb).unwrap();
+        assert_eq!(decoded[0], (-256 << 15, 255 << 15));
+        assert_eq!(decoded[11], (-36 << 15, 35 << 15));
+    }
+
+    #[test]
+    fn decompress_beamspace() {
+        // K = 4, coefficients 1 and 3 are active, exponent 1
+        let beamspace = Beamspace { iq_width: 8 };
+        let out = beamspace.decompress(&[0x50, 0x01], &[0x01, 0xFF, 0x02, 0xFE], 4);
+        assert_eq!(out.unwrap(), vec![
+            Complex::new(0.0, 0.0), Complex::new(2.0, -2.0),
+            Complex::new(0.0, 0.0), Complex::new(4.0, -4.0),
+        ]);
+        // the mask is cut short: the missing coefficients are inactive
+        let out = beamspace.decompress(&[], &[], 4);
+        assert_eq!(out, Some(vec![Complex::new(0.0, 0.0); 4]));
+        // the samples are cut short
+        assert_eq!(beamspace.decompress(&[0x50, 0x01], &[0x01, 0xFF], 4), None);
+    }
+
+    #[test]
+    fn reserved_method_has_no_decompressor() {
+        assert!(decompressor(&UdCompHdr::from(0x97), None).is_none());
+        let params = ModCompParams { csf: false, mod_comp_scaler: 0x400 << 4 };
+        let modcomp = decompressor(&UdCompHdr::new(2, UdCompMeth::ModulationCompression), Some(params)).unwrap();
+        // 16QAM, I = 1, Q = -2 without shift, scaled by 0.5
+        assert_eq!(modcomp.decompress(&[], &[0x60], 1), Some(vec![Complex::new(8192.0, -16384.0)]));
+    }
+
+    #[test]
+    fn mu_law_needs_4_bits() {
+        // sign + 3 bits segment, no room for the step below 4 bits
+        for width in 1..4 {
+            assert!(decompressor(&UdCompHdr::new(width, UdCompMeth::MuLaw), None).is_none());
+            assert!(compressor(&UdCompHdr::new(width, UdCompMeth::MuLaw)).is_none());
+        }
+        let mu_law = decompressor(&UdCompHdr::new(4, UdCompMeth::MuLaw), None).unwrap();
+        // I = 7 and Q = -7: segment 7 without step
+        assert_eq!(mu_law.decompress(&[], &[0x79], 1), Some(vec![Complex::new(16384.0, -16384.0)]));
+    }
+
+    #[test]
+    fn methods_without_compressor() {
+        assert!(compressor(&UdCompHdr::new(4, UdCompMeth::ModulationCompression)).is_none());
+        assert!(compressor(&UdCompHdr::from(0x97)).is_none());
+        assert_eq!(round_trip_sqnr(&UdCompHdr::new(2, UdCompMeth::ModulationCompression), &test_prbs(1.0)), None);
+    }
+
+    // Pseudo random QAM-like samples with Gaussian-ish amplitude, deterministic for the test.
+    fn test_prbs(amplitude: f32) -> Vec<Vec<Complex<f32>>> {
+        let mut seed: u32 = 0x1234_5678;
+        let mut next = || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            ((seed >> 8) as f32 / (1u32 << 24) as f32) - 0.5
+        };
+        (0..50).map(|_| (0..12).map(|_| Complex::new(next() * amplitude, next() * amplitude)).collect()).collect()
+    }
+
+    #[test]
+    fn pack_unpack_round_trip() {
+        for width in 1..=16u16 {
+            let max = (1i32 << (width - 1)) - 1;
+            let values = vec![(-max - 1, max), (0, -1), (max / 3, -max / 5)];
+            assert_eq!(unpack_samples(&pack_samples(&values, width), width, values.len()), Some(values));
+        }
+    }
+
+    #[test]
+    fn bfp_optimal_exponent() {
+        let bfp = BlockFloatingPoint { iq_width: 9 };
+        assert_eq!(bfp.optimal_exponent(&[Complex::new(255.0, -256.0)]), 0);
+        assert_eq!(bfp.optimal_exponent(&[Complex::new(256.0, 0.0)]), 1);
+        assert_eq!(bfp.optimal_exponent(&[Complex::new(-32768.0, 100.0)]), 7);
+
+        let prb = test_prbs(20000.0).remove(0);
+        let compressed = bfp.compress_prb(&prb);
+        assert_eq!(compressed.len(), 28);
+        let (_, parsed) = IQPrbuData::parse(&compressed, &UdCompHdr::new(9, UdCompMeth::BlockFloatingPoint)).unwrap();
+        assert_eq!(parsed.exponent, bfp.optimal_exponent(&prb));
+    }
+
+    #[test]
+    fn round_trip_quality() {
+        let prbs = test_prbs(20000.0);
+        let bfp_9 = round_trip_sqnr(&UdCompHdr::new(9, UdCompMeth::BlockFloatingPoint), &prbs).unwrap();
+        let bfp_14 = round_trip_sqnr(&UdCompHdr::new(14, UdCompMeth::BlockFloatingPoint), &prbs).unwrap();
+        let none_16 = round_trip_sqnr(&UdCompHdr::new(16, UdCompMeth::NoCompression), &prbs).unwrap();
+        let scaling_9 = round_trip_sqnr(&UdCompHdr::new(9, UdCompMeth::BlockScaling), &prbs).unwrap();
+        let mu_law_9 = round_trip_sqnr(&UdCompHdr::new(9, UdCompMeth::MuLaw), &prbs).unwrap();
+        // about 6 dB per bit
+        assert!(bfp_9 > 40.0 && bfp_9 < 55.0, "{}", bfp_9);
+        assert!(bfp_14 > bfp_9 + 25.0, "{}", bfp_14);
+        assert!(none_16 > 80.0, "{}", none_16);
+        assert!(scaling_9 > 40.0, "{}", scaling_9);
+        assert!(mu_law_9 > 30.0, "{}", mu_law_9);
+    }
+}
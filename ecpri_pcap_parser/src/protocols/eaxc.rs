@@ -0,0 +1,133 @@
+use std::fmt;
+
+// eAxC ID (16 bits), carried in ecpriPcid (U-Plane) and ecpriRtcid (C-Plane), ORAN-WG4.CUS.0 3.1.3.1.6:
+//   :DU_port_ID, distinguishes the processing units of the DU
+//   :BandSector_ID, aggregated cell identifier (band and sector)
+//   :CC_ID, component carrier
+//   :RU_port_ID, logical flow (layer, antenna, spatial stream or PRACH/SRS)
+// The bit widths of the four fields are not fixed, they are configured by the M-Plane
+// (o-ran-uplane-conf eaxc-id bitmasks), DU_port_ID takes the MSBs and RU_port_ID the LSBs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EaxcIdConfig {
+    pub du_port_mask: u16,
+    pub band_sector_mask: u16,
+    pub cc_mask: u16,
+    pub ru_port_mask: u16,
+}
+
+impl EaxcIdConfig {
+    // Contiguous fields from the widths, RU_port_ID starts at bit 0.
+    pub fn new(du_port_bits: u8, band_sector_bits: u8, cc_bits: u8, ru_port_bits: u8) -> Self {
+        let total = du_port_bits + band_sector_bits + cc_bits + ru_port_bits;
+        assert!(total <= 16, "eAxC ID fields take {} bits, only 16 available.", total);
+        let mask = |bits: u8, shift: u8| (((1u32 << bits) - 1) << shift) as u16;
+        Self {
+            ru_port_mask: mask(ru_port_bits, 0),
+            cc_mask: mask(cc_bits, ru_port_bits),
+            band_sector_mask: mask(band_sector_bits, ru_port_bits + cc_bits),
+            du_port_mask: mask(du_port_bits, ru_port_bits + cc_bits + band_sector_bits),
+        }
+    }
+
+    // The M-Plane bitmasks, every mask must be contiguous and they must not overlap.
+    pub fn from_bitmasks(du_port_mask: u16, band_sector_mask: u16, cc_mask: u16, ru_port_mask: u16) -> Option<Self> {
+        let masks = [du_port_mask, band_sector_mask, cc_mask, ru_port_mask];
+        let contiguous = |m: u16| {
+            let v = if m == 0 { 0 } else { (m >> m.trailing_zeros()) as u32 };
+            v & (v + 1) == 0
+        };
+        let overlapping = (0..4).any(|i| (i + 1..4).any(|j| masks[i] & masks[j] != 0));
+        if masks.iter().all(|&m| contiguous(m)) && !overlapping {
+            Some(Self { du_port_mask, band_sector_mask, cc_mask, ru_port_mask })
+        } else {
+            None
+        }
+    }
+
+    pub fn decode(&self, eaxc_id: u16) -> EaxcId {
+        let field = |mask: u16| if mask == 0 { 0 } else { (eaxc_id & mask) >> mask.trailing_zeros() };
+        EaxcId {
+            du_port_id: field(self.du_port_mask),
+            band_sector_id: field(self.band_sector_mask),
+            cc_id: field(self.cc_mask),
+            ru_port_id: field(self.ru_port_mask),
+        }
+    }
+
+    pub fn encode(&self, eaxc_id: &EaxcId) -> u16 {
+        let field = |value: u16, mask: u16| if mask == 0 { 0 } else { (value << mask.trailing_zeros()) & mask };
+        field(eaxc_id.du_port_id, self.du_port_mask)
+            | field(eaxc_id.band_sector_id, self.band_sector_mask)
+            | field(eaxc_id.cc_id, self.cc_mask)
+            | field(eaxc_id.ru_port_id, self.ru_port_mask)
+    }
+}
+
+impl Default for EaxcIdConfig {
+    // The split of the O-RAN M-Plane examples: 2 bits DU port, 6 bits band sector, 4 bits CC, 4 bits RU port
+    fn default() -> Self {
+        EaxcIdConfig::new(2, 6, 4, 4)
+    }
+}
+
+// Ordered by DU port, band sector, carrier and RU port, so the reports group the
+// antenna ports of the same carrier together.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EaxcId {
+    pub du_port_id: u16,
+    pub band_sector_id: u16,
+    pub cc_id: u16,
+    pub ru_port_id: u16,
+}
+
+impl fmt::Display for EaxcId {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            w,
+            "DU{}/BS{}/CC{}/RU{}",
+            self.du_port_id,
+            self.band_sector_id,
+            self.cc_id,
+            self.ru_port_id,
+        )
+    }
+}
+
+impl fmt::Debug for EaxcId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eaxc_id_split() {
+        let config = EaxcIdConfig::new(2, 6, 4, 4);
+        assert_eq!(config.du_port_mask, 0xC000);
+        assert_eq!(config.band_sector_mask, 0x3F00);
+        assert_eq!(config.cc_mask, 0x00F0);
+        assert_eq!(config.ru_port_mask, 0x000F);
+        let eaxc_id = config.decode(0x8123);
+        assert_eq!(eaxc_id, EaxcId { du_port_id: 2, band_sector_id: 1, cc_id: 2, ru_port_id: 3 });
+        assert_eq!(config.encode(&eaxc_id), 0x8123);
+
+        let config = EaxcIdConfig::new(0, 0, 3, 5);
+        let eaxc_id = config.decode(0x00FF);
+        assert_eq!(eaxc_id, EaxcId { du_port_id: 0, band_sector_id: 0, cc_id: 7, ru_port_id: 31 });
+        assert_eq!(format!("{}", eaxc_id), "DU0/BS0/CC7/RU31");
+    }
+
+    #[test]
+    fn eaxc_id_bitmasks() {
+        let config = EaxcIdConfig::from_bitmasks(0xF000, 0x0F00, 0x00F0, 0x000F).unwrap();
+        assert_eq!(config, EaxcIdConfig::new(4, 4, 4, 4));
+        for pcid in (0..=0xFFFFu32).step_by(97) {
+            assert_eq!(config.encode(&config.decode(pcid as u16)), pcid as u16);
+        }
+        assert!(EaxcIdConfig::from_bitmasks(0xF000, 0x0F00, 0x00F0, 0x000A).is_none());
+        assert!(EaxcIdConfig::from_bitmasks(0xF000, 0x1F00, 0x00F0, 0x000F).is_none());
+    }
+}
@@ -11,12 +11,18 @@ use nom::{
     bits::{complete::take as nom_bit_take, bits as nom_bits},
 };
 use crate::protocols::types;
+use num::complex::Complex;
+use crate::protocols::compression::{UdCompHdr, EaxcCompConfig, UPlaneCompConfig, decompressor};
+use crate::protocols::section_extension::SectionExtension;
+use crate::protocols::eaxc::{EaxcId, EaxcIdConfig};
+use crate::protocols::ethernet::SUPPORTED;
 
 const ECPRI_MAGIC_NUM: u16 = 0xAEFE;
 
 // Use for U-plane and C-Plane
 // eCPRI transport header also has another name: eCPRI common header
 // EcpriCommonHeader takes 4 bytes totally
+#[derive(Clone, PartialEq)]
 pub struct CommonHeader {
                         // The attribute of revision, reserved and concatenation altogather occupy 1 byte.
     pub revision: u8,   // Only the first 4 bits were used within one byte.
@@ -27,7 +33,7 @@ pub struct CommonHeader {
     pub message_type: u8, // the eCPRI message type, 1 byte
     pub payload_size: u16, // The size in bytes of payload part corresponding the eCPRI message, 2 bytes
     pub pcid: u16,         // 2 bytes
-    pub seqid: u16,        // 2 bytes
+    pub seqid: u16,        // 2 bytes, see EcpriSeqId
 }
 
 impl CommonHeader {
@@ -36,7 +42,7 @@ impl CommonHeader {
             nom_tuple((be_u8, be_u8, be_u16, be_u16, be_u16)),
             |(byte_u8, message_type, payload_size, pcid, seqid)| Self {
                 revision: byte_u8 >> 4,
-                reserved: (byte_u8 & 0xE0) >> 1,
+                reserved: (byte_u8 >> 1) & 0x07,
                 concatenation: byte_u8 & 0x01,
                 message_type,
                 payload_size,
@@ -46,14 +52,15 @@ impl CommonHeader {
         )(data)
     }
 
-    // The eAxC comprises data of one carrier related to one specific antenna (array)
-    // pub fn get_eaxc_id(&self) -> usize {
-    //     // the first 8 bit of seqid is the eAxC id
-    //     // SequenceID: wrap-around individual per c_eAxC
-    //     // Ebit: last message in subseq, for Application layer fragm, Ebit=1, SubseqID = 0
-    //     // SubSequenceID: value = 0
-    //     (self.seqid >> 8) as usize
-    // }
+    // The eAxC comprises data of one carrier related to one specific antenna (array),
+    // ecpriPcid (U-Plane) and ecpriRtcid (C-Plane) are both the eAxC ID.
+    pub fn eaxc_id(&self, config: &EaxcIdConfig) -> EaxcId {
+        config.decode(self.pcid)
+    }
+
+    pub fn seq_id(&self) -> EcpriSeqId {
+        EcpriSeqId::from(self.seqid)
+    }
 }
 
 impl fmt::Display for CommonHeader {
@@ -78,6 +85,58 @@ impl fmt::Debug for CommonHeader {
     }
 }
 
+// ecpriSeqid (16 bits), ORAN-WG4.CUS.0 3.1.3.1.7:
+//   :SequenceID (8bit), wraps around, individual per eAxC and per plane
+//   :E (1bit), 1 = last fragment of the message (or not fragmented)
+//   :SubsequenceID (7bit), counts the fragments of one message from 0
+#[derive(Clone, Copy, PartialEq)]
+pub struct EcpriSeqId {
+    pub sequence_id: u8,     // 8 bits
+    pub e_bit: u8,           // 1 bit
+    pub subsequence_id: u8,  // 7 bits
+}
+
+impl EcpriSeqId {
+    // A message that fits in one packet: E = 1, SubsequenceID = 0
+    pub fn is_unfragmented(&self) -> bool {
+        self.e_bit == 1 && self.subsequence_id == 0
+    }
+}
+
+impl From<u16> for EcpriSeqId {
+    fn from(item: u16) -> Self {
+        Self {
+            sequence_id: (item >> 8) as u8,
+            e_bit: ((item >> 7) & 0x1) as u8,
+            subsequence_id: (item & 0x7F) as u8,
+        }
+    }
+}
+
+impl From<EcpriSeqId> for u16 {
+    fn from(item: EcpriSeqId) -> Self {
+        ((item.sequence_id as u16) << 8) | ((item.e_bit as u16 & 0x1) << 7) | (item.subsequence_id as u16 & 0x7F)
+    }
+}
+
+impl fmt::Display for EcpriSeqId {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            w,
+            "{:02X}, {:01X}, {:02X}",
+            self.sequence_id,
+            self.e_bit,
+            self.subsequence_id,
+        )
+    }
+}
+
+impl fmt::Debug for EcpriSeqId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
 //Timing header fields:
 //   :D (1bit), data direction
 //   :Ver (3bit), value 1 set to indicate 1st protocol version 
@@ -88,6 +147,7 @@ impl fmt::Debug for CommonHeader {
 //   :symbolID (6bit). This parameter identifies the first symbol number with slot, for which the information of this message is applied to.
 //
 // Common to all sections in packet
+#[derive(PartialEq)]
 pub struct TimingHeader {
     pub dir: DataDirection,    // 1 bit
     pub payload_ver: u8,       // 3 bits    
@@ -147,7 +207,7 @@ impl fmt::Debug for TimingHeader {
 }
 
 // Take just 1 bit
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq)]
 pub enum DataDirection {
     UL = 0,  // Rx/Ul = 0
     DL = 1,  // Tx/Dl = 1
@@ -165,7 +225,10 @@ impl From<u8> for DataDirection {
 
 impl fmt::Display for DataDirection {
     fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
-        write!(w, "{:?}", self)
+        match self {
+            Self::UL => write!(w, "UL"),
+            Self::DL => write!(w, "DL"),
+        }
     }
 }
 
@@ -176,25 +239,53 @@ impl fmt::Debug for DataDirection {
 }
 
 // Take 4 bits
-#[derive(Clone, Copy)]
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, PartialEq)]
 pub enum FilterIndex {
-    NO_FILTER = 0,  // no filter = 0
-    NR_PRACH = 3,   // NR PRACH Format A1,A2,....,C2 
+    NO_FILTER,      // no filter = 0
+    PRACH_1_25,     // 1: PRACH Format 0, 1, 2 (1.25 kHz)
+    PRACH_5,        // 2: PRACH Format 3 (5 kHz)
+    NR_PRACH,       // 3: NR PRACH Format A1,A2,....,C2
+    NPRACH,         // 4: NPRACH Format 0, 1 (3.75 kHz)
+    Reserved(u8),   // 5..15
 }
 
 impl From<u8> for FilterIndex {
     fn from(item: u8) -> Self {
         match item {
-            _ if item == Self::NO_FILTER as u8 => Self::NO_FILTER,
-            _ if item == Self::NR_PRACH as u8 => Self::NR_PRACH,
-            item => { panic!("FilterIndex: {}, Error: Shouldn't come here.", item); },
+            0 => Self::NO_FILTER,
+            1 => Self::PRACH_1_25,
+            2 => Self::PRACH_5,
+            3 => Self::NR_PRACH,
+            4 => Self::NPRACH,
+            item => Self::Reserved(item),
+        }
+    }
+}
+
+impl From<FilterIndex> for u8 {
+    fn from(item: FilterIndex) -> Self {
+        match item {
+            FilterIndex::NO_FILTER => 0,
+            FilterIndex::PRACH_1_25 => 1,
+            FilterIndex::PRACH_5 => 2,
+            FilterIndex::NR_PRACH => 3,
+            FilterIndex::NPRACH => 4,
+            FilterIndex::Reserved(item) => item,
         }
     }
 }
 
 impl fmt::Display for FilterIndex {
     fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
-        write!(w, "{:?}", self)
+        match self {
+            Self::NO_FILTER => write!(w, "NO_FILTER"),
+            Self::PRACH_1_25 => write!(w, "PRACH_1_25"),
+            Self::PRACH_5 => write!(w, "PRACH_5"),
+            Self::NR_PRACH => write!(w, "NR_PRACH"),
+            Self::NPRACH => write!(w, "NPRACH"),
+            Self::Reserved(item) => write!(w, "Reserved({})", item),
+        }
     }
 }
 
@@ -207,8 +298,11 @@ impl fmt::Debug for FilterIndex {
 
 // Unused resource blocks or symbols in UL and DL
 // Idle/guard periods
+// Common header fields after the timing header (ORAN-WG4.CUS.0 Table 5-2):
+//   :timeOffset (16bit), frameStructure (8bit), cpLength (16bit), reserved (8bit)
+#[derive(PartialEq)]
 pub struct FCPSectionType0 {
-    // pub comm_ctrl_info: TimingHeader,
+    pub comm_ctrl_info: TimingHeader,
     pub time_offset: u16,        // 2 bytes
     pub frame_structure: FrameStructure,   // 1 byte
     pub cp_length: u16,           // 2 bytes
@@ -220,16 +314,16 @@ impl FCPSectionType0 {
     fn parse_without_sections(data: types::Input) -> types::Result<Self> {
         nom_map(
             nom_tuple((
-                // TimingHeader::parse,
+                TimingHeader::parse,
                 be_u16,
                 be_u8,
                 be_u16,
                 be_u8,
             )),
-            |(/* comm_ctrl_info, */ time_offset, 
+            |(comm_ctrl_info, time_offset, 
                 frame_struct, cp_length, reserved
             )| Self {
-                // comm_ctrl_info: comm_ctrl_info,
+                comm_ctrl_info,
                 time_offset,
                 frame_structure: FrameStructure::from(frame_struct),
                 cp_length,
@@ -239,9 +333,9 @@ impl FCPSectionType0 {
         )(data)
     }
 
-    pub fn parse(data: types::Input, num_of_sections: usize) -> types::Result<Self> {
+    pub fn parse(data: types::Input) -> types::Result<Self> {
         let (remaining, mut section_type0) = FCPSectionType0::parse_without_sections(data)?;
-        match nom_count(_SectionType0Data::parse, num_of_sections)(remaining) {
+        match nom_count(_SectionType0Data::parse, section_type0.comm_ctrl_info.num_of_sections as usize)(remaining) {
             Ok((remain, sections)) => {
                 section_type0.sections = sections;
                 Ok((remain, section_type0))
@@ -256,9 +350,8 @@ impl fmt::Display for FCPSectionType0 {
     fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
         write!(
             w,
-            // "{}, {:04X}, {}, {:04X}, {:02X}, {:?}",
-            "{:04X}, {}, {:04X}, {:02X}, {:?}",
-            // self.comm_ctrl_info,
+            "{}, {:04X}, {}, {:04X}, {:02X}, {:?}",
+            self.comm_ctrl_info,
             self.time_offset,
             self.frame_structure,
             self.cp_length,
@@ -289,14 +382,15 @@ impl fmt::Debug for FCPSectionType0 {
 
 // U-Plane Section header
 // Take 4,5,6 bytes
+#[derive(PartialEq)]
 pub struct SectionHeader {
-    section_id: u16,   // 12 bits
-    rb: u8,            // 1 bit
-    si: u8,           // si = symInc (symbol increment), occupy 1 bit
-    start_prbc: u16,  // 10 bits
-    num_prbc: u8,     // 1 byte
-    ud_comp_hdr: Option<u8>,   // Optional item, if exits, 8 bits, not use for now
-    reserved: Option<u8>       // Optional item, if exits, 8 bits, not use for now
+    pub section_id: u16,   // 12 bits
+    pub rb: u8,            // 1 bit
+    pub si: u8,           // si = symInc (symbol increment), occupy 1 bit
+    pub start_prbc: u16,  // 10 bits
+    pub num_prbc: u8,     // 1 byte
+    pub ud_comp_hdr: Option<UdCompHdr>,   // Optional item, only exits when the eAxC uses dynamic compression, 8 bits
+    pub reserved: Option<u8>       // Optional item, exits together with ud_comp_hdr, 8 bits
 }
 
 impl SectionHeader {
@@ -314,18 +408,31 @@ impl SectionHeader {
             }
         )(data)
     }
+
+    // Section header followed by udCompHdr and 1 reserved byte, 6 bytes totally
+    pub fn parse_with_comp_hdr(data: types::Input) -> types::Result<Self> {
+        nom_map(
+            nom_tuple((SectionHeader::parse, UdCompHdr::parse, be_u8)),
+            |(mut section_hdr, ud_comp_hdr, reserved)| {
+                section_hdr.ud_comp_hdr = Some(ud_comp_hdr);
+                section_hdr.reserved = Some(reserved);
+                section_hdr
+            }
+        )(data)
+    }
 }
 
 impl fmt::Display for SectionHeader {
     fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
         write!(
             w,
-            "{:04X}, {:02X}, {:02X}, {:04X}, {:02X}",
+            "{:04X}, {:02X}, {:02X}, {:04X}, {:02X}, {:?}",
             self.section_id,
             self.rb,
             self.si,
             self.start_prbc,
             self.num_prbc,
+            self.ud_comp_hdr,
         )
     }
 }
@@ -336,15 +443,16 @@ impl fmt::Debug for SectionHeader {
     }
 }
 
+#[derive(PartialEq)]
 pub struct FrameStructure {
-    fft_size: FFTSize,    // 4 bits
-    mu: MU                // 4 bits
+    pub fft_size: FFTSize,    // 4 bits
+    pub mu: MU                // 4 bits
 }
 
 impl From<u8> for FrameStructure {
     fn from(item: u8) -> Self {
         Self {
-            fft_size: FFTSize::from(item & 0xF0),
+            fft_size: FFTSize::from(item >> 4),
             mu: MU::from(item & 0x0F),
         }
     }
@@ -364,7 +472,7 @@ impl FrameStructure {
 
 impl fmt::Display for FrameStructure {
     fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
-        write!(w, "{:?}", self)
+        write!(w, "{:?}, {:?}", self.fft_size, self.mu)
     }
 }
 
@@ -374,6 +482,7 @@ impl fmt::Debug for FrameStructure {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum FFTSize {
     I_NONE = 0,
     RESERVED1 = 1,  // 1..7 reserved1
@@ -401,6 +510,21 @@ impl From<u8> for FFTSize {
     }
 }
 
+impl FFTSize {
+    // Number of points, None for reserved values
+    pub fn size(&self) -> Option<usize> {
+        match self {
+            Self::I_256 => Some(256),
+            Self::I_512 => Some(512),
+            Self::I_1024 => Some(1024),
+            Self::I_2048 => Some(2048),
+            Self::I_4096 => Some(4096),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum MU {
     KHZ_15 = 0,     // 0 - 15KHz
     KHZ_30 = 1,     // 1 - 30KHz/500us
@@ -434,17 +558,20 @@ impl From<u8> for MU {
     }
 }
 
+// Section of type 0:
+//   :section header (4 bytes), reMask (12bit) + numSymbol (4bit), ef (1bit) + reserved (15bit)
+#[derive(PartialEq)]
 pub struct _SectionType0Data {
     pub section_hdr: SectionHeader,
     pub re_mask: u16,     // 12 bits 
     pub num_symbol: u8,  // 4 bits
     pub ef: u8, // ef = extension flag, 1 bit
-    //////// not sure the "reserved" item takes 1 byte or 2 bytes
-    pub reserved: u16,    // 15 bits
+    pub reserved: u16,    // 15 bits, in place of the beamId of the other section types
+    pub extensions: Vec<SectionExtension>,  // while ef = 1
 }
 
 impl _SectionType0Data {
-    pub fn parse(data: types::Input) -> types::Result<Self> {
+    fn parse_without_extensions(data: types::Input) -> types::Result<Self> {
         nom_map(
             nom_tuple((SectionHeader::parse,
                 be_u16,
@@ -455,22 +582,31 @@ impl _SectionType0Data {
                 re_mask: (byte_u16_1 >> 4) as u16,
                 num_symbol: (byte_u16_1 & 0x000F) as u8,
                 ef: (byte_u16_2 >> 15) as u8,
-                reserved: (byte_u16_2 & 0x7FFF) as u16
+                reserved: byte_u16_2 & 0x7FFF,
+                extensions: Vec::new(),
             }
         )(data)
     }
+
+    pub fn parse(data: types::Input) -> types::Result<Self> {
+        let (remaining, mut section) = _SectionType0Data::parse_without_extensions(data)?;
+        let (remain, extensions) = SectionExtension::parse_if(section.ef, remaining)?;
+        section.extensions = extensions;
+        Ok((remain, section))
+    }
 }
 
 impl fmt::Display for _SectionType0Data {
     fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
         write!(
             w,
-            "{}, {:04X}, {:02X}, {:02X}, {:04X}",
+            "{}, {:04X}, {:02X}, {:02X}, {:04X}, {:?}",
             self.section_hdr,
             self.re_mask,
             self.num_symbol,
             self.ef,
             self.reserved,
+            self.extensions,
         )
     }
 }
@@ -484,8 +620,9 @@ impl fmt::Debug for _SectionType0Data {
 // Regular channels using common common resource grid
 // UL/DL fast associated control message(beamforming control)
 pub type FCPSectionType2 = FCPSectionType1;
+#[derive(PartialEq)]
 pub struct FCPSectionType1 {
-    // pub comm_ctrl_info: TimingHeader,
+    pub comm_ctrl_info: TimingHeader,
     pub ud_comp_hdr: u8,         // 1 byte
     pub reserved: u8,            // 1 byte
     pub sections: Vec<_SectionType1Data>  // number of sections
@@ -495,12 +632,12 @@ impl FCPSectionType1 {
     fn parse_without_sections(data: types::Input) -> types::Result<Self> {
         nom_map(
             nom_tuple((
-                // TimingHeader::parse,
+                TimingHeader::parse,
                 be_u8,
                 be_u8,
             )),
-            |(/* comm_ctrl_info, */ ud_comp_hdr, reserved)| Self {
-                // comm_ctrl_info,
+            |(comm_ctrl_info, ud_comp_hdr, reserved)| Self {
+                comm_ctrl_info,
                 ud_comp_hdr,
                 reserved,
                 sections: Vec::new(),
@@ -508,10 +645,9 @@ impl FCPSectionType1 {
         )(data)
     }
 
-    pub fn parse(data: types::Input, num_of_sections: usize) -> types::Result<Self> {
+    pub fn parse(data: types::Input) -> types::Result<Self> {
         let (remaining, mut section_type1) = FCPSectionType1::parse_without_sections(data)?;
-        // match nom_count(_SectionType1Data::parse, section_type1.comm_ctrl_info.num_of_sections as usize)(remaining) {
-        match nom_count(_SectionType1Data::parse, num_of_sections)(remaining) {
+        match nom_count(_SectionType1Data::parse, section_type1.comm_ctrl_info.num_of_sections as usize)(remaining) {
             Ok((remain, sections)) => {
                 section_type1.sections = sections;
                 Ok((remain, section_type1))
@@ -526,9 +662,8 @@ impl fmt::Display for FCPSectionType1 {
     fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
         write!(
             w,
-            // "{}, {:02X}, {:02X}, {:?}",
-            "{:02X}, {:02X}, {:?}",
-            // self.comm_ctrl_info,
+            "{}, {:02X}, {:02X}, {:?}",
+            self.comm_ctrl_info,
             self.ud_comp_hdr,
             self.reserved,
             self.sections,
@@ -542,16 +677,18 @@ impl fmt::Debug for FCPSectionType1 {
     }
 }
 
+#[derive(PartialEq)]
 pub struct _SectionType1Data {
     pub section_hdr: SectionHeader,
     pub re_mask: u16,      // 12 bits
     pub num_symbol: u8,   // 4 bits
     pub ef: u8,           // ef = extension flag, 1 bit
-    pub beam_id: u16      // 15 bits 
+    pub beam_id: u16,     // 15 bits
+    pub extensions: Vec<SectionExtension>,  // while ef = 1
 }
 
 impl _SectionType1Data {
-    pub fn parse(data: types::Input) -> types::Result<Self> {
+    fn parse_without_extensions(data: types::Input) -> types::Result<Self> {
         nom_map(
             nom_tuple((SectionHeader::parse,
                 be_u16,
@@ -562,22 +699,31 @@ impl _SectionType1Data {
                 re_mask: (byte_u16_1 >> 4) as u16,
                 num_symbol: (byte_u16_1 & 0x000F) as u8,
                 ef: (byte_u16_2 >> 15) as u8,
-                beam_id: (byte_u16_2 & 0x7FFF) as u16
+                beam_id: byte_u16_2 & 0x7FFF,
+                extensions: Vec::new(),
             }
         )(data)
     }
+
+    pub fn parse(data: types::Input) -> types::Result<Self> {
+        let (remaining, mut section) = _SectionType1Data::parse_without_extensions(data)?;
+        let (remain, extensions) = SectionExtension::parse_if(section.ef, remaining)?;
+        section.extensions = extensions;
+        Ok((remain, section))
+    }
 }
 
 impl fmt::Display for _SectionType1Data {
     fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
         write!(
             w,
-            "{}, {:04X}, {:02X}, {:02X}, {:04X}",
+            "{}, {:04X}, {:02X}, {:02X}, {:04X}, {:?}",
             self.section_hdr,
             self.re_mask,
             self.num_symbol,
             self.ef,
             self.beam_id,
+            self.extensions,
         )
     }
 }
@@ -590,14 +736,16 @@ impl fmt::Debug for _SectionType1Data {
 
 // Specific channels not fitting common resource grid
 // control of PRACH and mixed numerology channels
+// Common header fields after the timing header (ORAN-WG4.CUS.0 Table 5-4):
+//   :timeOffset (16bit), frameStructure (8bit), cpLength (16bit), udCompHdr (8bit)
+// numberOfSections and sectionType are the last fields of the timing header.
+#[derive(PartialEq)]
 pub struct FCPSectionType3 {
-    // pub comm_ctrl_info: TimingHeader,
-    pub num_of_sections: u8,  // 1 byte
-    pub section_type: u8,        // section type = 2 for SectionType 3, 1 byte
-    pub time_offset: u16,        // 2 bytes
+    pub comm_ctrl_info: TimingHeader,
+    pub time_offset: u16,        // 2 bytes, in units of Ts (1 / 30.72 MHz)
     pub frame_structure: FrameStructure,      // 1 byte
-    pub cp_length: u16,          // 2 bytes
-    pub ud_comp_hdr: u8,         // 1 byte
+    pub cp_length: u16,          // 2 bytes, in units of Ts
+    pub ud_comp_hdr: UdCompHdr,  // 1 byte
     pub sections: Vec<_SectionType3Data>  // number of sections
 }
 
@@ -605,20 +753,14 @@ impl FCPSectionType3 {
     fn parse_without_sections(data: types::Input) -> types::Result<Self> {
         nom_map(
             nom_tuple((
-                // TimingHeader::parse,
-                be_u8,
-                be_u8,
+                TimingHeader::parse,
                 be_u16,
                 be_u8,
                 be_u16,
-                be_u8,
+                UdCompHdr::parse,
             )),
-            |(/* comm_ctrl_info, */ num_of_sections, section_type,
-                time_offset, byte_one, cp_length, ud_comp_hdr 
-            )| Self {
-                // comm_ctrl_info,
-                num_of_sections,
-                section_type,
+            |(comm_ctrl_info, time_offset, byte_one, cp_length, ud_comp_hdr)| Self {
+                comm_ctrl_info,
                 time_offset,
                 frame_structure: FrameStructure::from(byte_one),
                 cp_length,
@@ -630,7 +772,7 @@ impl FCPSectionType3 {
 
     pub fn parse(data: types::Input) -> types::Result<Self> {
         let (remaining, mut section_type3) = FCPSectionType3::parse_without_sections(data)?;
-        match nom_count(_SectionType3Data::parse, section_type3.num_of_sections as usize)(remaining) {
+        match nom_count(_SectionType3Data::parse, section_type3.comm_ctrl_info.num_of_sections as usize)(remaining) {
             Ok((remain, sections)) => {
                 section_type3.sections = sections;
                 Ok((remain, section_type3))
@@ -645,11 +787,8 @@ impl fmt::Display for FCPSectionType3 {
     fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
         write!(
             w,
-            // "{}, {:02X}, {:02X}, {:04X}, {:?}, {:04X}, {:02X}, {:?}",
-            "{:02X}, {:02X}, {:04X}, {:?}, {:04X}, {:02X}, {:?}",
-            // self.comm_ctrl_info,
-            self.num_of_sections,
-            self.section_type,
+            "{}, {:04X}, {:?}, {:04X}, {}, {:?}",
+            self.comm_ctrl_info,
             self.time_offset,
             self.frame_structure,
             self.cp_length,
@@ -665,26 +804,29 @@ impl fmt::Debug for FCPSectionType3 {
     }
 }
 
+// Section of type 3:
+//   :section header (4 bytes), reMask (12bit) + numSymbol (4bit), ef (1bit) + beamId (15bit)
+//   :freqOffset (24bit), signed, in units of half the subcarrier spacing of the section
+//   :reserved (8bit)
+#[derive(PartialEq)]
 pub struct _SectionType3Data {
-    ////// number of sections ///////
     pub section_hdr: SectionHeader,
     pub re_mask: u16,       // 12 bits
     pub num_symbol: u8,     // 4 bits
     pub ef: u8, // ef = extension flag
     pub beam_id: u16,       // 15 bits
-    ///// For the following 2 items, not sure if only frequency_offset take 2 bytes
-    ////  or the frequency_offset take only 1 byte and reserved take another 1 byte
-    pub freq_offset: u16,  // 2 bytes
-    pub reserved: u8,  // 8 bits
+    pub freq_offset: i32,   // 24 bits
+    pub reserved: u8,       // 8 bits
+    pub extensions: Vec<SectionExtension>,  // while ef = 1
 }
 
 impl _SectionType3Data {
-    pub fn parse(data: types::Input) -> types::Result<Self> {
+    fn parse_without_extensions(data: types::Input) -> types::Result<Self> {
         nom_map(
             nom_tuple((SectionHeader::parse,
                 be_u16,
                 be_u16,
-                be_u16,
+                be_u24,
                 be_u8
             )),
             |(section_hdr, byte_u16_1, byte_u16_2, freq_offset, reserved)| Self {
@@ -693,18 +835,32 @@ impl _SectionType3Data {
                 num_symbol: (byte_u16_1 & 0x000F) as u8,
                 ef: (byte_u16_2 >> 15) as u8,
                 beam_id: (byte_u16_2 & 0x7FFF) as u16,
-                freq_offset,
-                reserved
+                // sign extension of the 24 bits value
+                freq_offset: ((freq_offset << 8) as i32) >> 8,
+                reserved,
+                extensions: Vec::new(),
             }
         )(data)
     }
+
+    pub fn parse(data: types::Input) -> types::Result<Self> {
+        let (remaining, mut section) = _SectionType3Data::parse_without_extensions(data)?;
+        let (remain, extensions) = SectionExtension::parse_if(section.ef, remaining)?;
+        section.extensions = extensions;
+        Ok((remain, section))
+    }
+
+    // Offset of the center of the section (k0) to the carrier center, in Hz
+    pub fn freq_offset_hz(&self, scs_hz: f64) -> f64 {
+        self.freq_offset as f64 * scs_hz / 2.0
+    }
 }
 
 impl fmt::Display for _SectionType3Data {
     fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
         write!(
             w,
-            "{}, {:04X}, {:02X}, {:02X}, {:04X}, {:04X}, {:02X}",
+            "{}, {:04X}, {:02X}, {:02X}, {:04X}, {}, {:02X}, {:?}",
             self.section_hdr,
             self.re_mask,
             self.num_symbol,
@@ -712,6 +868,7 @@ impl fmt::Display for _SectionType3Data {
             self.beam_id,
             self.freq_offset,
             self.reserved,
+            self.extensions,
         )
     }
 }
@@ -722,7 +879,511 @@ impl fmt::Debug for _SectionType3Data {
     }
 }
 
+// UE scheduling information
+// Used with the RU side beamforming weight calculation, the sections carry the ueId
+// instead of the beamId, the common header is the same as section type 1.
+#[derive(PartialEq)]
+pub struct FCPSectionType5 {
+    pub comm_ctrl_info: TimingHeader,
+    pub ud_comp_hdr: u8,         // 1 byte
+    pub reserved: u8,            // 1 byte
+    pub sections: Vec<_SectionType5Data>  // number of sections
+}
+
+impl FCPSectionType5 {
+    fn parse_without_sections(data: types::Input) -> types::Result<Self> {
+        nom_map(
+            nom_tuple((
+                TimingHeader::parse,
+                be_u8,
+                be_u8,
+            )),
+            |(comm_ctrl_info, ud_comp_hdr, reserved)| Self {
+                comm_ctrl_info,
+                ud_comp_hdr,
+                reserved,
+                sections: Vec::new(),
+            }
+        )(data)
+    }
+
+    pub fn parse(data: types::Input) -> types::Result<Self> {
+        let (remaining, mut section_type5) = FCPSectionType5::parse_without_sections(data)?;
+        match nom_count(_SectionType5Data::parse, section_type5.comm_ctrl_info.num_of_sections as usize)(remaining) {
+            Ok((remain, sections)) => {
+                section_type5.sections = sections;
+                Ok((remain, section_type5))
+            },
+            Err(e) => { Err(e)},
+        }
+    }
+}
+
+impl fmt::Display for FCPSectionType5 {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            w,
+            "{}, {:02X}, {:02X}, {:?}",
+            self.comm_ctrl_info,
+            self.ud_comp_hdr,
+            self.reserved,
+            self.sections,
+        )
+    }
+}
+
+impl fmt::Debug for FCPSectionType5 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+#[derive(PartialEq)]
+pub struct _SectionType5Data {
+    pub section_hdr: SectionHeader,
+    pub re_mask: u16,      // 12 bits
+    pub num_symbol: u8,   // 4 bits
+    pub ef: u8,           // ef = extension flag, 1 bit
+    pub ue_id: u16,       // 15 bits
+    pub extensions: Vec<SectionExtension>,  // while ef = 1
+}
+
+impl _SectionType5Data {
+    fn parse_without_extensions(data: types::Input) -> types::Result<Self> {
+        nom_map(
+            nom_tuple((SectionHeader::parse,
+                be_u16,
+                be_u16,
+            )),
+            |(section_hdr, byte_u16_1, byte_u16_2)| Self {
+                section_hdr,
+                re_mask: byte_u16_1 >> 4,
+                num_symbol: (byte_u16_1 & 0x000F) as u8,
+                ef: (byte_u16_2 >> 15) as u8,
+                ue_id: byte_u16_2 & 0x7FFF,
+                extensions: Vec::new(),
+            }
+        )(data)
+    }
+
+    pub fn parse(data: types::Input) -> types::Result<Self> {
+        let (remaining, mut section) = _SectionType5Data::parse_without_extensions(data)?;
+        let (remain, extensions) = SectionExtension::parse_if(section.ef, remaining)?;
+        section.extensions = extensions;
+        Ok((remain, section))
+    }
+}
+
+impl fmt::Display for _SectionType5Data {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            w,
+            "{}, {:04X}, {:02X}, {:02X}, {:04X}, {:?}",
+            self.section_hdr,
+            self.re_mask,
+            self.num_symbol,
+            self.ef,
+            self.ue_id,
+            self.extensions,
+        )
+    }
+}
+
+impl fmt::Debug for _SectionType5Data {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+// Channel information
+// Sends the channel estimation of every UE (per PRB and per TRX) to the RU, so that the RU
+// can calculate the beamforming weights. The number of TRX is configured by the M-Plane.
+//   :numberOfUEs (8bit), number of UEs (sections) in the message
+//   :ciCompHdr (8bit), ciIqWidth (4bit) + ciCompMeth (4bit), same encoding as udCompHdr
+#[derive(PartialEq)]
+pub struct FCPSectionType6 {
+    pub comm_ctrl_info: TimingHeader,
+    pub number_of_ues: u8,       // 1 byte
+    pub ci_comp_hdr: UdCompHdr,  // 1 byte
+    pub sections: Vec<_SectionType6Data>  // number of sections
+}
+
+impl FCPSectionType6 {
+    fn parse_without_sections(data: types::Input) -> types::Result<Self> {
+        nom_map(
+            nom_tuple((
+                TimingHeader::parse,
+                be_u8,
+                UdCompHdr::parse,
+            )),
+            |(comm_ctrl_info, number_of_ues, ci_comp_hdr)| Self {
+                comm_ctrl_info,
+                number_of_ues,
+                ci_comp_hdr,
+                sections: Vec::new(),
+            }
+        )(data)
+    }
+
+    pub fn parse(data: types::Input, num_trx: usize) -> types::Result<Self> {
+        let (remaining, mut section_type6) = FCPSectionType6::parse_without_sections(data)?;
+        let ci_comp_hdr = section_type6.ci_comp_hdr;
+        let section_parse = move |data| _SectionType6Data::parse(data, &ci_comp_hdr, num_trx);
+        match nom_count(section_parse, section_type6.comm_ctrl_info.num_of_sections as usize)(remaining) {
+            Ok((remain, sections)) => {
+                section_type6.sections = sections;
+                Ok((remain, section_type6))
+            },
+            Err(e) => { Err(e)},
+        }
+    }
+}
+
+impl fmt::Display for FCPSectionType6 {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            w,
+            "{}, {:02X}, {}, {:?}",
+            self.comm_ctrl_info,
+            self.number_of_ues,
+            self.ci_comp_hdr,
+            self.sections,
+        )
+    }
+}
+
+impl fmt::Debug for FCPSectionType6 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+// Section of type 6, one per UE:
+//   :ef (1bit) + ueId (15bit)
+//   :regularizationFactor (16bit), signed
+//   :reserved (4bit) + rb (1bit) + symInc (1bit) + startPrbc (10bit)
+//   :numPrbc (8bit)
+//   :numPrbc * (ciCompParam (if any) + numberOfTRX * (ciIsample + ciQsample))
+#[derive(PartialEq)]
+pub struct _SectionType6Data {
+    pub ef: u8,                    // 1 bit
+    pub ue_id: u16,                // 15 bits
+    pub regularization_factor: i16, // 2 bytes
+    pub reserved: u8,              // 4 bits
+    pub rb: u8,                    // 1 bit
+    pub si: u8,                    // 1 bit, symInc
+    pub start_prbc: u16,           // 10 bits
+    pub num_prbc: u8,              // 1 byte
+    pub ci_prbs: Vec<ChannelInfoPrb>,  // numPrbc
+    pub extensions: Vec<SectionExtension>,  // after the PRBs, while ef = 1
+}
+
+impl _SectionType6Data {
+    fn parse_without_prbs(data: types::Input) -> types::Result<Self> {
+        nom_map(
+            nom_tuple((be_u16, be_u16, be_u16, be_u8)),
+            |(byte_u16_1, regularization_factor, byte_u16_2, num_prbc)| Self {
+                ef: (byte_u16_1 >> 15) as u8,
+                ue_id: byte_u16_1 & 0x7FFF,
+                regularization_factor: regularization_factor as i16,
+                reserved: (byte_u16_2 >> 12) as u8,
+                rb: ((byte_u16_2 >> 11) & 0x1) as u8,
+                si: ((byte_u16_2 >> 10) & 0x1) as u8,
+                start_prbc: byte_u16_2 & 0x3FF,
+                num_prbc,
+                ci_prbs: Vec::new(),
+                extensions: Vec::new(),
+            }
+        )(data)
+    }
+
+    pub fn parse<'a>(data: types::Input<'a>, ci_comp_hdr: &UdCompHdr, num_trx: usize) -> types::Result<'a, Self> {
+        let (remaining, mut section) = _SectionType6Data::parse_without_prbs(data)?;
+        let prb_parse = |data| ChannelInfoPrb::parse(data, ci_comp_hdr, num_trx);
+        let (remaining, ci_prbs) = nom_count(prb_parse, section.num_prbc as usize)(remaining)?;
+        let (remain, extensions) = SectionExtension::parse_if(section.ef, remaining)?;
+        section.ci_prbs = ci_prbs;
+        section.extensions = extensions;
+        Ok((remain, section))
+    }
+}
+
+impl fmt::Display for _SectionType6Data {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            w,
+            "{:02X}, {:04X}, {}, {:02X}, {:02X}, {:04X}, {:02X}, {:?}, {:?}",
+            self.ef,
+            self.ue_id,
+            self.regularization_factor,
+            self.rb,
+            self.si,
+            self.start_prbc,
+            self.num_prbc,
+            self.ci_prbs,
+            self.extensions,
+        )
+    }
+}
+
+impl fmt::Debug for _SectionType6Data {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+// Channel information of one PRB: ciCompParam + ciIsample/ciQsample of every TRX,
+// the samples are padded to the byte boundary.
+#[derive(PartialEq)]
+pub struct ChannelInfoPrb {
+    pub ci_comp_param: Option<u8>,   // 1 byte, exists with BFP/block scaling/μ-law
+    pub ci_sample: Vec<u8>,          // numberOfTRX * ciIqWidth * 2 bits
+}
+
+impl ChannelInfoPrb {
+    pub fn parse<'a>(data: types::Input<'a>, ci_comp_hdr: &UdCompHdr, num_trx: usize) -> types::Result<'a, Self> {
+        let sample_size = (num_trx * ci_comp_hdr.iq_width() as usize * 2).div_ceil(8);
+        let (remaining, ci_comp_param) = if ci_comp_hdr.has_comp_param() {
+            let (remaining, comp_param) = be_u8(data)?;
+            (remaining, Some(comp_param))
+        } else {
+            (data, None)
+        };
+        nom_map(
+            nom_take(sample_size),
+            move |ci_sample: &[u8]| Self {
+                ci_comp_param,
+                ci_sample: ci_sample.to_vec(),
+            }
+        )(remaining)
+    }
+
+    // Decompressed channel coefficients, one per TRX, none if ciCompMeth is reserved
+    // or the samples are fewer than the TRX.
+    pub fn get_ci_samples(&self, ci_comp_hdr: &UdCompHdr, num_trx: usize) -> Vec<Complex<f32>> {
+        let comp_param = self.ci_comp_param.map(|p| vec![p]).unwrap_or_default();
+        decompressor(ci_comp_hdr, None)
+            .and_then(|d| d.decompress(&comp_param, &self.ci_sample, num_trx))
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Display for ChannelInfoPrb {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        write!(w, "{:?}, {:?}", self.ci_comp_param, self.ci_sample)
+    }
+}
+
+impl fmt::Debug for ChannelInfoPrb {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+// LAA (License Assisted Access) listen-before-talk messages between the DU and the RU.
+//   :reserved (16bit)
+//   :laaMsgType (4bit), type of the LAA message
+//   :laaMsgLen (4bit), number of 32 bits words of the LAA message, laaMsgType/laaMsgLen included
+#[derive(PartialEq)]
+pub struct FCPSectionType7 {
+    pub comm_ctrl_info: TimingHeader,
+    pub reserved: u16,           // 2 bytes
+    pub laa_msg_type: u8,        // 4 bits
+    pub laa_msg_len: u8,         // 4 bits
+    pub laa_msg: LaaMessage,     // laaMsgLen * 4 - 1 bytes
+}
+
+impl FCPSectionType7 {
+    pub fn parse(data: types::Input) -> types::Result<Self> {
+        let (remaining, (comm_ctrl_info, reserved, byte_u8)) = nom_tuple((
+            TimingHeader::parse,
+            be_u16,
+            be_u8,
+        ))(data)?;
+        let laa_msg_type = byte_u8 >> 4;
+        let laa_msg_len = byte_u8 & 0x0F;
+        let msg_size = (laa_msg_len as usize * 4).saturating_sub(1);
+        let (remain, msg) = nom_take(msg_size)(remaining)?;
+        Ok((remain, Self {
+            comm_ctrl_info,
+            reserved,
+            laa_msg_type,
+            laa_msg_len,
+            laa_msg: LaaMessage::from_bytes(laa_msg_type, msg),
+        }))
+    }
+}
+
+impl fmt::Display for FCPSectionType7 {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            w,
+            "{}, {:04X}, {:02X}, {:02X}, {:?}",
+            self.comm_ctrl_info,
+            self.reserved,
+            self.laa_msg_type,
+            self.laa_msg_len,
+            self.laa_msg,
+        )
+    }
+}
+
+impl fmt::Debug for FCPSectionType7 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+// The LAA message behind laaMsgType/laaMsgLen, reserved bits are not kept.
+#[derive(Debug, PartialEq)]
+pub enum LaaMessage {
+    // laaMsgType = 0: lbtHandle (16) + lbtOffset (10) + lbtMode (2) + reserved (1) + lbtDeferFactor (3)
+    //                 + lbtBackoffCounter (10) + MCOT (4) + reserved (10)
+    LbtPdschReq {
+        lbt_handle: u16,
+        lbt_offset: u16,
+        lbt_mode: u8,
+        lbt_defer_factor: u8,
+        lbt_backoff_counter: u16,
+        mcot: u8,
+    },
+    // laaMsgType = 1: lbtHandle (16) + lbtOffset (10) + lbtMode (2) + reserved (28)
+    LbtDrsReq {
+        lbt_handle: u16,
+        lbt_offset: u16,
+        lbt_mode: u8,
+    },
+    // laaMsgType = 2: lbtHandle (16) + lbtPdschRes (2) + inParSF (1) + sfStatus (1) + sfnSf (12) + reserved (24)
+    LbtPdschRsp {
+        lbt_handle: u16,
+        lbt_pdsch_res: u8,
+        in_par_sf: u8,
+        sf_status: u8,
+        sfn_sf: u16,
+    },
+    // laaMsgType = 3: lbtHandle (16) + lbtDrsRes (1) + reserved (7)
+    LbtDrsRsp {
+        lbt_handle: u16,
+        lbt_drs_res: u8,
+    },
+    // laaMsgType = 4: lbtHandle (16) + lbtBufErr (1) + reserved (7)
+    LbtBufferError {
+        lbt_handle: u16,
+        lbt_buf_err: u8,
+    },
+    // laaMsgType = 5: lbtHandle (16) + lbtCWconfig_H (8) + lbtCWconfig_T (8) + lbtMode (2)
+    //                 + lbtTrafficClass (3) + reserved (3)
+    LbtCwConfigReq {
+        lbt_handle: u16,
+        lbt_cw_config_h: u8,
+        lbt_cw_config_t: u8,
+        lbt_mode: u8,
+        lbt_traffic_class: u8,
+    },
+    // laaMsgType = 6: lbtHandle (16) + lbtCWR_Rst (1) + reserved (7)
+    LbtCwConfigRsp {
+        lbt_handle: u16,
+        lbt_cwr_rst: u8,
+    },
+    // laaMsgType = 7..15, the message is kept as it is
+    Reserved(Vec<u8>),
+}
+
+impl LaaMessage {
+    // Read `len` bits from bit `offset` of the message (MSB first), the missing bytes are zeros.
+    fn bits(msg: &[u8], offset: u32, len: u32) -> u32 {
+        let mut word = [0u8; 8];
+        let n = std::cmp::min(msg.len(), 8);
+        word[..n].copy_from_slice(&msg[..n]);
+        ((u64::from_be_bytes(word) >> (64 - offset - len)) & ((1u64 << len) - 1)) as u32
+    }
+
+    pub fn from_bytes(laa_msg_type: u8, msg: &[u8]) -> Self {
+        let bits = |offset, len| LaaMessage::bits(msg, offset, len);
+        let lbt_handle = bits(0, 16) as u16;
+        match laa_msg_type {
+            0 => Self::LbtPdschReq {
+                lbt_handle,
+                lbt_offset: bits(16, 10) as u16,
+                lbt_mode: bits(26, 2) as u8,
+                lbt_defer_factor: bits(29, 3) as u8,
+                lbt_backoff_counter: bits(32, 10) as u16,
+                mcot: bits(42, 4) as u8,
+            },
+            1 => Self::LbtDrsReq {
+                lbt_handle,
+                lbt_offset: bits(16, 10) as u16,
+                lbt_mode: bits(26, 2) as u8,
+            },
+            2 => Self::LbtPdschRsp {
+                lbt_handle,
+                lbt_pdsch_res: bits(16, 2) as u8,
+                in_par_sf: bits(18, 1) as u8,
+                sf_status: bits(19, 1) as u8,
+                sfn_sf: bits(20, 12) as u16,
+            },
+            3 => Self::LbtDrsRsp {
+                lbt_handle,
+                lbt_drs_res: bits(16, 1) as u8,
+            },
+            4 => Self::LbtBufferError {
+                lbt_handle,
+                lbt_buf_err: bits(16, 1) as u8,
+            },
+            5 => Self::LbtCwConfigReq {
+                lbt_handle,
+                lbt_cw_config_h: bits(16, 8) as u8,
+                lbt_cw_config_t: bits(24, 8) as u8,
+                lbt_mode: bits(32, 2) as u8,
+                lbt_traffic_class: bits(34, 3) as u8,
+            },
+            6 => Self::LbtCwConfigRsp {
+                lbt_handle,
+                lbt_cwr_rst: bits(16, 1) as u8,
+            },
+            _ => Self::Reserved(msg.to_vec()),
+        }
+    }
+}
+
+// Section types this parser doesn't know (type 4, 8 and the ones of the newer CUS versions),
+// the timing header is decoded and the rest of the message is kept as raw bytes.
+#[derive(PartialEq)]
+pub struct FCPSectionUnknown {
+    pub comm_ctrl_info: TimingHeader,
+    pub data: Vec<u8>,
+}
+
+impl FCPSectionUnknown {
+    pub fn parse(data: types::Input) -> types::Result<Self> {
+        let (remaining, comm_ctrl_info) = TimingHeader::parse(data)?;
+        Ok((&remaining[remaining.len()..], Self {
+            comm_ctrl_info,
+            data: remaining.to_vec(),
+        }))
+    }
+}
+
+impl fmt::Display for FCPSectionUnknown {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            w,
+            "{}, section type: {}, {:02X?}",
+            self.comm_ctrl_info,
+            self.comm_ctrl_info.section_type,
+            self.data,
+        )
+    }
+}
+
+impl fmt::Debug for FCPSectionUnknown {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
 // IQ transport
+#[derive(PartialEq)]
 pub struct UPlaneIQData {
     pub dir: DataDirection,    // 1 bit
     pub payload_ver: u8,       // 3 bits    
@@ -732,10 +1393,9 @@ pub struct UPlaneIQData {
     pub slot_id: u8,           // 6 bits
     pub start_symbol_id: u8,   // 6 bits
     ////// number of sections ///////
-    Here maybe contain more than one section!!! We can move the SectionHeader into IQPrbuData !!!
-    also, the number of section will represent at related C-Plane message
-    pub section_hdr: SectionHeader,
-    pub iq_prbu: Vec<IQPrbuData>, // The size of IQPrbuData should be variable because of different mantissa size.
+    // One message may contain more than one section, the number of sections is not carried
+    // in the U-Plane message, so the sections are parsed until the message ends.
+    pub sections: Vec<UPlaneSection>,
 }
 
 impl UPlaneIQData {
@@ -743,9 +1403,8 @@ impl UPlaneIQData {
         nom_map(
             nom_tuple((
                 be_u8, be_u8, be_u16,
-                SectionHeader::parse,
             )),
-            |(byte_u8, frame_id, byte_u16, section_hdr)| Self {
+            |(byte_u8, frame_id, byte_u16)| Self {
                 dir: DataDirection::from(byte_u8 >> 7),
                 payload_ver: (byte_u8 & 0x70) >> 4,
                 filter_index: FilterIndex::from(byte_u8 & 0x0F),
@@ -753,34 +1412,33 @@ impl UPlaneIQData {
                 subframe_id: (byte_u16 >> 12) as u8,
                 slot_id: ((byte_u16 & 0x0FC0) >> 6) as u8,
                 start_symbol_id: (byte_u16 & 0x003F) as u8,
-                section_hdr,
-                iq_prbu: Vec::new(),
+                sections: Vec::new(),
             }
         )(data)
     }
 
-    pub fn parse(data: types::Input, mantissa: u16) -> types::Result<Self> {
-        let (remaining, mut up_data) = UPlaneIQData::parse_without_sections(data)?;
-        let iq_prbu_parse_with_mantissa = |data| IQPrbuData::parse(data, mantissa);
-        match nom_count(iq_prbu_parse_with_mantissa, up_data.section_hdr.num_prbc as usize)(remaining) {
-            Ok((remain, iq_data)) => {
-                up_data.iq_prbu = iq_data;
-                Ok((remain, up_data))
-            },
-            Err(e) => { 
-                Err(e)
-             },
+    // The data should only contain this U-Plane message, the ethernet padding has to be removed.
+    pub fn parse(data: types::Input, comp_config: EaxcCompConfig) -> types::Result<Self> {
+        let (mut remaining, mut up_data) = UPlaneIQData::parse_without_sections(data)?;
+        while !remaining.is_empty() {
+            let (remain, section) = UPlaneSection::parse(remaining, comp_config)?;
+            up_data.sections.push(section);
+            remaining = remain;
         }
+        Ok((remaining, up_data))
     }
 
+    // Total PRBs of all sections in this message
+    pub fn prbu_count(&self) -> usize {
+        self.sections.iter().map(|s| s.iq_prbu.len()).sum()
+    }
 }
 
 impl fmt::Display for UPlaneIQData {
     fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
         write!(
             w,
-            // "{}, {:02X}, {}, {:02X}, {:02X}, {:02X}, {:02X}, {}, {:?}",
-            "{}, {:02X}, {}, {:02X}, {:02X}, {:02X}, {:02X}, {}",
+            "{}, {:02X}, {}, {:02X}, {:02X}, {:02X}, {:02X}, {:?}",
             DataDirection::from(self.dir),
             self.payload_ver,
             FilterIndex::from(self.filter_index),
@@ -788,8 +1446,7 @@ impl fmt::Display for UPlaneIQData {
             self.subframe_id,
             self.slot_id,
             self.start_symbol_id,
-            self.section_hdr ,
-            // self.iq_prbu,
+            self.sections,
         )
     }
 }
@@ -800,6 +1457,59 @@ impl fmt::Debug for UPlaneIQData {
     }
 }
 
+// One U-Plane data section: section header + numPrbu PRBs.
+// The udCompHdr of the section decides the PRB layout, it comes from the section header
+// with dynamic compression, otherwise from the static configuration of the eAxC.
+#[derive(PartialEq)]
+pub struct UPlaneSection {
+    pub section_hdr: SectionHeader,
+    pub ud_comp_hdr: UdCompHdr,
+    pub iq_prbu: Vec<IQPrbuData>, // The size of IQPrbuData should be variable because of different mantissa size.
+}
+
+impl UPlaneSection {
+    pub fn parse(data: types::Input, comp_config: EaxcCompConfig) -> types::Result<Self> {
+        let (remaining, section_hdr, ud_comp_hdr) = match comp_config {
+            EaxcCompConfig::Static(ud_comp_hdr) => {
+                let (remaining, section_hdr) = SectionHeader::parse(data)?;
+                (remaining, section_hdr, ud_comp_hdr)
+            },
+            EaxcCompConfig::Dynamic => {
+                let (remaining, section_hdr) = SectionHeader::parse_with_comp_hdr(data)?;
+                // always set by parse_with_comp_hdr
+                let ud_comp_hdr = section_hdr.ud_comp_hdr
+                    .ok_or_else(|| nom::Err::Error(types::Error::from_error_kind(data, ErrorKind::Verify)))?;
+                (remaining, section_hdr, ud_comp_hdr)
+            },
+        };
+        let iq_prbu_parse_with_comp = |data| IQPrbuData::parse(data, &ud_comp_hdr);
+        let (remain, iq_prbu) = nom_count(iq_prbu_parse_with_comp, section_hdr.num_prbc as usize)(remaining)?;
+        Ok((remain, Self {
+            section_hdr,
+            ud_comp_hdr,
+            iq_prbu,
+        }))
+    }
+}
+
+impl fmt::Display for UPlaneSection {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            w,
+            "{}, {}, PRBs: {}",
+            self.section_hdr,
+            self.ud_comp_hdr,
+            self.iq_prbu.len(),
+        )
+    }
+}
+
+impl fmt::Debug for UPlaneSection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
 // PRB fiedls when using block floating point:
 //      •Reserved/padding (4bit). Used for scale control in some Nokia systems
 //      •Exponent for I & Q samples (4bit).
@@ -820,11 +1530,13 @@ impl fmt::Debug for UPlaneIQData {
 // Type: signed integer.
 // Field length: 1-16 bits
 
+#[derive(PartialEq)]
 pub struct IQPrbuData {
-    pub reserved: u8,        // 4 bits
-    pub exponent: u8,        // 4 bits
+    pub reserved: u8,        // 4 bits, 0 if there is no udCompParam
+    pub exponent: u8,        // 4 bits, 0 if there is no udCompParam
     // pub iq_sample: Vec<(u16, u16)>,  // the I/Q samples, 9b case, size: [(u16, u16); 12], 9 bits
     pub iq_sample: Vec<u8>,  // total 27 bytes, the I/Q samples, 9b case, size: [(u16, u16); 12], 9 bits
+    pub ud_comp_hdr: UdCompHdr,  // the compression of this PRB, decides the width of I/Q samples
 }
 
 impl IQPrbuData {
@@ -834,16 +1546,13 @@ impl IQPrbuData {
         let mut iq_data = Vec::<(i16, i16)>::new();
         let mut remain = data;
 
-        let modulo: i16 = 1 << mantissa;
-        let max_value: i16 = (1 << (mantissa-1)) -1;
+        let shift = 32 - mantissa as u32;
+        // The RE binary representation is 2's complement.
+        let fix_two_complement = |i: u32| (((i << shift) as i32) >> shift) as i16;
 
         for _ in 0..RE_NUM {
-            let (other, i_real) = nom_bit_take(mantissa)(remain)?;
-            let (other, q_imag) = nom_bit_take(mantissa)(other)?;
-            let fix_two_complement = | i: i16 | {
-                // The RE binary representation is 2's complement.
-                if i < max_value { i } else { i - modulo }
-            };
+            let (other, i_real): (_, u32) = nom_bit_take(mantissa)(remain)?;
+            let (other, q_imag): (_, u32) = nom_bit_take(mantissa)(other)?;
             iq_data.push((
                 fix_two_complement(i_real), 
                 fix_two_complement(q_imag)
@@ -854,26 +1563,37 @@ impl IQPrbuData {
         Ok((remain, iq_data))
     }
 
-    pub fn get_iq_data<'a>(&'a self, mantissa: u16) -> IResult<&'a [u8], Vec<(i16, i16)>> {
-        let parser = |data: (&'a [u8], usize)| { IQPrbuData::_get_iq_samples(data, mantissa) };
+    // The udCompParam byte in front of the samples, if the compression method has one.
+    pub fn ud_comp_param(&self) -> Option<u8> {
+        if self.ud_comp_hdr.has_comp_param() {
+            Some((self.reserved << 4) | self.exponent)
+        } else {
+            None
+        }
+    }
+
+    pub fn get_iq_data(&self) -> IResult<&[u8], Vec<(i16, i16)>> {
+        let mantissa = self.ud_comp_hdr.iq_width();
+        let parser = move |data| { IQPrbuData::_get_iq_samples(data, mantissa) };
         nom_bits(parser)(&self.iq_sample[..])
     }
 
-    pub fn parse(data: types::Input, mantissa: u16) -> types::Result<Self> {
-        let iq_data_size = mantissa * 2 * 12 / 8;  // (I + Q) * mantissa * re_count / byte_len
+    pub fn parse<'a>(data: types::Input<'a>, ud_comp_hdr: &UdCompHdr) -> types::Result<'a, Self> {
+        let iq_data_size = ud_comp_hdr.prb_sample_size();  // (I + Q) * mantissa * re_count / byte_len
+        let (remaining, comp_param) = if ud_comp_hdr.has_comp_param() {
+            be_u8(data)?
+        } else {
+            (data, 0)
+        };
         nom_map(
-            nom_tuple((
-                be_u8,
-                // IQPrbuData::take_27_count,
-                // IQPrbuData::take_1_prbu_data,
-                nom_take(iq_data_size),
-            )),
-            |(byte_u8, iq_sample)| Self {
-                reserved: (byte_u8 >> 4) as u8,
-                exponent: (byte_u8 & 0x0F) as u8,
+            nom_take(iq_data_size),
+            move |iq_sample: &[u8]| Self {
+                reserved: comp_param >> 4,
+                exponent: comp_param & 0x0F,
                 iq_sample: iq_sample.to_vec(),
+                ud_comp_hdr: *ud_comp_hdr,
             }
-        )(data)
+        )(remaining)
     }
 }
 
@@ -881,7 +1601,8 @@ impl fmt::Display for IQPrbuData {
     fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
         write!(
             w,
-            "{:02X}, {:02X}, {:?}",
+            "{}, {:02X}, {:02X}, {:?}",
+            self.ud_comp_hdr,
             self.reserved,
             self.exponent,
             self.iq_sample,
@@ -928,7 +1649,7 @@ impl From<u16> for MessageType {
             _ if item == Self::IWFMapping as u16 => Self::IWFMapping,
             _ if item >= 12 && item <= 63 => Self::Reserved,
             _ if item >= 64 && item <= 225 => Self::VendorSpecific,
-            _ => { panic!("MessageType error: Shouldn't come here."); },
+            _ => Self::Reserved,    // 226..255 reserved
         }
     }
 }
@@ -942,13 +1663,77 @@ impl From<u16> for MessageType {
 //                        | eCPRI Transport Header | eCPRI application header(s) | eCPRI application payload |
 // The "eCPRI application header(s)" and "eCPRI application payload" depend on the message type                                                                     
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum EcpriType {
     IQData(Box<UPlaneIQData>),
     FCPType0(Box<FCPSectionType0>),
     FCPType1(Box<FCPSectionType1>),
     // FCPType2(FCPSectionType2),
     FCPType3(Box<FCPSectionType3>),
+    FCPType5(Box<FCPSectionType5>),
+    FCPType6(Box<FCPSectionType6>),
+    FCPType7(Box<FCPSectionType7>),
+    FCPUnknown(Box<FCPSectionUnknown>),
+}
+
+impl EcpriType {
+    // (dataDirection, frameId, subframeId, slotId, startSymbolId) of the message
+    pub fn timing(&self) -> (DataDirection, u8, u8, u8, u8) {
+        let h = match self {
+            EcpriType::IQData(d) => return (d.dir, d.frame_id, d.subframe_id, d.slot_id, d.start_symbol_id),
+            EcpriType::FCPType0(d) => &d.comm_ctrl_info,
+            EcpriType::FCPType1(d) => &d.comm_ctrl_info,
+            EcpriType::FCPType3(d) => &d.comm_ctrl_info,
+            EcpriType::FCPType5(d) => &d.comm_ctrl_info,
+            EcpriType::FCPType6(d) => &d.comm_ctrl_info,
+            EcpriType::FCPType7(d) => &d.comm_ctrl_info,
+            EcpriType::FCPUnknown(d) => &d.comm_ctrl_info,
+        };
+        (h.dir, h.frame_id, h.subframe_id, h.slot_id, h.start_symbol_id)
+    }
+}
+
+// The M-Plane configuration the parser needs, which is not carried in the messages.
+#[derive(Clone, Debug)]
+pub struct ParseConfig {
+    pub comp_config: UPlaneCompConfig,  // U-Plane compression per eAxC
+    pub num_trx: usize,                 // number of TRX, ciIsample/ciQsample per PRB of section type 6,
+                                        // not carried by the messages nor implied by their length
+    pub eaxc_config: EaxcIdConfig,      // split of the eAxC ID
+}
+
+impl Default for ParseConfig {
+    // 9 bits BFP, the default eAxC ID split and 64 TRX (a 64T64R massive MIMO RU),
+    // section type 6 messages of RUs with another number of TRX don't parse with it.
+    fn default() -> Self {
+        Self {
+            comp_config: UPlaneCompConfig::default(),
+            num_trx: 64,
+            eaxc_config: EaxcIdConfig::default(),
+        }
+    }
+}
+
+// Why a frame can't be taken as an eCPRI message, or a message can't be built
+#[derive(Debug, PartialEq)]
+pub enum EcpriError {
+    NotEcpri(u16),      // the EtherType (behind the VLAN tag) isn't 0xAEFE
+    Truncated,          // the frame ends within the eCPRI message
+    UnsupportedMessageType(u8),     // only IQ data (0) and real-time control data (2) are parsed
+    Malformed(&'static str),        // the message doesn't parse as the named part
+    PayloadTooLarge(usize),         // a built message doesn't fit in the 16 bits ecpriPayload
+}
+
+impl fmt::Display for EcpriError {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EcpriError::NotEcpri(ether_type) => write!(w, "not an eCPRI message, EtherType 0x{:04X}", ether_type),
+            EcpriError::Truncated => write!(w, "the eCPRI message is truncated"),
+            EcpriError::UnsupportedMessageType(message_type) => write!(w, "eCPRI message type {} is not supported", message_type),
+            EcpriError::Malformed(part) => write!(w, "can't parse {}", part),
+            EcpriError::PayloadTooLarge(size) => write!(w, "eCPRI payload of {} bytes is beyond 65535 bytes", size),
+        }
+    }
 }
 
 pub fn is_ecpri_data(data: &[u8]) -> types::Result<bool> {
@@ -961,54 +1746,184 @@ pub fn is_ecpri_data(data: &[u8]) -> types::Result<bool> {
     )(data)
 }
 
-// pub fn ecpri_parse(data: types::Input) -> types::Result<Self> {
-pub fn ecpri_parse(data: &[u8], mantissa: u16) -> EcpriType {
-    let msg_type: EcpriType;
+// The eCPRI common header and the payload behind it, without the ethernet padding.
+// data follows the EtherType of the ethernet header: VLAN TCI + 0xAEFE + eCPRI message
+// for 0x8100, the eCPRI message directly for 0xAEFE.
+pub fn ecpri_split(ether_type: u16, data: &[u8]) -> Result<(CommonHeader, &[u8]), EcpriError> {
+    let remain = match ether_type {
+        ECPRI_MAGIC_NUM => data,
+        _ if ether_type == SUPPORTED::ECPRI as u16 => {
+            // check if the data is ecpri data
+            let (remain, is_ecpri) = is_ecpri_data(data).map_err(|_| EcpriError::Truncated)?;
+            if !is_ecpri {
+                return Err(EcpriError::NotEcpri(u16::from_be_bytes([data[2], data[3]])));
+            }
+            remain
+        },
+        _ => return Err(EcpriError::NotEcpri(ether_type)),
+    };
 
-    // check if the data is ecpri data
-    let (remain, is_ecpri) = is_ecpri_data(&data).expect("Can't parse the ECPRI header.");
-    if !is_ecpri {
-        panic!("This is not a ecpri package.");
+    let (remain, header) = CommonHeader::parse(remain).map_err(|_| EcpriError::Truncated)?;
+    // payload_size counts from ecpriPcid, which has been consumed with ecpriSeqid (4 bytes),
+    // anything behind the payload is ethernet padding.
+    let payload_end = std::cmp::min((header.payload_size as usize).saturating_sub(4), remain.len());
+    Ok((header, &remain[..payload_end]))
+}
+
+pub fn ecpri_parse(ether_type: u16, data: &[u8], config: &ParseConfig) -> Result<(CommonHeader, EcpriType), EcpriError> {
+    let (header, payload) = ecpri_split(ether_type, data)?;
+    let msg_type = ecpri_parse_payload(&header, payload, config)?;
+    Ok((header, msg_type))
+}
+
+// The error of a payload parser: Truncated if it ran out of data, Malformed with the part
+// being parsed otherwise.
+fn payload_error(part: &'static str) -> impl Fn(nom::Err<types::Error<&[u8]>>) -> EcpriError {
+    move |e| match e {
+        nom::Err::Error(e) | nom::Err::Failure(e)
+            if e.errors.iter().any(|(_, kind)| matches!(kind, types::ErrorKind::Nom(ErrorKind::Eof))) => EcpriError::Truncated,
+        _ => EcpriError::Malformed(part),
     }
+}
 
-    let (remain, header) = CommonHeader::parse(&remain).expect("Can't parse the ECPRI header.");
-    match &header.message_type {
+// Parse the payload of a complete message, fragmented messages must be reassembled first.
+pub fn ecpri_parse_payload(header: &CommonHeader, remain: &[u8], config: &ParseConfig) -> Result<EcpriType, EcpriError> {
+    let msg_type = match header.message_type {
         0 => {  // U-Plane IQ data
-            println!("U-Plane IQ data.");
-            let (remain, iq_data) = UPlaneIQData::parse(&remain, mantissa).expect("Can't parse U-Plane IQ data.");
-            // use hex_slice::AsHex;
-            println!("IQ Prbu length: {}", iq_data.iq_prbu.len());
-            // for prbu in iq_data.iq_prbu.iter() {
-            //     println!("IQ data: {:?}", prbu);
-            // }
-            // println!("iq data: {:0X}", iq_data.as_hex());
-            msg_type = EcpriType::IQData(Box::new(iq_data));
-            // use std::mem;
-            // println!("IQ data len: {}", mem::size_of::<UPlaneIQData>());
+            let (_, iq_data) = UPlaneIQData::parse(remain, config.comp_config.get(header.pcid)).map_err(payload_error("U-Plane IQ data"))?;
+            EcpriType::IQData(Box::new(iq_data))
         },
         2 => {  // Fast-Control Plane
-            let (remain, timing_header) = TimingHeader::parse(&remain).expect("Can't parse timing header of ECPRI.");
-            match &timing_header.section_type {
-                0 => {
-                    println!("Idle/Guard periods.");
-                    let (remain, fcp_sect_type0) = FCPSectionType0::parse(&remain, timing_header.num_of_sections as usize).expect("Can't parse FCP section type 0 data.");
-                    msg_type = EcpriType::FCPType0(Box::new(fcp_sect_type0));
+            // the timing header is parsed again as part of the section type message
+            let (_, timing_header) = TimingHeader::parse(remain).map_err(payload_error("the timing header"))?;
+            match timing_header.section_type {
+                0 => {  // Idle/Guard periods
+                    let (_, fcp_sect_type0) = FCPSectionType0::parse(remain).map_err(payload_error("FCP section type 0 data"))?;
+                    EcpriType::FCPType0(Box::new(fcp_sect_type0))
                 },
-                1 => {
-                    println!("UL/DL channel.");
-                    let (remain, fcp_sect_type1) = FCPSectionType1::parse(&remain, timing_header.num_of_sections as usize).expect("Can't parse FCP section type 1 data.");
-                    msg_type = EcpriType::FCPType1(Box::new(fcp_sect_type1));
+                1 => {  // UL/DL channel
+                    let (_, fcp_sect_type1) = FCPSectionType1::parse(remain).map_err(payload_error("FCP section type 1 data"))?;
+                    EcpriType::FCPType1(Box::new(fcp_sect_type1))
                 },
-                3 => {
-                    println!("PRACH/mixed numerology channel.");
-                    let (remain, fcp_sect_type3) = FCPSectionType3::parse(&remain).expect("Can't parse FCP section type 3 data.");
-                    msg_type = EcpriType::FCPType3(Box::new(fcp_sect_type3));
+                3 => {  // PRACH/mixed numerology channel
+                    let (_, fcp_sect_type3) = FCPSectionType3::parse(remain).map_err(payload_error("FCP section type 3 data"))?;
+                    EcpriType::FCPType3(Box::new(fcp_sect_type3))
                 },
-                item => { panic!("eCpri parse: timing header: section type: {} Shouldn't come here.", item); }
+                5 => {  // UE scheduling information
+                    let (_, fcp_sect_type5) = FCPSectionType5::parse(remain).map_err(payload_error("FCP section type 5 data"))?;
+                    EcpriType::FCPType5(Box::new(fcp_sect_type5))
+                },
+                6 => {  // Channel information
+                    let (_, fcp_sect_type6) = FCPSectionType6::parse(remain, config.num_trx).map_err(payload_error("FCP section type 6 data"))?;
+                    EcpriType::FCPType6(Box::new(fcp_sect_type6))
+                },
+                7 => {  // LAA
+                    let (_, fcp_sect_type7) = FCPSectionType7::parse(remain).map_err(payload_error("FCP section type 7 data"))?;
+                    EcpriType::FCPType7(Box::new(fcp_sect_type7))
+                },
+                _ => {  // kept raw
+                    let (_, fcp_sect_unknown) = FCPSectionUnknown::parse(remain).map_err(payload_error("FCP section data"))?;
+                    EcpriType::FCPUnknown(Box::new(fcp_sect_unknown))
+                }
             }
         },
-        _ => { panic!("eCpri parse: common header: Shouldn't come here."); }
+        item => return Err(EcpriError::UnsupportedMessageType(item)),
+    };
+
+    Ok(msg_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::compression::UdCompMeth;
+    use crate::protocols::serialize::ToBytes;
+
+    // UL PRACH, format 0 (1.25 kHz) on a 30 kHz carrier, two sections for two occasions
+    const SECTION_TYPE3: [u8; 28] = [
+        0x11, 0x2A, 0x30, 0x40, 0x02, 0x03,  // UL, payloadVer 1, PRACH format 0 filter, frame 0x2A, subframe 3, slot 1
+        0x0C, 0xA0, 0xCC, 0x01, 0x20, 0x91,  // timeOffset, 4096 FFT + 1.25 kHz, cpLength, 9 bits BFP
+        0x00, 0x10, 0x00, 0x0C, 0xFF, 0xFC, 0x00, 0x05, 0xFF, 0xE7, 0x16, 0x00,  // freqOffset -6378
+        0x00, 0x20, 0x0C, 0x0C,              // second section, startPrbc 12
+    ];
+
+    #[test]
+    fn section_type3_layout() {
+        let mut data = SECTION_TYPE3.to_vec();
+        data.extend(&[0xFF, 0xFC, 0x00, 0x05, 0x00, 0x12, 0x34, 0x00]);
+        let (remain, type3) = FCPSectionType3::parse(&data).unwrap();
+        assert!(remain.is_empty());
+        assert_eq!(type3.comm_ctrl_info.num_of_sections, 2);
+        assert_eq!(type3.comm_ctrl_info.section_type, 3);
+        assert_eq!(type3.comm_ctrl_info.filter_index, FilterIndex::PRACH_1_25);
+        assert_eq!(type3.time_offset, 0x0CA0);
+        assert_eq!(type3.frame_structure.fft_size, FFTSize::I_4096);
+        assert_eq!(type3.frame_structure.mu, MU::KHZ_1_25);
+        assert_eq!(type3.cp_length, 0x0120);
+        assert_eq!(type3.ud_comp_hdr, UdCompHdr::new(9, UdCompMeth::BlockFloatingPoint));
+        assert_eq!(type3.sections.len(), 2);
+        assert_eq!(type3.sections[0].section_hdr.section_id, 1);
+        assert_eq!(type3.sections[0].section_hdr.num_prbc, 12);
+        assert_eq!(type3.sections[0].re_mask, 0xFFF);
+        assert_eq!(type3.sections[0].num_symbol, 12);
+        assert_eq!(type3.sections[0].beam_id, 5);
+        assert_eq!(type3.sections[0].freq_offset, -6378);
+        assert_eq!(type3.sections[0].freq_offset_hz(1250.0), -3986250.0);
+        assert_eq!(type3.sections[1].section_hdr.section_id, 2);
+        assert_eq!(type3.sections[1].section_hdr.start_prbc, 12);
+        assert_eq!(type3.sections[1].freq_offset, 0x1234);
+        assert_eq!(type3.to_bytes(), data);
+    }
+
+    // DL guard period over the last 2 symbols of the slot
+    const SECTION_TYPE0: [u8; 20] = [
+        0x90, 0x07, 0x52, 0xCC, 0x01, 0x00,  // DL, payloadVer 1, frame 7, subframe 5, slot 11, symbol 12
+        0x00, 0x00, 0xC1, 0x00, 0x00, 0x00,  // timeOffset, 4096 FFT + 30 kHz, cpLength, reserved
+        0x00, 0x30, 0x00, 0x00, 0xFF, 0xF2, 0x00, 0x00,  // section 3, all 273 PRBs (numPrbc 0)
+    ];
+
+    #[test]
+    fn section_type0_layout() {
+        let (remain, type0) = FCPSectionType0::parse(&SECTION_TYPE0).unwrap();
+        assert!(remain.is_empty());
+        assert_eq!(type0.comm_ctrl_info.dir, DataDirection::DL);
+        assert_eq!(type0.comm_ctrl_info.subframe_id, 5);
+        assert_eq!(type0.comm_ctrl_info.slot_id, 11);
+        assert_eq!(type0.comm_ctrl_info.start_symbol_id, 12);
+        assert_eq!(type0.frame_structure.mu, MU::KHZ_30);
+        assert_eq!(type0.sections.len(), 1);
+        assert_eq!(type0.sections[0].section_hdr.section_id, 3);
+        assert_eq!(type0.sections[0].section_hdr.num_prbc, 0);
+        assert_eq!(type0.sections[0].num_symbol, 2);
+        assert_eq!(type0.sections[0].ef, 0);
+        assert_eq!(type0.to_bytes(), SECTION_TYPE0.to_vec());
+    }
+
+    #[test]
+    fn split_rejects_other_frames() {
+        // VLAN tag + IPv4
+        assert_eq!(ecpri_split(0x8100, &[0xE0, 0x01, 0x08, 0x00, 0x45, 0x00, 0x00, 0x54]).unwrap_err(), EcpriError::NotEcpri(0x0800));
+        assert_eq!(ecpri_split(0x0800, &[0x45, 0x00, 0x00, 0x54]).unwrap_err(), EcpriError::NotEcpri(0x0800));
+        assert_eq!(ecpri_split(0x8100, &[0xE0, 0x01, 0xAE]).unwrap_err(), EcpriError::Truncated);
+        assert_eq!(ecpri_split(0x8100, &[0xE0, 0x01, 0xAE, 0xFE, 0x10, 0x00]).unwrap_err(), EcpriError::Truncated);
+        // the padding behind the payload is dropped
+        let (header, payload) = ecpri_split(0x8100, &[0xE0, 0x01, 0xAE, 0xFE, 0x10, 0x02, 0x00, 0x06, 0x00, 0x80, 0x12, 0x80, 0xAA, 0xBB, 0x00]).unwrap();
+        assert_eq!(header.pcid, 0x0080);
+        assert_eq!(payload, &[0xAA, 0xBB]);
     }
 
-    msg_type
-}
\ No newline at end of file
+    #[test]
+    fn unsupported_message_types() {
+        // one-way delay measurement, not parsed
+        let (header, payload) = ecpri_split(0xAEFE, &[0x10, 0x05, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]).unwrap();
+        assert_eq!(ecpri_parse_payload(&header, payload, &ParseConfig::default()).unwrap_err(), EcpriError::UnsupportedMessageType(5));
+        // a U-Plane message cut within its timing header
+        assert_eq!(ecpri_parse(0xAEFE, &[0x10, 0x00, 0x00, 0x06, 0x00, 0x00, 0x00, 0x00, 0x10, 0x01], &ParseConfig::default()).unwrap_err(),
+                   EcpriError::Truncated);
+        // one 9 bits BFP PRB announced, 4 of its 27 sample bytes present
+        let data = [0x10, 0x00, 0x00, 0x11, 0x00, 0x00, 0x00, 0x00, 0x90, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x01,
+                    0x03, 0x12, 0x34, 0x56, 0x78];
+        assert_eq!(ecpri_parse(0xAEFE, &data, &ParseConfig::default()).unwrap_err(), EcpriError::Truncated);
+        assert!(matches!(MessageType::from(0xFF), MessageType::Reserved));
+    }
+}
@@ -18,7 +18,9 @@ pub struct Ethernet {
 #[derive(Debug, PartialEq)]
 pub enum SUPPORTED {
     BIP = 0x8951,
-    ECPRI = 0x8100,
+    ECPRI = 0x8100,             // eCPRI behind a VLAN tag
+    #[allow(non_camel_case_types)]
+    ECPRI_UNTAGGED = 0xAEFE,    // eCPRI without VLAN tag
     PTP = 0x88F7,
 }
 
@@ -44,6 +46,7 @@ impl From<u16> for PacketDataType {
         match item {
             _ if item == SUPPORTED::BIP as u16 => Self::SUPPORTED(SUPPORTED::BIP),
             _ if item == SUPPORTED::ECPRI as u16 => Self::SUPPORTED(SUPPORTED::ECPRI),
+            _ if item == SUPPORTED::ECPRI_UNTAGGED as u16 => Self::SUPPORTED(SUPPORTED::ECPRI_UNTAGGED),
             _ if item == SUPPORTED::PTP as u16 => Self::SUPPORTED(SUPPORTED::PTP),
             _ => Self::UNKNOWN(item),
         }
@@ -85,7 +88,26 @@ impl Ethernet {
     }
 }
 
-pub struct MacAddr([u8; 6]);
+// IEEE 802.1Q tag control information, follows the 0x8100 TPID
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VlanTag {
+    pub pcp: u8,    // priority code point, 3 bits
+    pub dei: u8,    // drop eligible indicator, 1 bit
+    pub vid: u16,   // VLAN identifier, 12 bits
+}
+
+impl VlanTag {
+    pub fn parse(i: types::Input) -> types::Result<Self> {
+        nom_map(be_u16, |tci| Self {
+            pcp: (tci >> 13) as u8,
+            dei: ((tci >> 12) & 0x1) as u8,
+            vid: tci & 0x0FFF,
+        })(i)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct MacAddr(pub [u8; 6]);
 
 impl fmt::Display for MacAddr{
     fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
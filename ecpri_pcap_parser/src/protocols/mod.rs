@@ -6,8 +6,16 @@ pub mod types;
 pub mod ethernet;
 pub mod bip;
 pub mod ecpri;
+pub mod compression;
+pub mod section_extension;
+pub mod eaxc;
+pub mod serialize;
 
 pub use types::*;
 pub use ethernet::*;
 pub use bip::*;
 pub use ecpri::*;
+pub use compression::*;
+pub use section_extension::*;
+pub use eaxc::*;
+pub use serialize::*;
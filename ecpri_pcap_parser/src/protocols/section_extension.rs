@@ -0,0 +1,605 @@
+use std::fmt;
+use nom::{
+    number::complete::{be_u16, be_u8},
+    bytes::complete::take as nom_take,
+};
+use num::complex::Complex;
+use crate::protocols::types;
+use crate::protocols::compression::{UdCompHdr, UdCompMeth, ModCompParams, Beamspace, Decompressor, decompressor};
+
+// C-Plane section extension, follows the section fields when ef = 1:
+//   :ef (1bit), another extension follows this one
+//   :extType (7bit), type of the extension
+//   :extLen (8bit, 16bit for extType 11), length of the extension in 32 bits words,
+//                    ef/extType/extLen and the padding included
+#[derive(PartialEq)]
+pub struct SectionExtension {
+    pub ef: u8,             // 1 bit
+    pub ext_type: u8,       // 7 bits
+    pub ext_len: u16,       // 1 or 2 bytes
+    pub payload: Vec<u8>,   // extLen * 4 bytes without ef/extType/extLen
+    pub ext: ExtensionData, // the decoded payload
+}
+
+impl SectionExtension {
+    // extType 11 is the only one with 16 bits extLen
+    pub fn header_size(ext_type: u8) -> usize {
+        if ext_type == 11 { 3 } else { 2 }
+    }
+
+    pub fn parse(data: types::Input) -> types::Result<Self> {
+        let (remaining, byte_u8) = be_u8(data)?;
+        let ef = byte_u8 >> 7;
+        let ext_type = byte_u8 & 0x7F;
+        let (remaining, ext_len) = if ext_type == 11 {
+            be_u16(remaining)?
+        } else {
+            let (remaining, ext_len) = be_u8(remaining)?;
+            (remaining, ext_len as u16)
+        };
+        let payload_size = (ext_len as usize * 4).saturating_sub(SectionExtension::header_size(ext_type));
+        let (remain, payload) = nom_take(payload_size)(remaining)?;
+        Ok((remain, Self {
+            ef,
+            ext_type,
+            ext_len,
+            payload: payload.to_vec(),
+            ext: ExtensionData::from_bytes(ext_type, payload),
+        }))
+    }
+
+    // Parse the extensions until the one with ef = 0
+    pub fn parse_chain(data: types::Input) -> types::Result<Vec<Self>> {
+        let mut extensions = Vec::new();
+        let mut remaining = data;
+        loop {
+            let (remain, extension) = SectionExtension::parse(remaining)?;
+            let ef = extension.ef;
+            extensions.push(extension);
+            remaining = remain;
+            if ef == 0 {
+                break;
+            }
+        }
+        Ok((remaining, extensions))
+    }
+
+    // Parse the chain only if the section has its ef bit set
+    pub fn parse_if(ef: u8, data: types::Input) -> types::Result<Vec<Self>> {
+        if ef == 1 {
+            SectionExtension::parse_chain(data)
+        } else {
+            Ok((data, Vec::new()))
+        }
+    }
+}
+
+impl fmt::Display for SectionExtension {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            w,
+            "{:02X}, {:02X}, {:04X}, {:?}",
+            self.ef,
+            self.ext_type,
+            self.ext_len,
+            self.ext,
+        )
+    }
+}
+
+impl fmt::Debug for SectionExtension {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+// MSB first bit reader over the extension payload, reads zeros beyond the end.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn read(&mut self, len: usize) -> u32 {
+        let mut value = 0u32;
+        for _ in 0..len {
+            let byte = self.data.get(self.pos / 8).cloned().unwrap_or(0);
+            value = (value << 1) | ((byte >> (7 - self.pos % 8)) & 1) as u32;
+            self.pos += 1;
+        }
+        value
+    }
+
+    fn remaining_bits(&self) -> usize {
+        (self.data.len() * 8).saturating_sub(self.pos)
+    }
+}
+
+// The typed content of the extensions, the less common ones are kept as raw bytes.
+#[derive(Debug, PartialEq)]
+pub enum ExtensionData {
+    BeamformingWeights(Ext1BeamformingWeights),        // extType 1
+    BeamformingAttributes(Ext2BeamformingAttributes),  // extType 2
+    DlPrecoding(Ext3DlPrecoding),                      // extType 3
+    ModCompParams(ModCompParams),                      // extType 4
+    ModCompMultipleParams(Vec<Ext5ModCompSet>),        // extType 5
+    NonContiguousPrb(Ext6NonContiguousPrb),            // extType 6
+    BeamGroups(Ext10BeamGroups),                       // extType 10
+    FlexibleWeights(Ext11FlexibleWeights),             // extType 11
+    NonContiguousPrbAllocation(Ext12NonContiguousPrb), // extType 12
+    FrequencyHopping(Vec<Ext13PrbAllocation>),         // extType 13
+    Raw(Vec<u8>),                                      // all the other types
+}
+
+impl ExtensionData {
+    pub fn from_bytes(ext_type: u8, payload: &[u8]) -> Self {
+        let mut r = BitReader::new(payload);
+        match ext_type {
+            1 => Self::BeamformingWeights(Ext1BeamformingWeights {
+                bfw_comp_hdr: UdCompHdr::from(r.read(8) as u8),
+                data: payload.get(1..).unwrap_or_default().to_vec(),
+            }),
+            2 => {
+                let widths = [r.read(3), r.read(3), r.read(3), r.read(3)];
+                r.read(4);
+                let mut field = |w: u32| if w == 0 { None } else { Some(r.read(w as usize + 1) as u8) };
+                let bf_az_pt = field(widths[0]);
+                let bf_ze_pt = field(widths[1]);
+                let bf_az_3dd = field(widths[2]);
+                let bf_ze_3dd = field(widths[3]);
+                Self::BeamformingAttributes(Ext2BeamformingAttributes {
+                    bf_az_pt_width: widths[0] as u8,
+                    bf_ze_pt_width: widths[1] as u8,
+                    bf_az_3dd_width: widths[2] as u8,
+                    bf_ze_3dd_width: widths[3] as u8,
+                    bf_az_pt,
+                    bf_ze_pt,
+                    bf_az_3dd,
+                    bf_ze_3dd,
+                    bf_az_sl: r.read(3) as u8,
+                    bf_ze_sl: r.read(3) as u8,
+                })
+            },
+            3 => {
+                let codebook_index = r.read(8) as u8;
+                let layer_id = r.read(4) as u8;
+                let num_layers = r.read(4) as u8;
+                let first_layer = if layer_id == 0 {
+                    let tx_scheme = r.read(4) as u8;
+                    let crs_re_mask = r.read(12) as u16;
+                    let crs_shift = r.read(1) as u8;
+                    r.read(3);
+                    let crs_sym_num = r.read(4) as u8;
+                    r.read(8);
+                    let mut beam_id = || { r.read(1); r.read(15) as u16 };
+                    Some(Ext3FirstLayer {
+                        tx_scheme,
+                        crs_re_mask,
+                        crs_shift,
+                        crs_sym_num,
+                        beam_id_ap1: beam_id(),
+                        beam_id_ap2: beam_id(),
+                        beam_id_ap3: beam_id(),
+                    })
+                } else {
+                    None
+                };
+                Self::DlPrecoding(Ext3DlPrecoding { codebook_index, layer_id, num_layers, first_layer })
+            },
+            4 => Self::ModCompParams(ModCompParams {
+                csf: r.read(1) == 1,
+                mod_comp_scaler: r.read(15) as u16,
+            }),
+            5 => {
+                let mut sets = Vec::new();
+                while r.remaining_bits() >= 28 {
+                    let mc_scale_re_mask = r.read(12) as u16;
+                    let csf = r.read(1) == 1;
+                    let mc_scale_offset = r.read(15) as u16;
+                    if mc_scale_re_mask == 0 {
+                        break;  // padding
+                    }
+                    sets.push(Ext5ModCompSet { mc_scale_re_mask, csf, mc_scale_offset });
+                }
+                Self::ModCompMultipleParams(sets)
+            },
+            6 => Self::NonContiguousPrb(Ext6NonContiguousPrb {
+                repetition: r.read(1) as u8,
+                rbg_size: r.read(3) as u8,
+                rbg_mask: r.read(28),
+                priority: r.read(2) as u8,
+                symbol_mask: r.read(14) as u16,
+            }),
+            10 => {
+                let beam_group_type = r.read(2) as u8;
+                let num_portc = r.read(6) as u8;
+                let beam_ids = if beam_group_type == 2 {
+                    // beam vector listing, the beamId of the first port is the one in the section
+                    (1..num_portc).map(|_| { r.read(1); r.read(15) as u16 }).collect()
+                } else {
+                    Vec::new()
+                };
+                Self::BeamGroups(Ext10BeamGroups { num_portc, beam_group_type, beam_ids })
+            },
+            11 => {
+                let disable_bfws = r.read(1) as u8;
+                let rad = r.read(1) as u8;
+                r.read(6);
+                let num_bund_prb = r.read(8) as u8;
+                let (bfw_comp_hdr, offset) = if disable_bfws == 0 {
+                    (Some(UdCompHdr::from(r.read(8) as u8)), 3)
+                } else {
+                    (None, 2)
+                };
+                Self::FlexibleWeights(Ext11FlexibleWeights {
+                    disable_bfws,
+                    rad,
+                    num_bund_prb,
+                    bfw_comp_hdr,
+                    data: payload[offset.min(payload.len())..].to_vec(),
+                })
+            },
+            12 => {
+                let priority = r.read(2) as u8;
+                let symbol_mask = r.read(14) as u16;
+                let mut ranges = Vec::new();
+                while r.remaining_bits() >= 16 {
+                    let off_start_prb = r.read(8) as u8;
+                    let num_prb = r.read(8) as u8;
+                    if num_prb == 0 {
+                        break;  // padding
+                    }
+                    ranges.push(Ext12PrbRange { off_start_prb, num_prb });
+                }
+                Self::NonContiguousPrbAllocation(Ext12NonContiguousPrb { priority, symbol_mask, ranges })
+            },
+            13 => {
+                let mut allocations = Vec::new();
+                while r.remaining_bits() >= 16 {
+                    r.read(2);
+                    let next_symbol_id = r.read(4) as u8;
+                    let next_start_prbc = r.read(10) as u16;
+                    allocations.push(Ext13PrbAllocation { next_symbol_id, next_start_prbc });
+                }
+                // the padding looks like an allocation to symbol 0, PRB 0
+                while allocations.len() > 1 && allocations.last() == Some(&Ext13PrbAllocation::default()) {
+                    allocations.pop();
+                }
+                Self::FrequencyHopping(allocations)
+            },
+            _ => Self::Raw(payload.to_vec()),
+        }
+    }
+
+    // Modulation compression parameters of extType 4, or of the first set of extType 5.
+    pub fn mod_comp_params(&self) -> Option<ModCompParams> {
+        match self {
+            Self::ModCompParams(params) => Some(*params),
+            Self::ModCompMultipleParams(sets) => sets.first().map(|s| ModCompParams {
+                csf: s.csf,
+                mod_comp_scaler: s.mc_scale_offset,
+            }),
+            _ => None,
+        }
+    }
+}
+
+// bfwCompParam size in bytes, the beamspace one (bfwCompMeth = 4) depends on the number of TRX (K)
+fn bfw_comp_param_size(bfw_comp_hdr: &UdCompHdr, num_trx: usize) -> usize {
+    match bfw_comp_hdr.ud_comp_meth as u8 {
+        0 => 0,
+        1..=3 => 1,
+        4 => num_trx.div_ceil(8) + 1,   // beamspace: activeBeamspaceCoefficientMask + exponent
+        _ => 0,
+    }
+}
+
+// Decompress the weights (bfwCompParam + bfwI/bfwQ of each TRX) at the start of data,
+// return them with the number of bytes they take.
+// The weights are cut at the end of data, so a short extension gives the first TRX only.
+fn decode_weights(data: &[u8], bfw_comp_hdr: &UdCompHdr, num_trx: usize) -> (Vec<Complex<f32>>, usize) {
+    let param_size = bfw_comp_param_size(bfw_comp_hdr, num_trx);
+    let param = &data[..param_size.min(data.len())];
+    let rest = &data[param_size.min(data.len())..];
+    let width = bfw_comp_hdr.iq_width();
+    // complete bfwI/bfwQ pairs in the data
+    let available = rest.len() * 8 / (width as usize * 2);
+    if bfw_comp_hdr.ud_comp_meth as u8 == 4 {
+        let active = (0..num_trx).filter(|&k| param.get(k / 8).is_some_and(|m| m & (0x80 >> (k % 8)) != 0)).collect::<Vec<_>>();
+        // up to the first active coefficient which is missing
+        let num_trx = active.get(available).cloned().unwrap_or(num_trx);
+        let size = (active.len().min(available) * width as usize * 2).div_ceil(8);
+        let weights = Beamspace { iq_width: width }.decompress(param, rest, num_trx).unwrap_or_default();
+        (weights, param_size + size)
+    } else {
+        let num_trx = num_trx.min(available);
+        let size = (num_trx * width as usize * 2).div_ceil(8);
+        // bfwCompMeth shares the values 0..3 with udCompMeth, 5..15 are reserved
+        let hdr = UdCompHdr::new(bfw_comp_hdr.ud_iq_width, match bfw_comp_hdr.ud_comp_meth {
+            meth @ UdCompMeth::BlockFloatingPoint | meth @ UdCompMeth::BlockScaling | meth @ UdCompMeth::MuLaw => meth,
+            _ => UdCompMeth::NoCompression,
+        });
+        let weights = decompressor(&hdr, None).and_then(|d| d.decompress(param, rest, num_trx)).unwrap_or_default();
+        (weights, param_size + size)
+    }
+}
+
+// extType 1: beamforming weights of every TRX for the section
+//   :bfwCompHdr (8bit), bfwIqWidth (4bit) + bfwCompMeth (4bit)
+//   :bfwCompParam (0, 1 or variable bytes) + bfwI/bfwQ * numberOfTRX
+// The number of TRX is configured by the M-Plane, so the weights are decoded on demand.
+#[derive(Debug, PartialEq)]
+pub struct Ext1BeamformingWeights {
+    pub bfw_comp_hdr: UdCompHdr,
+    pub data: Vec<u8>,   // bfwCompParam + weights + padding
+}
+
+impl Ext1BeamformingWeights {
+    pub fn weights(&self, num_trx: usize) -> Vec<Complex<f32>> {
+        decode_weights(&self.data, &self.bfw_comp_hdr, num_trx).0
+    }
+}
+
+// extType 2: beamforming attributes (azimuth/zenith pointing angles, 3 dB beamwidths and sidelobe suppression)
+//   :bfaCompHdr (16bit), widths of bfAzPt, bfZePt, bfAz3dd, bfZe3dd (3bit each, 0 = not present,
+//                        n = n + 1 bits) + reserved (4bit)
+//   :bfAzPt, bfZePt, bfAz3dd, bfZe3dd, bfAzSl (3bit), bfZeSl (3bit)
+#[derive(Debug, PartialEq)]
+pub struct Ext2BeamformingAttributes {
+    pub bf_az_pt_width: u8,
+    pub bf_ze_pt_width: u8,
+    pub bf_az_3dd_width: u8,
+    pub bf_ze_3dd_width: u8,
+    pub bf_az_pt: Option<u8>,
+    pub bf_ze_pt: Option<u8>,
+    pub bf_az_3dd: Option<u8>,
+    pub bf_ze_3dd: Option<u8>,
+    pub bf_az_sl: u8,
+    pub bf_ze_sl: u8,
+}
+
+// extType 3: DL precoding configuration (LTE transmission modes)
+//   :codebookIndex (8bit), layerID (4bit), numLayers (4bit)
+//   first data layer only: txScheme (4bit), crsReMask (12bit), crsShift (1bit), reserved (3bit),
+//   crsSymNum (4bit), reserved (8bit), beamIdAP1, beamIdAP2, beamIdAP3 (1 reserved + 15bit each)
+#[derive(Debug, PartialEq)]
+pub struct Ext3DlPrecoding {
+    pub codebook_index: u8,
+    pub layer_id: u8,
+    pub num_layers: u8,
+    pub first_layer: Option<Ext3FirstLayer>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Ext3FirstLayer {
+    pub tx_scheme: u8,
+    pub crs_re_mask: u16,
+    pub crs_shift: u8,
+    pub crs_sym_num: u8,
+    pub beam_id_ap1: u16,
+    pub beam_id_ap2: u16,
+    pub beam_id_ap3: u16,
+}
+
+// extType 5: one set of modulation compression parameters
+//   :mcScaleReMask (12bit), csf (1bit), mcScaleOffset (15bit)
+#[derive(Debug, PartialEq)]
+pub struct Ext5ModCompSet {
+    pub mc_scale_re_mask: u16,
+    pub csf: bool,
+    pub mc_scale_offset: u16,
+}
+
+// extType 6: non-contiguous PRB allocation in time and frequency domain
+//   :repetition (1bit), rbgSize (3bit), rbgMask (28bit), priority (2bit), symbolMask (14bit)
+#[derive(Debug, PartialEq)]
+pub struct Ext6NonContiguousPrb {
+    pub repetition: u8,
+    pub rbg_size: u8,     // 0: reserved, 1..7: 1, 2, 3, 4, 8, 16 PRBs
+    pub rbg_mask: u32,
+    pub priority: u8,
+    pub symbol_mask: u16,
+}
+
+impl Ext6NonContiguousPrb {
+    // The PRBs in one resource block group
+    pub fn rbg_prbs(&self) -> u16 {
+        match self.rbg_size {
+            1 => 1,
+            2 => 2,
+            3 => 3,
+            4 => 4,
+            5 => 8,
+            6 => 16,
+            _ => 0,
+        }
+    }
+}
+
+// extType 10: section description for a group of configured eAxC
+//   :beamGroupType (2bit), numPortc (6bit), beamId (1 reserved + 15bit) per port if beamGroupType = 2
+#[derive(Debug, PartialEq)]
+pub struct Ext10BeamGroups {
+    pub num_portc: u8,
+    pub beam_group_type: u8,  // 0: common beam, 1: beam matrix indication, 2: beam vector listing
+    pub beam_ids: Vec<u16>,
+}
+
+// extType 11: flexible beamforming weights, one set of weights per PRB bundle
+//   :disableBFWs (1bit), RAD (1bit), reserved (6bit), numBundPrb (8bit), bfwCompHdr (8bit, if weights present)
+//   :per bundle: bfwCompParam + beamId (1 reserved + 15bit) + bfwI/bfwQ * numberOfTRX, or only beamId
+//    (16bit) if disableBFWs = 1
+#[derive(Debug, PartialEq)]
+pub struct Ext11FlexibleWeights {
+    pub disable_bfws: u8,
+    pub rad: u8,
+    pub num_bund_prb: u8,
+    pub bfw_comp_hdr: Option<UdCompHdr>,
+    pub data: Vec<u8>,   // the bundles + padding
+}
+
+impl Ext11FlexibleWeights {
+    // (beamId, weights) of every bundle, the number of bundles is ceil(numPrbc / numBundPrb).
+    pub fn bundles(&self, num_prbc: usize, num_trx: usize) -> Vec<(u16, Vec<Complex<f32>>)> {
+        let num_bundles = if self.num_bund_prb == 0 { 0 } else { num_prbc.div_ceil(self.num_bund_prb as usize) };
+        let mut bundles = Vec::with_capacity(num_bundles);
+        let mut data = &self.data[..];
+        for _ in 0..num_bundles {
+            match self.bfw_comp_hdr {
+                None => {
+                    if data.len() < 2 { break; }
+                    bundles.push((u16::from_be_bytes([data[0], data[1]]) & 0x7FFF, Vec::new()));
+                    data = &data[2..];
+                },
+                Some(hdr) => {
+                    let param_size = bfw_comp_param_size(&hdr, num_trx);
+                    if data.len() < param_size + 2 { break; }
+                    let beam_id = u16::from_be_bytes([data[param_size], data[param_size + 1]]) & 0x7FFF;
+                    // the beamId sits between bfwCompParam and the weights
+                    let mut weights_data = data[..param_size].to_vec();
+                    weights_data.extend_from_slice(&data[param_size + 2..]);
+                    let (weights, size) = decode_weights(&weights_data, &hdr, num_trx);
+                    bundles.push((beam_id, weights));
+                    data = &data[(size + 2).min(data.len())..];
+                },
+            }
+        }
+        bundles
+    }
+}
+
+// extType 12: non-contiguous PRB allocation with off-start PRBs
+//   :priority (2bit), symbolMask (14bit)
+//   :per range: offStartPrb (8bit), numPrb (8bit), relative to the end of the previous range
+#[derive(Debug, PartialEq)]
+pub struct Ext12NonContiguousPrb {
+    pub priority: u8,
+    pub symbol_mask: u16,
+    pub ranges: Vec<Ext12PrbRange>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Ext12PrbRange {
+    pub off_start_prb: u8,
+    pub num_prb: u8,
+}
+
+// extType 13: PRB allocation of the next symbols with frequency hopping
+//   :reserved (2bit), nextSymbolId (4bit), nextStartPrbc (10bit)
+#[derive(Debug, Default, PartialEq)]
+pub struct Ext13PrbAllocation {
+    pub next_symbol_id: u8,
+    pub next_start_prbc: u16,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::compression::pack_samples;
+
+    #[test]
+    fn parse_extension_chain() {
+        let data = [
+            0x84, 0x01, 0x80, 0x10,                          // ext 4, csf = 1, modCompScaler = 0x10, ef = 1
+            0x86, 0x02, 0xA5, 0x00, 0x00, 0x0F, 0x7F, 0xFF,  // ext 6, rbgSize 2, rbgMask 0x500000F, priority 1, symbolMask 0x3FFF
+            0x0C, 0x02, 0x80, 0x0C, 0x0A, 0x14, 0x00, 0x00,  // ext 12, priority 2, symbolMask 0x000C, (10, 20), padding
+            0xFF,
+        ];
+        let (remain, extensions) = SectionExtension::parse_chain(&data).unwrap();
+        assert_eq!(remain, &[0xFF]);
+        assert_eq!(extensions.len(), 3);
+        assert_eq!(extensions[0].ext, ExtensionData::ModCompParams(ModCompParams { csf: true, mod_comp_scaler: 0x10 }));
+        assert_eq!(extensions[0].ext.mod_comp_params(), Some(ModCompParams { csf: true, mod_comp_scaler: 0x10 }));
+        assert_eq!(extensions[1].ext, ExtensionData::NonContiguousPrb(Ext6NonContiguousPrb {
+            repetition: 1,
+            rbg_size: 2,
+            rbg_mask: 0x500000F,
+            priority: 1,
+            symbol_mask: 0x3FFF,
+        }));
+        assert_eq!(extensions[2].ext, ExtensionData::NonContiguousPrbAllocation(Ext12NonContiguousPrb {
+            priority: 2,
+            symbol_mask: 0x000C,
+            ranges: vec![Ext12PrbRange { off_start_prb: 10, num_prb: 20 }],
+        }));
+    }
+
+    #[test]
+    fn parse_beam_groups() {
+        // beamGroupType 2 (beam vector listing), numPortc 3: the beamIds of ports 1 and 2
+        let data = [0x0A, 0x02, 0x83, 0x00, 0x12, 0x80, 0x34, 0x00];
+        let (_, extensions) = SectionExtension::parse_chain(&data).unwrap();
+        assert_eq!(extensions[0].ext, ExtensionData::BeamGroups(Ext10BeamGroups {
+            num_portc: 3,
+            beam_group_type: 2,
+            beam_ids: vec![0x0012, 0x0034],
+        }));
+        // an extType 1 without payload doesn't panic
+        assert_eq!(ExtensionData::from_bytes(1, &[]), ExtensionData::BeamformingWeights(Ext1BeamformingWeights {
+            bfw_comp_hdr: UdCompHdr::from(0),
+            data: Vec::new(),
+        }));
+    }
+
+    #[test]
+    fn parse_beamforming_weights() {
+        // 4 TRX, 16 bits weights without compression
+        let weights = [(1, -1), (100, 200), (-32768, 32767), (0, 0)];
+        let mut data = vec![0x01, 0x05, 0x00];
+        data.extend(pack_samples(&weights, 16));
+        data.extend(&[0, 0, 0, 0, 0]);  // padding to 5 words
+        let (_, extensions) = SectionExtension::parse_chain(&data).unwrap();
+        match &extensions[0].ext {
+            ExtensionData::BeamformingWeights(ext1) => {
+                let decoded = ext1.weights(4);
+                assert_eq!(decoded.iter().map(|w| (w.re as i32, w.im as i32)).collect::<Vec<_>>(), weights.to_vec());
+            },
+            ext => panic!("Unexpected extension: {:?}", ext),
+        }
+    }
+
+    #[test]
+    fn truncated_beamforming_weights() {
+        // extLen 2 words: the 16 bits weights of the 4 TRX are cut after the first one, ef = 1
+        let mut data = vec![0x81, 0x02, 0x00];
+        data.extend(&pack_samples(&[(7, -7), (8, -8)], 16)[..5]);
+        // beamspace, every coefficient active: one 8 bits weight fits
+        data.extend(&[0x01, 0x02, 0x84, 0xF0, 0x00, 0x01, 0x02, 0x03]);
+        let (_, extensions) = SectionExtension::parse_chain(&data).unwrap();
+        let weights = extensions.iter()
+            .map(|e| match &e.ext {
+                ExtensionData::BeamformingWeights(ext1) => ext1.weights(4),
+                ext => panic!("Unexpected extension: {:?}", ext),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(weights, vec![vec![Complex::new(7.0, -7.0)], vec![Complex::new(1.0, 2.0)]]);
+    }
+
+    #[test]
+    fn parse_flexible_weights() {
+        // 2 bundles, 2 TRX, 9 bits BFP weights
+        let mut data = vec![0x0B, 0x00, 0x06, 0x00, 0x02, 0x91];
+        for (beam_id, exponent) in [(0x0010u16, 1u8), (0x0020, 2)].iter() {
+            data.push(*exponent);
+            data.extend(&beam_id.to_be_bytes());
+            data.extend(pack_samples(&[(1, 2), (-3, -4)], 9));
+        }
+        data.resize(24, 0);
+        let (_, extensions) = SectionExtension::parse_chain(&data).unwrap();
+        match &extensions[0].ext {
+            ExtensionData::FlexibleWeights(ext11) => {
+                let bundles = ext11.bundles(4, 2);
+                assert_eq!(bundles.len(), 2);
+                assert_eq!(bundles[0].0, 0x0010);
+                assert_eq!(bundles[0].1[1], Complex::new(-6.0, -8.0));
+                assert_eq!(bundles[1].0, 0x0020);
+                assert_eq!(bundles[1].1[0], Complex::new(4.0, 8.0));
+            },
+            ext => panic!("Unexpected extension: {:?}", ext),
+        }
+    }
+}
@@ -0,0 +1,829 @@
+// Serialization of the eCPRI/O-RAN messages, the reverse of the parse functions.
+// Every type writes exactly the bytes its parse function consumes, so test harnesses can
+// craft packets and parse(to_bytes(x)) == x holds.
+use std::convert::TryFrom;
+use crate::protocols::ethernet::{Ethernet, MacAddr, VlanTag, SUPPORTED};
+use crate::protocols::compression::UdCompHdr;
+use crate::protocols::ecpri::*;
+use crate::protocols::section_extension::SectionExtension;
+
+const ECPRI_ETHER_TYPE: u16 = 0xAEFE;
+// Minimum ethernet frame without FCS
+const MIN_FRAME_SIZE: usize = 60;
+
+pub trait ToBytes {
+    fn write_bytes(&self, buf: &mut Vec<u8>);
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.write_bytes(&mut buf);
+        buf
+    }
+}
+
+impl ToBytes for MacAddr {
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.0);
+    }
+}
+
+impl ToBytes for Ethernet {
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        self.dst_mac_addr.write_bytes(buf);
+        self.src_mac_addr.write_bytes(buf);
+        buf.extend_from_slice(&self.ether_type.to_be_bytes());
+    }
+}
+
+impl ToBytes for VlanTag {
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        let tci = ((self.pcp as u16 & 0x7) << 13) | ((self.dei as u16 & 0x1) << 12) | (self.vid & 0x0FFF);
+        buf.extend_from_slice(&tci.to_be_bytes());
+    }
+}
+
+impl ToBytes for CommonHeader {
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        buf.push((self.revision << 4) | ((self.reserved & 0x7) << 1) | (self.concatenation & 0x1));
+        buf.push(self.message_type);
+        buf.extend_from_slice(&self.payload_size.to_be_bytes());
+        buf.extend_from_slice(&self.pcid.to_be_bytes());
+        buf.extend_from_slice(&self.seqid.to_be_bytes());
+    }
+}
+
+// dataDirection + payloadVersion + filterIndex, frameId, subframeId + slotId + startSymbolid,
+// the first 4 bytes of the timing header are shared by the C-Plane and U-Plane messages.
+fn write_timing(buf: &mut Vec<u8>, header: &TimingHeader) {
+    buf.push(((header.dir as u8) << 7) | ((header.payload_ver & 0x7) << 4) | (u8::from(header.filter_index) & 0x0F));
+    buf.push(header.frame_id);
+    let byte_u16 = ((header.subframe_id as u16 & 0x0F) << 12)
+        | ((header.slot_id as u16 & 0x3F) << 6)
+        | (header.start_symbol_id as u16 & 0x3F);
+    buf.extend_from_slice(&byte_u16.to_be_bytes());
+}
+
+impl ToBytes for TimingHeader {
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        write_timing(buf, self);
+        buf.push(self.num_of_sections);
+        buf.push(self.section_type);
+    }
+}
+
+impl ToBytes for UdCompHdr {
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        buf.push((self.ud_iq_width << 4) | (self.ud_comp_meth as u8 & 0x0F));
+    }
+}
+
+impl ToBytes for SectionHeader {
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        let byte_u24 = ((self.section_id as u32 & 0x0FFF) << 12)
+            | ((self.rb as u32 & 0x1) << 11)
+            | ((self.si as u32 & 0x1) << 10)
+            | (self.start_prbc as u32 & 0x3FF);
+        buf.extend_from_slice(&byte_u24.to_be_bytes()[1..]);
+        buf.push(self.num_prbc);
+        if let Some(ud_comp_hdr) = self.ud_comp_hdr {
+            ud_comp_hdr.write_bytes(buf);
+            buf.push(self.reserved.unwrap_or(0));
+        }
+    }
+}
+
+impl ToBytes for FrameStructure {
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        buf.push(((self.fft_size as u8) << 4) | (self.mu as u8 & 0x0F));
+    }
+}
+
+impl ToBytes for SectionExtension {
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        buf.push(((self.ef & 0x1) << 7) | (self.ext_type & 0x7F));
+        if SectionExtension::header_size(self.ext_type) == 3 {
+            buf.extend_from_slice(&self.ext_len.to_be_bytes());
+        } else {
+            buf.push(self.ext_len as u8);
+        }
+        buf.extend_from_slice(&self.payload);
+    }
+}
+
+// reMask (12 bits) + numSymbol (4 bits), ef (1 bit) + 15 bits
+fn write_re_mask(buf: &mut Vec<u8>, re_mask: u16, num_symbol: u8, ef: u8, low_15_bits: u16) {
+    buf.extend_from_slice(&(((re_mask & 0x0FFF) << 4) | (num_symbol as u16 & 0x0F)).to_be_bytes());
+    buf.extend_from_slice(&(((ef as u16 & 0x1) << 15) | (low_15_bits & 0x7FFF)).to_be_bytes());
+}
+
+impl ToBytes for _SectionType0Data {
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        self.section_hdr.write_bytes(buf);
+        write_re_mask(buf, self.re_mask, self.num_symbol, self.ef, self.reserved);
+        self.extensions.iter().for_each(|e| e.write_bytes(buf));
+    }
+}
+
+impl ToBytes for FCPSectionType0 {
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        self.comm_ctrl_info.write_bytes(buf);
+        buf.extend_from_slice(&self.time_offset.to_be_bytes());
+        self.frame_structure.write_bytes(buf);
+        buf.extend_from_slice(&self.cp_length.to_be_bytes());
+        buf.push(self.reserved);
+        self.sections.iter().for_each(|s| s.write_bytes(buf));
+    }
+}
+
+impl ToBytes for _SectionType1Data {
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        self.section_hdr.write_bytes(buf);
+        write_re_mask(buf, self.re_mask, self.num_symbol, self.ef, self.beam_id);
+        self.extensions.iter().for_each(|e| e.write_bytes(buf));
+    }
+}
+
+impl ToBytes for FCPSectionType1 {
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        self.comm_ctrl_info.write_bytes(buf);
+        buf.push(self.ud_comp_hdr);
+        buf.push(self.reserved);
+        self.sections.iter().for_each(|s| s.write_bytes(buf));
+    }
+}
+
+impl ToBytes for _SectionType3Data {
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        self.section_hdr.write_bytes(buf);
+        write_re_mask(buf, self.re_mask, self.num_symbol, self.ef, self.beam_id);
+        buf.extend_from_slice(&self.freq_offset.to_be_bytes()[1..]);
+        buf.push(self.reserved);
+        self.extensions.iter().for_each(|e| e.write_bytes(buf));
+    }
+}
+
+impl ToBytes for FCPSectionType3 {
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        self.comm_ctrl_info.write_bytes(buf);
+        buf.extend_from_slice(&self.time_offset.to_be_bytes());
+        self.frame_structure.write_bytes(buf);
+        buf.extend_from_slice(&self.cp_length.to_be_bytes());
+        self.ud_comp_hdr.write_bytes(buf);
+        self.sections.iter().for_each(|s| s.write_bytes(buf));
+    }
+}
+
+impl ToBytes for _SectionType5Data {
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        self.section_hdr.write_bytes(buf);
+        write_re_mask(buf, self.re_mask, self.num_symbol, self.ef, self.ue_id);
+        self.extensions.iter().for_each(|e| e.write_bytes(buf));
+    }
+}
+
+impl ToBytes for FCPSectionType5 {
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        self.comm_ctrl_info.write_bytes(buf);
+        buf.push(self.ud_comp_hdr);
+        buf.push(self.reserved);
+        self.sections.iter().for_each(|s| s.write_bytes(buf));
+    }
+}
+
+impl ToBytes for ChannelInfoPrb {
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        if let Some(comp_param) = self.ci_comp_param {
+            buf.push(comp_param);
+        }
+        buf.extend_from_slice(&self.ci_sample);
+    }
+}
+
+impl ToBytes for _SectionType6Data {
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&(((self.ef as u16 & 0x1) << 15) | (self.ue_id & 0x7FFF)).to_be_bytes());
+        buf.extend_from_slice(&self.regularization_factor.to_be_bytes());
+        let byte_u16 = ((self.reserved as u16 & 0x0F) << 12)
+            | ((self.rb as u16 & 0x1) << 11)
+            | ((self.si as u16 & 0x1) << 10)
+            | (self.start_prbc & 0x3FF);
+        buf.extend_from_slice(&byte_u16.to_be_bytes());
+        buf.push(self.num_prbc);
+        self.ci_prbs.iter().for_each(|p| p.write_bytes(buf));
+        self.extensions.iter().for_each(|e| e.write_bytes(buf));
+    }
+}
+
+impl ToBytes for FCPSectionType6 {
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        self.comm_ctrl_info.write_bytes(buf);
+        buf.push(self.number_of_ues);
+        self.ci_comp_hdr.write_bytes(buf);
+        self.sections.iter().for_each(|s| s.write_bytes(buf));
+    }
+}
+
+impl LaaMessage {
+    // Write `len` bits of value at bit `offset` of the message
+    fn set_bits(msg: &mut [u8], offset: u32, len: u32, value: u32) {
+        for n in 0..len {
+            if (value >> (len - 1 - n)) & 1 == 1 {
+                let bit = offset + n;
+                msg[(bit / 8) as usize] |= 0x80 >> (bit % 8);
+            }
+        }
+    }
+
+    // The message bytes behind laaMsgType/laaMsgLen, laa_msg_len words totally.
+    pub fn to_bytes(&self, laa_msg_len: u8) -> Vec<u8> {
+        let mut msg = vec![0u8; (laa_msg_len as usize * 4).saturating_sub(1)];
+        let lbt_handle = match self {
+            LaaMessage::LbtPdschReq { lbt_handle, lbt_offset, lbt_mode, lbt_defer_factor, lbt_backoff_counter, mcot } => {
+                LaaMessage::set_bits(&mut msg, 16, 10, *lbt_offset as u32);
+                LaaMessage::set_bits(&mut msg, 26, 2, *lbt_mode as u32);
+                LaaMessage::set_bits(&mut msg, 29, 3, *lbt_defer_factor as u32);
+                LaaMessage::set_bits(&mut msg, 32, 10, *lbt_backoff_counter as u32);
+                LaaMessage::set_bits(&mut msg, 42, 4, *mcot as u32);
+                *lbt_handle
+            },
+            LaaMessage::LbtDrsReq { lbt_handle, lbt_offset, lbt_mode } => {
+                LaaMessage::set_bits(&mut msg, 16, 10, *lbt_offset as u32);
+                LaaMessage::set_bits(&mut msg, 26, 2, *lbt_mode as u32);
+                *lbt_handle
+            },
+            LaaMessage::LbtPdschRsp { lbt_handle, lbt_pdsch_res, in_par_sf, sf_status, sfn_sf } => {
+                LaaMessage::set_bits(&mut msg, 16, 2, *lbt_pdsch_res as u32);
+                LaaMessage::set_bits(&mut msg, 18, 1, *in_par_sf as u32);
+                LaaMessage::set_bits(&mut msg, 19, 1, *sf_status as u32);
+                LaaMessage::set_bits(&mut msg, 20, 12, *sfn_sf as u32);
+                *lbt_handle
+            },
+            LaaMessage::LbtDrsRsp { lbt_handle, lbt_drs_res } => {
+                LaaMessage::set_bits(&mut msg, 16, 1, *lbt_drs_res as u32);
+                *lbt_handle
+            },
+            LaaMessage::LbtBufferError { lbt_handle, lbt_buf_err } => {
+                LaaMessage::set_bits(&mut msg, 16, 1, *lbt_buf_err as u32);
+                *lbt_handle
+            },
+            LaaMessage::LbtCwConfigReq { lbt_handle, lbt_cw_config_h, lbt_cw_config_t, lbt_mode, lbt_traffic_class } => {
+                LaaMessage::set_bits(&mut msg, 16, 8, *lbt_cw_config_h as u32);
+                LaaMessage::set_bits(&mut msg, 24, 8, *lbt_cw_config_t as u32);
+                LaaMessage::set_bits(&mut msg, 32, 2, *lbt_mode as u32);
+                LaaMessage::set_bits(&mut msg, 34, 3, *lbt_traffic_class as u32);
+                *lbt_handle
+            },
+            LaaMessage::LbtCwConfigRsp { lbt_handle, lbt_cwr_rst } => {
+                LaaMessage::set_bits(&mut msg, 16, 1, *lbt_cwr_rst as u32);
+                *lbt_handle
+            },
+            LaaMessage::Reserved(data) => {
+                let n = std::cmp::min(data.len(), msg.len());
+                msg[..n].copy_from_slice(&data[..n]);
+                return msg;
+            },
+        };
+        LaaMessage::set_bits(&mut msg, 0, 16, lbt_handle as u32);
+        msg
+    }
+}
+
+impl ToBytes for FCPSectionType7 {
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        self.comm_ctrl_info.write_bytes(buf);
+        buf.extend_from_slice(&self.reserved.to_be_bytes());
+        buf.push((self.laa_msg_type << 4) | (self.laa_msg_len & 0x0F));
+        buf.extend(self.laa_msg.to_bytes(self.laa_msg_len));
+    }
+}
+
+impl ToBytes for FCPSectionUnknown {
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        self.comm_ctrl_info.write_bytes(buf);
+        buf.extend_from_slice(&self.data);
+    }
+}
+
+impl ToBytes for IQPrbuData {
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        if let Some(comp_param) = self.ud_comp_param() {
+            buf.push(comp_param);
+        }
+        buf.extend_from_slice(&self.iq_sample);
+    }
+}
+
+impl ToBytes for UPlaneSection {
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        self.section_hdr.write_bytes(buf);
+        self.iq_prbu.iter().for_each(|p| p.write_bytes(buf));
+    }
+}
+
+impl ToBytes for UPlaneIQData {
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        let timing = TimingHeader {
+            dir: self.dir,
+            payload_ver: self.payload_ver,
+            filter_index: self.filter_index,
+            frame_id: self.frame_id,
+            subframe_id: self.subframe_id,
+            slot_id: self.slot_id,
+            start_symbol_id: self.start_symbol_id,
+            num_of_sections: 0,
+            section_type: 0,
+        };
+        write_timing(buf, &timing);
+        self.sections.iter().for_each(|s| s.write_bytes(buf));
+    }
+}
+
+impl EcpriType {
+    // eCPRI message type of the message
+    pub fn message_type(&self) -> u8 {
+        match self {
+            EcpriType::IQData(_) => MessageType::IQData as u8,
+            _ => MessageType::RealTimeControlData as u8,
+        }
+    }
+}
+
+impl ToBytes for EcpriType {
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        match self {
+            EcpriType::IQData(d) => d.write_bytes(buf),
+            EcpriType::FCPType0(d) => d.write_bytes(buf),
+            EcpriType::FCPType1(d) => d.write_bytes(buf),
+            EcpriType::FCPType3(d) => d.write_bytes(buf),
+            EcpriType::FCPType5(d) => d.write_bytes(buf),
+            EcpriType::FCPType6(d) => d.write_bytes(buf),
+            EcpriType::FCPType7(d) => d.write_bytes(buf),
+            EcpriType::FCPUnknown(d) => d.write_bytes(buf),
+        }
+    }
+}
+
+// Builds complete ethernet frames (without FCS) carrying one eCPRI message:
+// | dst MAC | src MAC | 0x8100 | VLAN TCI | 0xAEFE | eCPRI common header | message |
+// or without VLAN tag:
+// | dst MAC | src MAC | 0xAEFE | eCPRI common header | message |
+pub struct EcpriPacketBuilder {
+    dst_mac_addr: MacAddr,
+    src_mac_addr: MacAddr,
+    vlan: Option<VlanTag>,
+    revision: u8,
+    pcid: u16,
+    seqid: u16,
+}
+
+impl EcpriPacketBuilder {
+    pub fn new(dst_mac_addr: MacAddr, src_mac_addr: MacAddr) -> Self {
+        Self {
+            dst_mac_addr,
+            src_mac_addr,
+            vlan: None,
+            revision: 1,
+            pcid: 0,
+            seqid: 0,
+        }
+    }
+
+    pub fn vlan(mut self, vlan: VlanTag) -> Self {
+        self.vlan = Some(vlan);
+        self
+    }
+
+    pub fn revision(mut self, revision: u8) -> Self {
+        self.revision = revision;
+        self
+    }
+
+    pub fn pcid(mut self, pcid: u16) -> Self {
+        self.pcid = pcid;
+        self
+    }
+
+    pub fn seqid(mut self, seqid: u16) -> Self {
+        self.seqid = seqid;
+        self
+    }
+
+    // The common header of the message, payload size counts from ecpriPcid.
+    pub fn common_header(&self, message: &EcpriType, message_len: usize) -> Result<CommonHeader, EcpriError> {
+        let payload_size = message_len + 4;
+        Ok(CommonHeader {
+            revision: self.revision,
+            reserved: 0,
+            concatenation: 0,
+            message_type: message.message_type(),
+            payload_size: u16::try_from(payload_size).map_err(|_| EcpriError::PayloadTooLarge(payload_size))?,
+            pcid: self.pcid,
+            seqid: self.seqid,
+        })
+    }
+
+    pub fn build(&self, message: &EcpriType) -> Result<Vec<u8>, EcpriError> {
+        let body = message.to_bytes();
+        let ether_type = match self.vlan {
+            Some(_) => SUPPORTED::ECPRI as u16,
+            None => ECPRI_ETHER_TYPE,
+        };
+        let mut frame = Ethernet {
+            dst_mac_addr: self.dst_mac_addr,
+            src_mac_addr: self.src_mac_addr,
+            ether_type,
+        }.to_bytes();
+        if let Some(vlan) = self.vlan {
+            vlan.write_bytes(&mut frame);
+            frame.extend_from_slice(&ECPRI_ETHER_TYPE.to_be_bytes());
+        }
+        self.common_header(message, body.len())?.write_bytes(&mut frame);
+        frame.extend(body);
+        if frame.len() < MIN_FRAME_SIZE {
+            frame.resize(MIN_FRAME_SIZE, 0);
+        }
+        Ok(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::compression::{UdCompMeth, EaxcCompConfig};
+    use crate::protocols::section_extension::ExtensionData;
+
+    // Deterministic pseudo random generator, good enough to walk the value ranges.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self, max: u32) -> u32 {
+            self.0 = self.0.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+            ((self.0 >> 33) % (max as u64 + 1)) as u32
+        }
+    }
+
+    fn timing_header(rng: &mut Rng, num_of_sections: u8, section_type: u8) -> TimingHeader {
+        TimingHeader {
+            dir: DataDirection::from(rng.next(1) as u8),
+            payload_ver: 1,
+            filter_index: FilterIndex::from(rng.next(15) as u8),
+            frame_id: rng.next(255) as u8,
+            subframe_id: rng.next(9) as u8,
+            slot_id: rng.next(63) as u8,
+            start_symbol_id: rng.next(13) as u8,
+            num_of_sections,
+            section_type,
+        }
+    }
+
+    fn section_header(rng: &mut Rng, ud_comp_hdr: Option<UdCompHdr>) -> SectionHeader {
+        SectionHeader {
+            section_id: rng.next(0xFFF) as u16,
+            rb: rng.next(1) as u8,
+            si: rng.next(1) as u8,
+            start_prbc: rng.next(0x3FF) as u16,
+            num_prbc: rng.next(255) as u8,
+            ud_comp_hdr,
+            reserved: ud_comp_hdr.map(|_| 0),
+        }
+    }
+
+    fn frame_structure(rng: &mut Rng) -> FrameStructure {
+        FrameStructure {
+            fft_size: FFTSize::from(8 + rng.next(4) as u8),
+            mu: MU::from(rng.next(5) as u8),
+        }
+    }
+
+    #[test]
+    fn common_header_round_trip() {
+        let mut rng = Rng(1);
+        for _ in 0..100 {
+            let header = CommonHeader {
+                revision: rng.next(15) as u8,
+                reserved: rng.next(7) as u8,
+                concatenation: rng.next(1) as u8,
+                message_type: rng.next(7) as u8,
+                payload_size: rng.next(0xFFFF) as u16,
+                pcid: rng.next(0xFFFF) as u16,
+                seqid: rng.next(0xFFFF) as u16,
+            };
+            let bytes = header.to_bytes();
+            assert_eq!(bytes.len(), 8);
+            assert_eq!(CommonHeader::parse(&bytes).unwrap(), (&[][..], header));
+        }
+    }
+
+    #[test]
+    fn c_plane_round_trip() {
+        let mut rng = Rng(2);
+        for _ in 0..50 {
+            let num = rng.next(5) as u8;
+            let type0 = FCPSectionType0 {
+                comm_ctrl_info: timing_header(&mut rng, num, 0),
+                time_offset: rng.next(0xFFFF) as u16,
+                frame_structure: frame_structure(&mut rng),
+                cp_length: rng.next(0xFFFF) as u16,
+                reserved: 0,
+                sections: (0..num).map(|_| _SectionType0Data {
+                    section_hdr: section_header(&mut rng, None),
+                    re_mask: rng.next(0xFFF) as u16,
+                    num_symbol: rng.next(14) as u8,
+                    ef: 0,
+                    reserved: rng.next(0x7FFF) as u16,
+                    extensions: Vec::new(),
+                }).collect(),
+            };
+            assert_eq!(FCPSectionType0::parse(&type0.to_bytes()).unwrap(), (&[][..], type0));
+
+            let type1 = FCPSectionType1 {
+                comm_ctrl_info: timing_header(&mut rng, num, 1),
+                ud_comp_hdr: rng.next(255) as u8,
+                reserved: 0,
+                sections: (0..num).map(|_| _SectionType1Data {
+                    section_hdr: section_header(&mut rng, None),
+                    re_mask: rng.next(0xFFF) as u16,
+                    num_symbol: rng.next(14) as u8,
+                    ef: 0,
+                    beam_id: rng.next(0x7FFF) as u16,
+                    extensions: Vec::new(),
+                }).collect(),
+            };
+            assert_eq!(FCPSectionType1::parse(&type1.to_bytes()).unwrap(), (&[][..], type1));
+
+            let type3 = FCPSectionType3 {
+                comm_ctrl_info: timing_header(&mut rng, num, 3),
+                time_offset: rng.next(0xFFFF) as u16,
+                frame_structure: frame_structure(&mut rng),
+                cp_length: rng.next(0xFFFF) as u16,
+                ud_comp_hdr: UdCompHdr::new(rng.next(15) as u8, UdCompMeth::from(rng.next(6) as u8)),
+                sections: (0..num).map(|_| _SectionType3Data {
+                    section_hdr: section_header(&mut rng, None),
+                    re_mask: rng.next(0xFFF) as u16,
+                    num_symbol: rng.next(14) as u8,
+                    ef: 0,
+                    beam_id: rng.next(0x7FFF) as u16,
+                    freq_offset: rng.next(0xFFFFFF) as i32 - 0x800000,
+                    reserved: 0,
+                    extensions: Vec::new(),
+                }).collect(),
+            };
+            assert_eq!(FCPSectionType3::parse(&type3.to_bytes()).unwrap(), (&[][..], type3));
+        }
+    }
+
+    #[test]
+    fn c_plane_extensions_round_trip() {
+        let mut rng = Rng(8);
+        for _ in 0..20 {
+            let num = 1 + rng.next(3) as u8;
+            let type1 = FCPSectionType1 {
+                comm_ctrl_info: timing_header(&mut rng, num, 1),
+                ud_comp_hdr: rng.next(255) as u8,
+                reserved: 0,
+                sections: (0..num).map(|_| {
+                    let mut extensions: Vec<SectionExtension> = (0..rng.next(3)).map(|_| {
+                        let ext_type = [1, 4, 6, 11, 12, 20][rng.next(5) as usize];
+                        let ext_len = 1 + rng.next(4) as u16;
+                        let payload: Vec<u8> = (0..ext_len as usize * 4 - SectionExtension::header_size(ext_type))
+                            .map(|_| rng.next(255) as u8).collect();
+                        SectionExtension {
+                            ef: 1,
+                            ext_type,
+                            ext_len,
+                            ext: ExtensionData::from_bytes(ext_type, &payload),
+                            payload,
+                        }
+                    }).collect();
+                    if let Some(last) = extensions.last_mut() {
+                        last.ef = 0;
+                    }
+                    _SectionType1Data {
+                        section_hdr: section_header(&mut rng, None),
+                        re_mask: rng.next(0xFFF) as u16,
+                        num_symbol: rng.next(14) as u8,
+                        ef: if extensions.is_empty() { 0 } else { 1 },
+                        beam_id: rng.next(0x7FFF) as u16,
+                        extensions,
+                    }
+                }).collect(),
+            };
+            assert_eq!(FCPSectionType1::parse(&type1.to_bytes()).unwrap(), (&[][..], type1));
+        }
+    }
+
+    #[test]
+    fn c_plane_ue_round_trip() {
+        let mut rng = Rng(5);
+        for _ in 0..20 {
+            let num = rng.next(4) as u8;
+            let type5 = FCPSectionType5 {
+                comm_ctrl_info: timing_header(&mut rng, num, 5),
+                ud_comp_hdr: rng.next(255) as u8,
+                reserved: 0,
+                sections: (0..num).map(|_| _SectionType5Data {
+                    section_hdr: section_header(&mut rng, None),
+                    re_mask: rng.next(0xFFF) as u16,
+                    num_symbol: rng.next(14) as u8,
+                    ef: 0,
+                    ue_id: rng.next(0x7FFF) as u16,
+                    extensions: Vec::new(),
+                }).collect(),
+            };
+            assert_eq!(FCPSectionType5::parse(&type5.to_bytes()).unwrap(), (&[][..], type5));
+
+            let num_trx = 1 + rng.next(15) as usize;
+            let ci_comp_hdr = if rng.next(1) == 0 {
+                UdCompHdr::new(16, UdCompMeth::NoCompression)
+            } else {
+                UdCompHdr::new(1 + rng.next(14) as u8, UdCompMeth::BlockFloatingPoint)
+            };
+            let type6 = FCPSectionType6 {
+                comm_ctrl_info: timing_header(&mut rng, num, 6),
+                number_of_ues: num,
+                ci_comp_hdr,
+                sections: (0..num).map(|_| {
+                    let num_prbc = rng.next(8) as u8;
+                    _SectionType6Data {
+                        ef: 0,
+                        ue_id: rng.next(0x7FFF) as u16,
+                        regularization_factor: rng.next(0xFFFF) as u16 as i16,
+                        reserved: 0,
+                        rb: rng.next(1) as u8,
+                        si: rng.next(1) as u8,
+                        start_prbc: rng.next(0x3FF) as u16,
+                        num_prbc,
+                        ci_prbs: (0..num_prbc).map(|_| ChannelInfoPrb {
+                            ci_comp_param: if ci_comp_hdr.has_comp_param() { Some(rng.next(15) as u8) } else { None },
+                            ci_sample: (0..(num_trx * ci_comp_hdr.iq_width() as usize * 2).div_ceil(8))
+                                .map(|_| rng.next(255) as u8).collect(),
+                        }).collect(),
+                        extensions: Vec::new(),
+                    }
+                }).collect(),
+            };
+            assert_eq!(FCPSectionType6::parse(&type6.to_bytes(), num_trx).unwrap(), (&[][..], type6));
+        }
+    }
+
+    #[test]
+    fn laa_round_trip() {
+        let mut rng = Rng(6);
+        let messages = vec![
+            (0, 2, LaaMessage::LbtPdschReq {
+                lbt_handle: 0x1234, lbt_offset: 0x3FF, lbt_mode: 2, lbt_defer_factor: 7, lbt_backoff_counter: 0x155, mcot: 10,
+            }),
+            (1, 2, LaaMessage::LbtDrsReq { lbt_handle: 0xFFFF, lbt_offset: 0x100, lbt_mode: 3 }),
+            (2, 2, LaaMessage::LbtPdschRsp { lbt_handle: 1, lbt_pdsch_res: 2, in_par_sf: 1, sf_status: 0, sfn_sf: 0xABC }),
+            (3, 1, LaaMessage::LbtDrsRsp { lbt_handle: 2, lbt_drs_res: 1 }),
+            (4, 1, LaaMessage::LbtBufferError { lbt_handle: 3, lbt_buf_err: 1 }),
+            (5, 2, LaaMessage::LbtCwConfigReq {
+                lbt_handle: 4, lbt_cw_config_h: 0x55, lbt_cw_config_t: 0xAA, lbt_mode: 1, lbt_traffic_class: 5,
+            }),
+            (6, 1, LaaMessage::LbtCwConfigRsp { lbt_handle: 5, lbt_cwr_rst: 1 }),
+            (9, 2, LaaMessage::Reserved(vec![1, 2, 3, 4, 5, 6, 7])),
+        ];
+        for (laa_msg_type, laa_msg_len, laa_msg) in messages {
+            let type7 = FCPSectionType7 {
+                comm_ctrl_info: timing_header(&mut rng, 0, 7),
+                reserved: 0,
+                laa_msg_type,
+                laa_msg_len,
+                laa_msg,
+            };
+            let bytes = type7.to_bytes();
+            assert_eq!(bytes.len(), 6 + 2 + laa_msg_len as usize * 4);
+            assert_eq!(FCPSectionType7::parse(&bytes).unwrap(), (&[][..], type7));
+        }
+    }
+
+    #[test]
+    fn unknown_section_type_is_kept() {
+        let mut rng = Rng(7);
+        let message = EcpriType::FCPUnknown(Box::new(FCPSectionUnknown {
+            comm_ctrl_info: timing_header(&mut rng, 1, 8),
+            data: vec![0xDE, 0xAD, 0xBE, 0xEF, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07,
+                       0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F, 0x10, 0x11, 0x12, 0x13,
+                       0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1A, 0x1B, 0x1C, 0x1D, 0x1E, 0x1F],
+        }));
+        let frame = EcpriPacketBuilder::new(MacAddr([0; 6]), MacAddr([1; 6]))
+            .vlan(VlanTag { pcp: 7, dei: 0, vid: 1 })
+            .build(&message)
+            .unwrap();
+        let (ether_data, ether_header) = Ethernet::parse(&frame).unwrap();
+        assert_eq!(ecpri_parse(ether_header.ether_type, ether_data, &ParseConfig::default()).unwrap().1, message);
+    }
+
+    #[test]
+    fn channel_info_samples() {
+        // 2 TRX, 16 bits uncompressed: (1, -1), (0x7FFF, -0x8000)
+        let data = [0x00, 0x01, 0xFF, 0xFF, 0x7F, 0xFF, 0x80, 0x00];
+        let ci_comp_hdr = UdCompHdr::new(16, UdCompMeth::NoCompression);
+        let (_, prb) = ChannelInfoPrb::parse(&data, &ci_comp_hdr, 2).unwrap();
+        let samples = prb.get_ci_samples(&ci_comp_hdr, 2);
+        assert_eq!(samples[0].re, 1.0);
+        assert_eq!(samples[0].im, -1.0);
+        assert_eq!(samples[1].re, 32767.0);
+        assert_eq!(samples[1].im, -32768.0);
+    }
+
+    fn u_plane_message(rng: &mut Rng, ud_comp_hdr: UdCompHdr, dynamic: bool) -> UPlaneIQData {
+        let sections = (0..1 + rng.next(2)).map(|_| {
+            let mut section_hdr = section_header(rng, if dynamic { Some(ud_comp_hdr) } else { None });
+            section_hdr.num_prbc = 1 + rng.next(10) as u8;
+            let iq_prbu = (0..section_hdr.num_prbc).map(|_| {
+                let comp_param = if ud_comp_hdr.has_comp_param() { rng.next(255) as u8 } else { 0 };
+                IQPrbuData {
+                    reserved: comp_param >> 4,
+                    exponent: comp_param & 0x0F,
+                    iq_sample: (0..ud_comp_hdr.prb_sample_size()).map(|_| rng.next(255) as u8).collect(),
+                    ud_comp_hdr,
+                }
+            }).collect();
+            UPlaneSection { section_hdr, ud_comp_hdr, iq_prbu }
+        }).collect();
+        let timing = timing_header(rng, 0, 0);
+        UPlaneIQData {
+            dir: timing.dir,
+            payload_ver: timing.payload_ver,
+            filter_index: timing.filter_index,
+            frame_id: timing.frame_id,
+            subframe_id: timing.subframe_id,
+            slot_id: timing.slot_id,
+            start_symbol_id: timing.start_symbol_id,
+            sections,
+        }
+    }
+
+    #[test]
+    fn u_plane_round_trip() {
+        let mut rng = Rng(3);
+        for width in 1..=16 {
+            for &meth in [UdCompMeth::NoCompression, UdCompMeth::BlockFloatingPoint, UdCompMeth::MuLaw].iter() {
+                let ud_comp_hdr = UdCompHdr::new(width, meth);
+                let message = u_plane_message(&mut rng, ud_comp_hdr, false);
+                let bytes = message.to_bytes();
+                assert_eq!(UPlaneIQData::parse(&bytes, EaxcCompConfig::Static(ud_comp_hdr)).unwrap(), (&[][..], message));
+
+                let message = u_plane_message(&mut rng, ud_comp_hdr, true);
+                let bytes = message.to_bytes();
+                assert_eq!(UPlaneIQData::parse(&bytes, EaxcCompConfig::Dynamic).unwrap(), (&[][..], message));
+            }
+        }
+    }
+
+    #[test]
+    fn packet_round_trip() {
+        let mut rng = Rng(4);
+        let ud_comp_hdr = UdCompHdr::new(9, UdCompMeth::BlockFloatingPoint);
+        let builder = EcpriPacketBuilder::new(MacAddr([0x00, 0x11, 0x22, 0x33, 0x44, 0x55]), MacAddr([0x66, 0x77, 0x88, 0x99, 0xAA, 0xBB]))
+            .vlan(VlanTag { pcp: 7, dei: 0, vid: 2 })
+            .pcid(0x0080)
+            .seqid(0x1280);
+        let message = EcpriType::IQData(Box::new(u_plane_message(&mut rng, ud_comp_hdr, false)));
+        let frame = builder.build(&message).unwrap();
+
+        let (ether_data, ether_header) = Ethernet::parse(&frame).unwrap();
+        assert_eq!(ether_header.ether_type, SUPPORTED::ECPRI as u16);
+        let (header, parsed) = ecpri_parse(ether_header.ether_type, ether_data, &ParseConfig::default()).unwrap();
+        assert_eq!(header.pcid, 0x0080);
+        assert_eq!(header.seqid, 0x1280);
+        assert_eq!(parsed, message);
+
+        // A short C-Plane message is padded to the minimum frame size, the padding is ignored.
+        let message = EcpriType::FCPType1(Box::new(FCPSectionType1 {
+            comm_ctrl_info: timing_header(&mut rng, 0, 1),
+            ud_comp_hdr: 0x91,
+            reserved: 0,
+            sections: Vec::new(),
+        }));
+        let frame = builder.build(&message).unwrap();
+        assert_eq!(frame.len(), 60);
+        let (ether_data, ether_header) = Ethernet::parse(&frame).unwrap();
+        assert_eq!(ecpri_parse(ether_header.ether_type, ether_data, &ParseConfig::default()).unwrap().1, message);
+
+        // ecpriPayload is 16 bits and counts the 4 bytes of ecpriPcid + ecpriSeqid
+        assert_eq!(builder.common_header(&message, 65531).unwrap().payload_size, 0xFFFF);
+        assert_eq!(builder.common_header(&message, 65532).unwrap_err(), EcpriError::PayloadTooLarge(65536));
+    }
+
+    #[test]
+    fn untagged_packet_round_trip() {
+        let mut rng = Rng(5);
+        let ud_comp_hdr = UdCompHdr::new(9, UdCompMeth::BlockFloatingPoint);
+        let message = EcpriType::IQData(Box::new(u_plane_message(&mut rng, ud_comp_hdr, false)));
+        let frame = EcpriPacketBuilder::new(MacAddr([0x00, 0x11, 0x22, 0x33, 0x44, 0x55]), MacAddr([0x66, 0x77, 0x88, 0x99, 0xAA, 0xBB]))
+            .pcid(0x0081)
+            .seqid(0x0780)
+            .build(&message)
+            .unwrap();
+        assert_eq!(&frame[12..14], &[0xAE, 0xFE]);
+
+        let (ether_data, ether_header) = Ethernet::parse(&frame).unwrap();
+        assert_eq!(ether_header.ether_type, SUPPORTED::ECPRI_UNTAGGED as u16);
+        let (header, parsed) = ecpri_parse(ether_header.ether_type, ether_data, &ParseConfig::default()).unwrap();
+        assert_eq!(header.pcid, 0x0081);
+        assert_eq!(header.seqid, 0x0780);
+        assert_eq!(parsed, message);
+    }
+}
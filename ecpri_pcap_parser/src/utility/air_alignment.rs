@@ -0,0 +1,200 @@
+use std::io::{self, Write};
+use chrono::{DateTime, TimeZone, Utc};
+use crate::protocols::{DataDirection, EaxcIdConfig};
+use crate::utility::ecpri_analysis::{EcpriData, EcpriDataVec};
+use crate::utility::timing_model::{Numerology, SymbolTime, sfn_from_frame_id, FRAME_NS, SFN_PERIOD};
+
+// Alignment of the packets to the air interface, O-RAN WG4 CUS 4.7 / 11.7:
+// the radio frames are aligned to the GPS epoch, frame 0 started at 1980-01-06 00:00:00 GPS
+// (plus a frame offset configured over the M-Plane), so SFN = frames since the epoch mod 1024.
+// The capture clock is usually UTC (GPS = UTC + leap seconds) or the PTP timescale
+// (TAI, GPS = TAI - 19 s), the difference is added to the pcap timestamp.
+
+pub const GPS_UTC_LEAP_SECONDS: i64 = 18;  // since 2017-01-01
+pub const TAI_GPS_OFFSET_SECONDS: i64 = 19;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EpochConfig {
+    pub epoch: DateTime<Utc>,    // start of frame 0 on the capture clock without offsets
+    pub clock_offset_ns: i64,    // GPS time - capture time
+    pub frame_offset_ns: i64,    // start of the radio frames after the epoch 10 ms boundaries
+}
+
+impl EpochConfig {
+    // Capture timestamps in UTC
+    pub fn utc(leap_seconds: i64) -> Self {
+        Self {
+            epoch: Utc.ymd(1980, 1, 6).and_hms(0, 0, 0),
+            clock_offset_ns: leap_seconds * 1_000_000_000,
+            frame_offset_ns: 0,
+        }
+    }
+
+    // Capture timestamps in the PTP timescale (TAI)
+    pub fn tai() -> Self {
+        Self {
+            clock_offset_ns: -TAI_GPS_OFFSET_SECONDS * 1_000_000_000,
+            ..EpochConfig::utc(0)
+        }
+    }
+
+    // Capture timestamps already in GPS time
+    pub fn gps() -> Self {
+        EpochConfig::utc(0)
+    }
+
+    pub fn with_frame_offset_ns(mut self, frame_offset_ns: i64) -> Self {
+        self.frame_offset_ns = frame_offset_ns;
+        self
+    }
+
+    // Time from the start of frame 0, in ns
+    pub fn frame_time_ns(&self, timestamp: DateTime<Utc>) -> i64 {
+        let since_epoch = (timestamp - self.epoch).num_nanoseconds().expect("Timestamp too far from the epoch.");
+        since_epoch + self.clock_offset_ns - self.frame_offset_ns
+    }
+
+    // Which radio frame the timestamp falls in and where within the frame.
+    pub fn frame_position(&self, timestamp: DateTime<Utc>) -> (i64, u16, i64) {
+        let ns = self.frame_time_ns(timestamp);
+        let frame_count = ns.div_euclid(FRAME_NS);
+        (frame_count, frame_count.rem_euclid(SFN_PERIOD as i64) as u16, ns.rem_euclid(FRAME_NS))
+    }
+
+    // Align one packet to the air time of its (frameId, subframeId, slotId, symbolId), the
+    // 10 bits SFN is the one closest to the frame of the packet.
+    pub fn align(&self, timestamp: DateTime<Utc>, numerology: &Numerology, frame_id: u8, subframe_id: u8, slot_id: u8, symbol_id: u8) -> PacketAlignment {
+        let (frame_count, sfn, offset_to_10ms_ns) = self.frame_position(timestamp);
+        let air_time = SymbolTime {
+            sfn: sfn_from_frame_id(frame_id, sfn),
+            subframe: subframe_id,
+            slot: slot_id,
+            symbol: symbol_id,
+        };
+        let period = SFN_PERIOD as i64;
+        let mut frames = (air_time.sfn as i64 - sfn as i64).rem_euclid(period);
+        if frames > period / 2 {
+            frames -= period;
+        }
+        let air_offset_ns = (frames * FRAME_NS) as f64
+            + numerology.symbol_start_ns(subframe_id, slot_id, symbol_id);
+        PacketAlignment {
+            timestamp,
+            frame_count,
+            sfn,
+            offset_to_10ms_ns,
+            air_time,
+            advance_to_aif_ns: offset_to_10ms_ns as f64 - air_offset_ns,
+        }
+    }
+}
+
+impl Default for EpochConfig {
+    fn default() -> Self {
+        EpochConfig::utc(GPS_UTC_LEAP_SECONDS)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PacketAlignment {
+    pub timestamp: DateTime<Utc>,
+    pub frame_count: i64,         // 10 ms frames since the epoch
+    pub sfn: u16,                 // SFN of the frame the packet was captured in
+    pub offset_to_10ms_ns: i64,   // capture time within that frame
+    pub air_time: SymbolTime,     // symbol the packet is for
+    pub advance_to_aif_ns: f64,   // capture time - air time of the symbol, negative if the packet is ahead of it
+}
+
+// One packet of the capture and its alignment
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AlignedPacket {
+    pub pcid: u16,
+    pub seqid: u16,
+    pub message_type: u8,
+    pub dir: DataDirection,
+    pub alignment: PacketAlignment,
+}
+
+pub struct AirAlignment {
+    pub epoch_config: EpochConfig,
+    pub numerology: Numerology,
+    pub packets: Vec<AlignedPacket>,
+}
+
+impl AirAlignment {
+    pub fn from_data(data: &EcpriDataVec, epoch_config: EpochConfig, numerology: Numerology) -> Self {
+        let packets = data.0.iter()
+            .map(|d| AirAlignment::align(d, &epoch_config, &numerology))
+            .collect();
+        Self { epoch_config, numerology, packets }
+    }
+
+    pub fn align(data: &EcpriData, epoch_config: &EpochConfig, numerology: &Numerology) -> AlignedPacket {
+        let (dir, frame_id, subframe_id, slot_id, symbol_id) = data.data.timing();
+        AlignedPacket {
+            pcid: data.header.pcid,
+            seqid: data.header.seqid,
+            message_type: data.header.message_type,
+            dir,
+            alignment: epoch_config.align(data.timestamp, numerology, frame_id, subframe_id, slot_id, symbol_id),
+        }
+    }
+
+    // Same columns as the PacketOffsetTo10ms/PacketAdvanceToAif export
+    pub fn write_csv<W: Write>(&self, w: &mut W, eaxc_config: &EaxcIdConfig) -> io::Result<()> {
+        writeln!(w, "timestamp_s, timestamp_ns, pcid, eaxc, seqid, message_type, dir, sfn, frame_id, subframe_id, slot_id, symbol_id, packet_sfn, offset_to_10ms_ns, advance_to_aif_ns")?;
+        for p in self.packets.iter() {
+            let a = &p.alignment;
+            writeln!(
+                w,
+                "{}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {:.1}",
+                a.timestamp.timestamp(), a.timestamp.timestamp_subsec_nanos(),
+                p.pcid, eaxc_config.decode(p.pcid), p.seqid, p.message_type, p.dir as u8,
+                a.air_time.sfn, a.air_time.sfn as u8, a.air_time.subframe, a.air_time.slot, a.air_time.symbol,
+                a.sfn, a.offset_to_10ms_ns, a.advance_to_aif_ns
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::timing_model::CpType;
+
+    #[test]
+    fn csv_row_reproduced() {
+        // EcpriIqData.csv: 315965949 s, 314172806 ns, frameId 243, subframe 4, slot 0, symbol 12,
+        // PacketOffsetTo10ms 4172806 ns, PacketAdvanceToAif -255839.8 ns at 30 kHz
+        let config = EpochConfig::gps();
+        let timestamp = Utc.timestamp(315_965_949, 314_172_806);
+        let a = config.align(timestamp, &Numerology::new(1, CpType::Normal), 243, 4, 0, 12);
+        assert_eq!(a.sfn, 243);
+        assert_eq!(a.offset_to_10ms_ns, 4_172_806);
+        assert_eq!(a.air_time, SymbolTime { sfn: 243, subframe: 4, slot: 0, symbol: 12 });
+        assert!((a.advance_to_aif_ns - -255_839.8).abs() < 0.1);
+    }
+
+    #[test]
+    fn clock_and_frame_offsets() {
+        let numerology = Numerology::new(0, CpType::Normal);
+        let gps = Utc.ymd(2020, 1, 1).and_hms(0, 0, 0);
+        let (frame_count, _, offset) = EpochConfig::gps().frame_position(gps);
+        assert_eq!(offset, 0);
+        // the same instant on the UTC and TAI clocks
+        let utc = gps - chrono::Duration::seconds(GPS_UTC_LEAP_SECONDS);
+        let tai = gps + chrono::Duration::seconds(TAI_GPS_OFFSET_SECONDS);
+        assert_eq!(EpochConfig::default().frame_position(utc).0, frame_count);
+        assert_eq!(EpochConfig::tai().frame_position(tai).0, frame_count);
+
+        // frames start 1 ms later, the packet is in the last ms of the previous frame
+        let config = EpochConfig::gps().with_frame_offset_ns(1_000_000);
+        let (count, sfn, offset) = config.frame_position(gps);
+        assert_eq!((count, offset), (frame_count - 1, 9_000_000));
+        // a packet for symbol 0 of the next frame sent 1 ms ahead
+        let a = config.align(gps, &numerology, (sfn + 1) as u8, 0, 0, 0);
+        assert_eq!(a.air_time.sfn, (sfn + 1) % SFN_PERIOD);
+        assert!((a.advance_to_aif_ns - -1_000_000.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,337 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::{self, Write};
+use chrono::{DateTime, Utc};
+use crate::protocols::{DataDirection, EaxcId, EaxcIdConfig, EcpriType, IQPrbuData, UdCompMeth, unpack_samples, RE_PER_PRB};
+use crate::utility::ecpri_analysis::{EcpriData, EcpriDataVec};
+
+// Statistics of the block floating point udCompParam (reserved 4 bits + exponent 4 bits) and
+// of the mantissas of the U-Plane PRBs.
+// A mantissa at the full scale of udIqWidth (-2^(w-1) or 2^(w-1) - 1) is saturated, many of
+// them point to clipping in the compressor (DL: DU gain too high, UL: RU gain too high).
+// Exponents above 16 - udIqWidth give samples beyond the 16 bits full scale, while exponents
+// staying at 0 with small mantissas waste the dynamic range (gain too low).
+// Saturation is also counted for the uncompressed and block scaling streams, whose samples
+// are linear as well.
+
+// Ratio of saturated samples flagged as clipping
+pub const CLIPPING_RATIO: f64 = 1e-3;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StreamFlag {
+    Clipping { saturated: u64, samples: u64 },
+    BeyondFullScale { prbs: u64 },       // exponent > 16 - udIqWidth
+    LowLevel { mean_exponent: f64, max_mantissa: u32 },
+    ReservedNotZero { prbs: u64 },
+}
+
+impl fmt::Display for StreamFlag {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StreamFlag::Clipping { saturated, samples } =>
+                write!(w, "clipping: {} of {} samples saturated", saturated, samples),
+            StreamFlag::BeyondFullScale { prbs } => write!(w, "{} PRBs beyond 16 bits full scale", prbs),
+            StreamFlag::LowLevel { mean_exponent, max_mantissa } =>
+                write!(w, "low level: mean exponent {:.2}, max |mantissa| {}", mean_exponent, max_mantissa),
+            StreamFlag::ReservedNotZero { prbs } => write!(w, "{} PRBs with reserved bits set", prbs),
+        }
+    }
+}
+
+// Exponent sum and count, for the mean exponent per PRB/symbol
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ExponentMean {
+    pub prbs: u64,
+    pub sum: u64,
+}
+
+impl ExponentMean {
+    fn add(&mut self, exponent: u8) {
+        self.prbs += 1;
+        self.sum += exponent as u64;
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.prbs == 0 { 0.0 } else { self.sum as f64 / self.prbs as f64 }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StreamBfpStats {
+    pub iq_width: u16,
+    pub prbs: u64,
+    pub bfp_prbs: u64,
+    pub exponent_histogram: [u64; 16],
+    pub per_prb: BTreeMap<u16, ExponentMean>,
+    pub per_symbol: BTreeMap<u8, ExponentMean>,
+    pub samples: u64,              // I and Q counted separately
+    pub saturated_samples: u64,
+    pub saturated_prbs: u64,
+    pub beyond_full_scale_prbs: u64,
+    pub reserved_not_zero: u64,
+    pub max_mantissa: u32,         // largest |mantissa|
+}
+
+impl StreamBfpStats {
+    pub fn mean_exponent(&self) -> f64 {
+        let sum: u64 = self.exponent_histogram.iter().enumerate().map(|(e, n)| e as u64 * n).sum();
+        if self.bfp_prbs == 0 { 0.0 } else { sum as f64 / self.bfp_prbs as f64 }
+    }
+
+    pub fn saturation_ratio(&self) -> f64 {
+        if self.samples == 0 { 0.0 } else { self.saturated_samples as f64 / self.samples as f64 }
+    }
+
+    pub fn flags(&self) -> Vec<StreamFlag> {
+        let mut flags = Vec::new();
+        if self.samples > 0 && self.saturation_ratio() >= CLIPPING_RATIO {
+            flags.push(StreamFlag::Clipping { saturated: self.saturated_samples, samples: self.samples });
+        }
+        if self.beyond_full_scale_prbs > 0 {
+            flags.push(StreamFlag::BeyondFullScale { prbs: self.beyond_full_scale_prbs });
+        }
+        // less than a quarter of the mantissa range used and never shifted
+        if self.bfp_prbs > 0 && self.exponent_histogram[0] == self.bfp_prbs && self.iq_width > 2
+            && self.max_mantissa < 1 << (self.iq_width - 3) {
+            flags.push(StreamFlag::LowLevel { mean_exponent: self.mean_exponent(), max_mantissa: self.max_mantissa });
+        }
+        if self.reserved_not_zero > 0 {
+            flags.push(StreamFlag::ReservedNotZero { prbs: self.reserved_not_zero });
+        }
+        flags
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlotBfpStats {
+    pub timestamp: DateTime<Utc>,   // of the first message of the slot
+    pub eaxc_id: EaxcId,
+    pub frame_id: u8,
+    pub subframe_id: u8,
+    pub slot_id: u8,
+    pub prbs: u64,
+    pub exponent: ExponentMean,
+    pub max_exponent: u8,
+    pub saturated_samples: u64,
+}
+
+// (eAxC, dataDirection)
+pub type StreamId = (EaxcId, u8);
+
+pub struct BfpStatistics {
+    pub eaxc_config: EaxcIdConfig,
+    pub streams: BTreeMap<StreamId, StreamBfpStats>,
+    pub time_series: Vec<SlotBfpStats>,
+    current_slot: HashMap<StreamId, SlotBfpStats>,
+}
+
+impl BfpStatistics {
+    pub fn new(eaxc_config: EaxcIdConfig) -> Self {
+        Self {
+            eaxc_config,
+            streams: BTreeMap::new(),
+            time_series: Vec::new(),
+            current_slot: HashMap::new(),
+        }
+    }
+
+    pub fn from_data(data: &EcpriDataVec, eaxc_config: EaxcIdConfig) -> Self {
+        let mut statistics = BfpStatistics::new(eaxc_config);
+        for d in data.0.iter() {
+            statistics.push(d);
+        }
+        statistics.finish();
+        statistics
+    }
+
+    // A message of another slot closes the slot in progress of its stream, so the time series
+    // follows the order of the pushes
+    pub fn push(&mut self, d: &EcpriData) {
+        let iq_data = match &d.data {
+            EcpriType::IQData(iq_data) => iq_data,
+            _ => return,
+        };
+        let eaxc_id = d.header.eaxc_id(&self.eaxc_config);
+        let stream_id = (eaxc_id, iq_data.dir as u8);
+        let slot = (iq_data.frame_id, iq_data.subframe_id, iq_data.slot_id);
+
+        let current = self.current_slot.entry(stream_id).or_insert_with(|| SlotBfpStats {
+            timestamp: d.timestamp,
+            eaxc_id,
+            frame_id: slot.0,
+            subframe_id: slot.1,
+            slot_id: slot.2,
+            prbs: 0,
+            exponent: ExponentMean::default(),
+            max_exponent: 0,
+            saturated_samples: 0,
+        });
+        if (current.frame_id, current.subframe_id, current.slot_id) != slot {
+            let next = SlotBfpStats {
+                timestamp: d.timestamp,
+                frame_id: slot.0,
+                subframe_id: slot.1,
+                slot_id: slot.2,
+                prbs: 0,
+                exponent: ExponentMean::default(),
+                max_exponent: 0,
+                saturated_samples: 0,
+                ..current.clone()
+            };
+            self.time_series.push(std::mem::replace(current, next));
+        }
+
+        let stream = self.streams.entry(stream_id).or_default();
+        for section in iq_data.sections.iter() {
+            let hdr = &section.section_hdr;
+            let step = if hdr.rb == 1 { 2 } else { 1 };
+            for (n, prb) in section.iq_prbu.iter().enumerate() {
+                let prb_index = hdr.start_prbc + (n * step) as u16;
+                let saturated = BfpStatistics::add_prb(stream, current, prb, prb_index, iq_data.start_symbol_id);
+                current.prbs += 1;
+                current.saturated_samples += saturated;
+            }
+        }
+    }
+
+    // Returns the number of saturated samples of the PRB
+    fn add_prb(stream: &mut StreamBfpStats, slot: &mut SlotBfpStats, prb: &IQPrbuData, prb_index: u16, symbol: u8) -> u64 {
+        let iq_width = prb.ud_comp_hdr.iq_width();
+        stream.iq_width = iq_width;
+        stream.prbs += 1;
+        match prb.ud_comp_hdr.ud_comp_meth {
+            UdCompMeth::BlockFloatingPoint | UdCompMeth::BfpSelectiveRe => {
+                let exponent = prb.exponent & 0x0F;
+                stream.bfp_prbs += 1;
+                stream.exponent_histogram[exponent as usize] += 1;
+                stream.per_prb.entry(prb_index).or_default().add(exponent);
+                stream.per_symbol.entry(symbol).or_default().add(exponent);
+                if exponent as u16 + iq_width > 16 {
+                    stream.beyond_full_scale_prbs += 1;
+                }
+                if prb.reserved != 0 {
+                    stream.reserved_not_zero += 1;
+                }
+                slot.exponent.add(exponent);
+                slot.max_exponent = slot.max_exponent.max(exponent);
+            },
+            UdCompMeth::NoCompression | UdCompMeth::BlockScaling => (),
+            _ => return 0,
+        }
+
+        let max = (1i32 << (iq_width - 1)) - 1;
+        let min = -(1i32 << (iq_width - 1));
+        let mut saturated = 0;
+        for (i, q) in unpack_samples(&prb.iq_sample, iq_width, RE_PER_PRB).unwrap_or_default() {
+            for v in [i, q].iter() {
+                stream.max_mantissa = stream.max_mantissa.max(v.unsigned_abs());
+                if *v == max || *v == min {
+                    saturated += 1;
+                }
+            }
+        }
+        stream.samples += 2 * RE_PER_PRB as u64;
+        stream.saturated_samples += saturated;
+        if saturated > 0 {
+            stream.saturated_prbs += 1;
+        }
+        saturated
+    }
+
+    // Moves the last slot of every stream to the time series, by time of their first message
+    pub fn finish(&mut self) {
+        let mut slots: Vec<SlotBfpStats> = self.current_slot.drain().map(|(_, slot)| slot).collect();
+        slots.sort_by_key(|slot| slot.timestamp);
+        self.time_series.extend(slots);
+    }
+
+    pub fn write_summary_csv<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "eaxc, dir, iq_width, prbs, mean_exponent, {}, saturated_samples, samples, saturated_prbs, flags",
+                 (0..16).map(|e| format!("exp{}", e)).collect::<Vec<_>>().join(", "))?;
+        for ((eaxc_id, dir), s) in self.streams.iter() {
+            let histogram: Vec<String> = s.exponent_histogram.iter().map(|n| n.to_string()).collect();
+            let flags: Vec<String> = s.flags().iter().map(|f| f.to_string()).collect();
+            writeln!(w, "{}, {}, {}, {}, {:.3}, {}, {}, {}, {}, {}",
+                     eaxc_id, dir, s.iq_width, s.prbs, s.mean_exponent(), histogram.join(", "),
+                     s.saturated_samples, s.samples, s.saturated_prbs, flags.join("; "))?;
+        }
+        Ok(())
+    }
+
+    pub fn write_time_series_csv<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "timestamp, eaxc, frame_id, subframe_id, slot_id, prbs, mean_exponent, max_exponent, saturated_samples")?;
+        for s in self.time_series.iter() {
+            writeln!(w, "{}, {}, {}, {}, {}, {}, {:.3}, {}, {}",
+                     s.timestamp, s.eaxc_id, s.frame_id, s.subframe_id, s.slot_id, s.prbs,
+                     s.exponent.mean(), s.max_exponent, s.saturated_samples)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for BfpStatistics {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(w, "{:>18} {:>4} {:>6} {:>10} {:>9} {:>9} {:>11}  flags",
+                 "eAxC", "dir", "width", "prbs", "mean exp", "max |m|", "saturated")?;
+        for ((eaxc_id, dir), s) in self.streams.iter() {
+            let flags: Vec<String> = s.flags().iter().map(|f| f.to_string()).collect();
+            writeln!(
+                w,
+                "{:>18} {:>4} {:>6} {:>10} {:>9.3} {:>9} {:>11}  {}",
+                eaxc_id.to_string(), DataDirection::from(*dir).to_string(), s.iq_width, s.prbs,
+                s.mean_exponent(), s.max_mantissa, s.saturated_samples, flags.join("; ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::UdCompHdr;
+    use crate::utility::test_support::{prb, section_hdr, u_plane, u_section};
+
+    fn bfp_prb(exponent: u8, value: i32) -> IQPrbuData {
+        prb(UdCompHdr::new(9, UdCompMeth::BlockFloatingPoint), exponent, (value, 1))
+    }
+
+    // DL, frame 0, subframe 0, the PRBs from PRB 10
+    fn bfp_u_plane(us: i64, slot_id: u8, symbol: u8, prbs: Vec<IQPrbuData>) -> EcpriData {
+        u_plane(us, 0, (1, 0, 0, slot_id), symbol, vec![u_section(section_hdr(1, 10, prbs.len() as u8), prbs)])
+    }
+
+    #[test]
+    fn exponents_and_saturation() {
+        let mut data = EcpriDataVec::new();
+        data.append(bfp_u_plane(0, 0, 0, vec![bfp_prb(3, 100), bfp_prb(5, 255)]));
+        data.append(bfp_u_plane(10, 0, 1, vec![bfp_prb(8, -256), bfp_prb(3, 10)]));
+        data.append(bfp_u_plane(600, 1, 0, vec![bfp_prb(4, 10)]));
+        let statistics = BfpStatistics::from_data(&data, EaxcIdConfig::default());
+        let s = &statistics.streams[&(EaxcIdConfig::default().decode(0), 1)];
+        assert_eq!((s.prbs, s.bfp_prbs), (5, 5));
+        assert_eq!(s.exponent_histogram[3], 2);
+        assert_eq!(s.per_prb[&10].mean(), 5.0);
+        assert_eq!(s.per_symbol[&1].mean(), 5.5);
+        assert_eq!((s.saturated_samples, s.saturated_prbs), (24, 2));
+        assert_eq!(s.beyond_full_scale_prbs, 1);
+        assert_eq!(s.max_mantissa, 256);
+        let flags = s.flags();
+        assert!(matches!(flags[0], StreamFlag::Clipping { saturated: 24, samples: 120 }));
+        assert!(matches!(flags[1], StreamFlag::BeyondFullScale { prbs: 1 }));
+
+        assert_eq!(statistics.time_series.len(), 2);
+        assert_eq!(statistics.time_series[0].prbs, 4);
+        assert_eq!(statistics.time_series[0].max_exponent, 8);
+        assert_eq!(statistics.time_series[1].slot_id, 1);
+    }
+
+    #[test]
+    fn low_level_flag() {
+        let mut data = EcpriDataVec::new();
+        data.append(bfp_u_plane(0, 0, 0, vec![bfp_prb(0, 20), bfp_prb(0, -30)]));
+        let statistics = BfpStatistics::from_data(&data, EaxcIdConfig::default());
+        let flags = statistics.streams.values().next().unwrap().flags();
+        assert_eq!(flags, vec![StreamFlag::LowLevel { mean_exponent: 0.0, max_mantissa: 30 }]);
+    }
+}
@@ -0,0 +1,289 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use serde::Deserialize;
+use crate::protocols::{EcpriType, FrameStructure, MU};
+use crate::utility::ecpri_analysis::EcpriDataVec;
+use crate::utility::timing_model::{CpType, Numerology, TC_PER_SECOND};
+
+// The carrier the fronthaul streams belong to, which is not carried in the messages:
+// N_RB of the channel bandwidth (TS 38.101-1/-2 5.3.2, maximum transmission bandwidth
+// configuration), the FFT size (the smallest power of 2 holding the 12 * N_RB subcarriers,
+// i.e. the usual 30.72 MHz * 2^n sample rates) and the CP lengths in samples.
+// offsetToPointA: PRBs from point A to the lowest PRB overlapping the SSB (TS 38.211 4.4.4.2).
+
+// (channel bandwidth MHz, N_RB) per subcarrier spacing, TS 38.101-1 Table 5.3.2-1 (FR1)
+const FR1_N_RB_15: &[(u16, u16)] = &[
+    (5, 25), (10, 52), (15, 79), (20, 106), (25, 133), (30, 160), (35, 188), (40, 216), (45, 242), (50, 270),
+];
+const FR1_N_RB_30: &[(u16, u16)] = &[
+    (5, 11), (10, 24), (15, 38), (20, 51), (25, 65), (30, 78), (35, 92), (40, 106), (45, 119), (50, 133),
+    (60, 162), (70, 189), (80, 217), (90, 245), (100, 273),
+];
+const FR1_N_RB_60: &[(u16, u16)] = &[
+    (10, 11), (15, 18), (20, 24), (25, 31), (30, 38), (35, 44), (40, 51), (45, 58), (50, 65),
+    (60, 79), (70, 93), (80, 107), (90, 121), (100, 135),
+];
+// TS 38.101-2 Table 5.3.2-1 (FR2)
+const FR2_N_RB_60: &[(u16, u16)] = &[(50, 66), (100, 132), (200, 264)];
+const FR2_N_RB_120: &[(u16, u16)] = &[(50, 32), (100, 66), (200, 132), (400, 264)];
+
+// FR2 bands start at n257
+const FR2_FIRST_BAND: u16 = 257;
+
+fn n_rb_table(mu: u8, fr2: bool) -> &'static [(u16, u16)] {
+    match (mu, fr2) {
+        (0, false) => FR1_N_RB_15,
+        (1, false) => FR1_N_RB_30,
+        (2, false) => FR1_N_RB_60,
+        (2, true) => FR2_N_RB_60,
+        (3, true) => FR2_N_RB_120,
+        _ => &[],
+    }
+}
+
+// N_RB of the channel bandwidth, None if the combination doesn't exist
+pub fn n_rb(bandwidth_mhz: u16, mu: u8, fr2: bool) -> Option<u16> {
+    n_rb_table(mu, fr2).iter().find(|(bw, _)| *bw == bandwidth_mhz).map(|(_, n)| *n)
+}
+
+pub fn fft_size_for(n_rb: u16) -> usize {
+    (n_rb as usize * 12).next_power_of_two().max(128)
+}
+
+#[derive(Debug)]
+pub enum CarrierConfigError {
+    Io(io::Error),
+    Toml(toml::de::Error),
+    UnknownBandwidth { bandwidth_mhz: u16, mu: u8 },
+    UnknownCp(String),
+    UnknownNumerology(u8),
+    InvalidFftSize { fft_size: usize, num_subcarriers: usize },
+}
+
+impl fmt::Display for CarrierConfigError {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CarrierConfigError::Io(e) => write!(w, "can't read the carrier config: {}", e),
+            CarrierConfigError::Toml(e) => write!(w, "invalid carrier config: {}", e),
+            CarrierConfigError::UnknownBandwidth { bandwidth_mhz, mu } =>
+                write!(w, "no N_RB for {} MHz at {} kHz, set n_rb", bandwidth_mhz, 15 << mu),
+            CarrierConfigError::UnknownCp(cp) => write!(w, "cp = \"{}\", expected \"normal\" or \"extended\"", cp),
+            CarrierConfigError::UnknownNumerology(mu) => write!(w, "mu = {}, expected 0..5", mu),
+            CarrierConfigError::InvalidFftSize { fft_size, num_subcarriers } =>
+                write!(w, "fft_size = {}, expected a power of 2 of at least {} subcarriers", fft_size, num_subcarriers),
+        }
+    }
+}
+
+impl From<io::Error> for CarrierConfigError {
+    fn from(e: io::Error) -> Self {
+        CarrierConfigError::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for CarrierConfigError {
+    fn from(e: toml::de::Error) -> Self {
+        CarrierConfigError::Toml(e)
+    }
+}
+
+// Layout of the TOML file, e.g.
+//   band = 78
+//   bandwidth_mhz = 100
+//   mu = 1
+//   cp = "normal"           # optional, normal by default
+//   offset_to_point_a = 24  # optional
+//   n_rb = 273              # optional, from the bandwidth by default
+//   fft_size = 4096         # optional, from N_RB by default, a power of 2 of at least 12 * N_RB
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CarrierConfigFile {
+    band: Option<u16>,
+    bandwidth_mhz: u16,
+    mu: u8,
+    cp: Option<String>,
+    offset_to_point_a: Option<u16>,
+    n_rb: Option<u16>,
+    fft_size: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CarrierConfig {
+    pub band: Option<u16>,          // NR operating band, n78 = 78
+    pub bandwidth_mhz: u16,
+    pub numerology: Numerology,
+    pub n_rb: u16,
+    pub fft_size: usize,
+    pub offset_to_point_a: u16,
+}
+
+impl CarrierConfig {
+    pub fn new(band: Option<u16>, bandwidth_mhz: u16, numerology: Numerology) -> Result<Self, CarrierConfigError> {
+        let fr2 = match band {
+            Some(band) => band >= FR2_FIRST_BAND,
+            None => numerology.mu >= 3,
+        };
+        let n_rb = n_rb(bandwidth_mhz, numerology.mu, fr2)
+            .ok_or(CarrierConfigError::UnknownBandwidth { bandwidth_mhz, mu: numerology.mu })?;
+        Ok(Self {
+            band,
+            bandwidth_mhz,
+            numerology,
+            n_rb,
+            fft_size: fft_size_for(n_rb),
+            offset_to_point_a: 0,
+        })
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, CarrierConfigError> {
+        let file: CarrierConfigFile = toml::from_str(text)?;
+        let cp = match file.cp.as_deref() {
+            None | Some("normal") => CpType::Normal,
+            Some("extended") => CpType::Extended,
+            Some(cp) => return Err(CarrierConfigError::UnknownCp(cp.to_string())),
+        };
+        if file.mu > 5 {
+            return Err(CarrierConfigError::UnknownNumerology(file.mu));
+        }
+        let numerology = Numerology::new(file.mu, cp);
+        let mut config = match file.n_rb {
+            Some(n_rb) => Self {
+                band: file.band,
+                bandwidth_mhz: file.bandwidth_mhz,
+                numerology,
+                n_rb,
+                fft_size: fft_size_for(n_rb),
+                offset_to_point_a: 0,
+            },
+            None => CarrierConfig::new(file.band, file.bandwidth_mhz, numerology)?,
+        };
+        if let Some(fft_size) = file.fft_size {
+            config.fft_size = fft_size;
+            config.check_fft_size()?;
+        }
+        config.offset_to_point_a = file.offset_to_point_a.unwrap_or(0);
+        Ok(config)
+    }
+
+    // The OFDM (de)modulation needs a radix 2 FFT holding every subcarrier
+    pub fn check_fft_size(&self) -> Result<(), CarrierConfigError> {
+        if self.fft_size.is_power_of_two() && self.fft_size >= self.num_subcarriers() {
+            Ok(())
+        } else {
+            Err(CarrierConfigError::InvalidFftSize { fft_size: self.fft_size, num_subcarriers: self.num_subcarriers() })
+        }
+    }
+
+    pub fn from_toml_file<P: AsRef<Path>>(path: P) -> Result<Self, CarrierConfigError> {
+        CarrierConfig::from_toml(&fs::read_to_string(path)?)
+    }
+
+    // The widest channel bandwidth of the numerology fitting the FFT size of the frameStructure
+    pub fn from_frame_structure(frame_structure: &FrameStructure) -> Option<Self> {
+        let fft_size = frame_structure.fft_size.size()?;
+        let numerology = Numerology::from_mu(&frame_structure.mu, CpType::Normal)?;
+        let fr2 = numerology.mu >= 3;
+        n_rb_table(numerology.mu, fr2).iter().rev()
+            .find(|(_, n_rb)| fft_size_for(*n_rb) == fft_size)
+            .map(|&(bandwidth_mhz, n_rb)| Self {
+                band: None,
+                bandwidth_mhz,
+                numerology,
+                n_rb,
+                fft_size,
+                offset_to_point_a: 0,
+            })
+    }
+
+    // From the first section type 0 or 3 message of the capture, the only ones with frameStructure
+    pub fn infer(data: &EcpriDataVec) -> Option<Self> {
+        data.0.iter().find_map(|d| match &d.data {
+            EcpriType::FCPType0(c) => Some(&c.frame_structure),
+            EcpriType::FCPType3(c) if !matches!(c.frame_structure.mu, MU::KHZ_1_25 | MU::KHZ_3_75 | MU::KHZ_5 | MU::KHZ_7_5) =>
+                Some(&c.frame_structure),
+            _ => None,
+        }).and_then(CarrierConfig::from_frame_structure)
+    }
+
+    pub fn num_subcarriers(&self) -> usize {
+        self.n_rb as usize * 12
+    }
+
+    pub fn scs_hz(&self) -> u32 {
+        self.numerology.scs_hz()
+    }
+
+    pub fn sample_rate_hz(&self) -> f64 {
+        self.fft_size as f64 * self.scs_hz() as f64
+    }
+
+    pub fn symbols_per_slot(&self) -> u8 {
+        self.numerology.symbols_per_slot()
+    }
+
+    // CP length of a symbol of the slot, in samples
+    pub fn cp_length(&self, slot: u8, symbol: u8) -> usize {
+        let slot_in_subframe = slot % self.numerology.slots_per_subframe();
+        let l = slot_in_subframe as u16 * self.symbols_per_slot() as u16 + symbol as u16;
+        (self.numerology.cp_length_tc(l) as f64 * self.sample_rate_hz() / TC_PER_SECOND as f64).round() as usize
+    }
+
+    // CP lengths of every symbol of the slot, in samples
+    pub fn cp_lengths(&self, slot: u8) -> Vec<usize> {
+        (0..self.symbols_per_slot()).map(|symbol| self.cp_length(slot, symbol)).collect()
+    }
+}
+
+impl Default for CarrierConfig {
+    // n78, 100 MHz, 30 kHz
+    fn default() -> Self {
+        CarrierConfig::new(Some(78), 100, Numerology::new(1, CpType::Normal)).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn n_rb_tables() {
+        let config = CarrierConfig::default();
+        assert_eq!((config.n_rb, config.fft_size), (273, 4096));
+        assert_eq!(config.sample_rate_hz(), 122.88e6);
+        assert_eq!(config.cp_lengths(0), vec![352, 288, 288, 288, 288, 288, 288, 288, 288, 288, 288, 288, 288, 288]);
+        assert_eq!(config.cp_length(1, 0), 352);
+        assert_eq!(config.cp_length(2, 0), 352);
+        let config = CarrierConfig::new(Some(1), 20, Numerology::new(0, CpType::Normal)).unwrap();
+        assert_eq!((config.n_rb, config.fft_size), (106, 2048));
+        assert_eq!(config.cp_length(0, 0), 160);
+        assert_eq!(config.cp_length(0, 7), 160);
+        // the same bandwidth and SCS in FR1 and FR2
+        assert_eq!(n_rb(100, 2, false), Some(135));
+        assert_eq!(CarrierConfig::new(Some(257), 100, Numerology::new(2, CpType::Normal)).unwrap().n_rb, 132);
+        assert!(CarrierConfig::new(None, 7, Numerology::new(1, CpType::Normal)).is_err());
+    }
+
+    #[test]
+    fn toml_and_frame_structure() {
+        let config = CarrierConfig::from_toml("band = 78\nbandwidth_mhz = 40\nmu = 1\noffset_to_point_a = 24\n").unwrap();
+        assert_eq!((config.n_rb, config.fft_size, config.offset_to_point_a), (106, 2048, 24));
+        let config = CarrierConfig::from_toml("bandwidth_mhz = 100\nmu = 1\nn_rb = 270\nfft_size = 8192\n").unwrap();
+        assert_eq!((config.n_rb, config.fft_size), (270, 8192));
+        assert!(CarrierConfig::from_toml("bandwidth_mhz = 100\nmu = 1\ncp = \"short\"\n").is_err());
+        assert!(CarrierConfig::from_toml("mu = 1\n").is_err());
+        assert!(matches!(CarrierConfig::from_toml("bandwidth_mhz = 100\nmu = 7\n"), Err(CarrierConfigError::UnknownNumerology(7))));
+        assert!(matches!(CarrierConfig::from_toml("bandwidth_mhz = 100\nmu = 1\nfft_size = 3072\n"),
+                         Err(CarrierConfigError::InvalidFftSize { fft_size: 3072, num_subcarriers: 3276 })));
+        assert!(CarrierConfig::from_toml("bandwidth_mhz = 100\nmu = 1\nfft_size = 2048\n").is_err());
+        // a typo isn't silently ignored
+        assert!(matches!(CarrierConfig::from_toml("bandwidth_mhz = 100\nmu = 1\nfftsize = 4096\n"), Err(CarrierConfigError::Toml(_))));
+
+        let config = CarrierConfig::from_frame_structure(&FrameStructure::from(0xC1)).unwrap();
+        assert_eq!((config.bandwidth_mhz, config.n_rb), (100, 273));
+        let config = CarrierConfig::from_frame_structure(&FrameStructure::from(0xB0)).unwrap();
+        assert_eq!((config.bandwidth_mhz, config.n_rb), (30, 160));
+        assert!(CarrierConfig::from_frame_structure(&FrameStructure::from(0x0C)).is_none());
+    }
+}
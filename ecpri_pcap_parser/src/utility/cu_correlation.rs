@@ -0,0 +1,344 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+use chrono::{DateTime, Utc};
+use crate::protocols::{DataDirection, EaxcId, EaxcIdConfig, EcpriType, SectionHeader, TimingHeader};
+use crate::utility::ecpri_analysis::{EcpriData, EcpriDataVec};
+
+// Correlation of the U-Plane sections with the C-Plane sections that scheduled them.
+// A U-Plane section belongs to the C-Plane section with the same sectionId, eAxC, direction
+// and slot whose symbols (startSymbolId + symInc .. + numSymbol) contain its symbol, its PRBs
+// have to be within the PRBs of the C-Plane section. Every symbol of a C-Plane section is
+// expected to be served by U-Plane data, except for section type 0 (idle/guard periods).
+// The frameId wraps every 2.56 s, so a new C-Plane section for the same symbols replaces the
+// previous one, which is checked at that point.
+
+// (eAxC, dataDirection, frameId, subframeId, slotId, sectionId)
+type SectionKey = (EaxcId, u8, u8, u8, u8, u16);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SectionRef {
+    pub timestamp: DateTime<Utc>,
+    pub eaxc_id: EaxcId,
+    pub dir: DataDirection,
+    pub frame_id: u8,
+    pub subframe_id: u8,
+    pub slot_id: u8,
+    pub symbol_id: u8,     // first symbol of the section
+    pub section_id: u16,
+    pub rb: u8,
+    pub start_prb: u16,
+    pub num_prb: u16,      // numPrbc/numPrbu = 0 resolved to the rest of the carrier
+}
+
+impl fmt::Display for SectionRef {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            w,
+            "{}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}",
+            self.timestamp, self.eaxc_id, self.dir, self.frame_id, self.subframe_id, self.slot_id,
+            self.symbol_id, self.section_id, self.rb, self.start_prb, self.num_prb
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CorrelationIssue {
+    // No C-Plane section with this sectionId in the slot
+    NoCPlane(SectionRef),
+    // C-Plane sections with this sectionId exist in the slot, but not for this symbol
+    SymbolNotScheduled(SectionRef),
+    // The U-Plane PRBs (or the rb flag) don't match the C-Plane section
+    PrbMismatch { u_plane: SectionRef, c_plane: SectionRef },
+    // No U-Plane data for the C-Plane section at all
+    NotServed(SectionRef),
+    // Symbols of the C-Plane section without U-Plane data
+    CoverageGap { c_plane: SectionRef, missing_symbols: Vec<u8> },
+}
+
+impl CorrelationIssue {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CorrelationIssue::NoCPlane(_) => "no C-Plane",
+            CorrelationIssue::SymbolNotScheduled(_) => "symbol not scheduled",
+            CorrelationIssue::PrbMismatch { .. } => "PRB mismatch",
+            CorrelationIssue::NotServed(_) => "not served",
+            CorrelationIssue::CoverageGap { .. } => "coverage gap",
+        }
+    }
+}
+
+struct CPlaneEntry {
+    section: SectionRef,
+    section_type: u8,
+    num_symbol: u8,
+    served: u32,   // bit n: U-Plane data received for symbol n
+}
+
+// Bit of the symbol in the symbol masks, none for the symbolIds (6 bits) beyond 31
+fn symbol_bit(symbol_id: u8) -> u32 {
+    1u32.checked_shl(symbol_id as u32).unwrap_or(0)
+}
+
+impl CPlaneEntry {
+    fn symbol_mask(&self) -> u32 {
+        let end = (self.section.symbol_id as u32 + self.num_symbol as u32).min(32);
+        (self.section.symbol_id as u32..end).fold(0, |mask, l| mask | (1 << l))
+    }
+}
+
+pub struct CuCorrelator {
+    pub eaxc_config: EaxcIdConfig,
+    pub num_prb: u16,     // PRBs of the carrier, for numPrbc/numPrbu = 0
+    pub matched: u64,     // U-Plane sections matched to a C-Plane section
+    pub issues: Vec<CorrelationIssue>,
+    c_plane: HashMap<SectionKey, Vec<CPlaneEntry>>,
+}
+
+impl CuCorrelator {
+    pub fn new(eaxc_config: EaxcIdConfig, num_prb: u16) -> Self {
+        Self {
+            eaxc_config,
+            num_prb,
+            matched: 0,
+            issues: Vec::new(),
+            c_plane: HashMap::new(),
+        }
+    }
+
+    pub fn from_data(data: &EcpriDataVec, eaxc_config: EaxcIdConfig, num_prb: u16) -> Self {
+        let mut correlator = CuCorrelator::new(eaxc_config, num_prb);
+        for d in data.0.iter() {
+            correlator.push(d);
+        }
+        correlator.finish();
+        correlator
+    }
+
+    fn resolve_num_prb(&self, start_prb: u16, num_prb: u8) -> u16 {
+        if num_prb == 0 {
+            self.num_prb.saturating_sub(start_prb)
+        } else {
+            num_prb as u16
+        }
+    }
+
+    fn section_ref(&self, d: &EcpriData, timing: (DataDirection, u8, u8, u8), symbol_id: u8, hdr: &SectionHeader) -> SectionRef {
+        let (dir, frame_id, subframe_id, slot_id) = timing;
+        SectionRef {
+            timestamp: d.timestamp,
+            eaxc_id: d.header.eaxc_id(&self.eaxc_config),
+            dir,
+            frame_id,
+            subframe_id,
+            slot_id,
+            symbol_id,
+            section_id: hdr.section_id,
+            rb: hdr.rb,
+            start_prb: hdr.start_prbc,
+            num_prb: self.resolve_num_prb(hdr.start_prbc, hdr.num_prbc),
+        }
+    }
+
+    // Messages have to be fed in capture order
+    pub fn push(&mut self, d: &EcpriData) {
+        match &d.data {
+            EcpriType::IQData(iq_data) => {
+                let timing = (iq_data.dir, iq_data.frame_id, iq_data.subframe_id, iq_data.slot_id);
+                for section in iq_data.sections.iter() {
+                    let u_plane = self.section_ref(d, timing, iq_data.start_symbol_id, &section.section_hdr);
+                    self.serve(u_plane);
+                }
+            },
+            EcpriType::FCPType0(c) => {
+                let sections: Vec<_> = c.sections.iter().map(|s| (&s.section_hdr, s.num_symbol)).collect();
+                self.schedule(d, 0, &c.comm_ctrl_info, &sections);
+            },
+            EcpriType::FCPType1(c) => {
+                let sections: Vec<_> = c.sections.iter().map(|s| (&s.section_hdr, s.num_symbol)).collect();
+                self.schedule(d, 1, &c.comm_ctrl_info, &sections);
+            },
+            EcpriType::FCPType3(c) => {
+                let sections: Vec<_> = c.sections.iter().map(|s| (&s.section_hdr, s.num_symbol)).collect();
+                self.schedule(d, 3, &c.comm_ctrl_info, &sections);
+            },
+            _ => (),
+        }
+    }
+
+    fn schedule(&mut self, d: &EcpriData, section_type: u8, h: &TimingHeader, sections: &[(&SectionHeader, u8)]) {
+        let timing = (h.dir, h.frame_id, h.subframe_id, h.slot_id);
+        // symInc = 1 moves the current symbol to the next one for this and the following sections
+        let mut symbol_id = h.start_symbol_id;
+        for (hdr, num_symbol) in sections.iter() {
+            if hdr.si == 1 {
+                symbol_id = symbol_id.saturating_add(1);
+            }
+            let entry = CPlaneEntry {
+                section: self.section_ref(d, timing, symbol_id, hdr),
+                section_type,
+                num_symbol: *num_symbol,
+                served: 0,
+            };
+            let s = &entry.section;
+            let key = (s.eaxc_id, s.dir as u8, s.frame_id, s.subframe_id, s.slot_id, s.section_id);
+            let mask = entry.symbol_mask();
+            let entries = self.c_plane.entry(key).or_default();
+            let (replaced, kept): (Vec<_>, Vec<_>) = entries.drain(..).partition(|e| e.symbol_mask() & mask != 0);
+            *entries = kept;
+            entries.push(entry);
+            for old in replaced {
+                self.check(old);
+            }
+        }
+    }
+
+    fn serve(&mut self, u_plane: SectionRef) {
+        let key = (u_plane.eaxc_id, u_plane.dir as u8, u_plane.frame_id, u_plane.subframe_id, u_plane.slot_id, u_plane.section_id);
+        let entry = match self.c_plane.get_mut(&key) {
+            None => {
+                self.issues.push(CorrelationIssue::NoCPlane(u_plane));
+                return;
+            },
+            Some(entries) => match entries.iter_mut().find(|e| e.symbol_mask() & symbol_bit(u_plane.symbol_id) != 0) {
+                None => {
+                    self.issues.push(CorrelationIssue::SymbolNotScheduled(u_plane));
+                    return;
+                },
+                Some(entry) => entry,
+            },
+        };
+        entry.served |= symbol_bit(u_plane.symbol_id);
+        self.matched += 1;
+        let c = &entry.section;
+        if u_plane.rb != c.rb || u_plane.start_prb < c.start_prb
+            || u_plane.start_prb + u_plane.num_prb > c.start_prb + c.num_prb {
+            let c_plane = c.clone();
+            self.issues.push(CorrelationIssue::PrbMismatch { u_plane, c_plane });
+        }
+    }
+
+    fn check(&mut self, entry: CPlaneEntry) {
+        // section type 0 tells the O-RU there is no data
+        if entry.section_type == 0 {
+            return;
+        }
+        let missing = entry.symbol_mask() & !entry.served;
+        if entry.served == 0 {
+            self.issues.push(CorrelationIssue::NotServed(entry.section));
+        } else if missing != 0 {
+            let missing_symbols = (0..32u8).filter(|l| missing & (1 << l) != 0).collect();
+            self.issues.push(CorrelationIssue::CoverageGap { c_plane: entry.section, missing_symbols });
+        }
+    }
+
+    // End of the capture, checks the C-Plane sections still waiting for U-Plane data
+    pub fn finish(&mut self) {
+        let mut entries: Vec<CPlaneEntry> = self.c_plane.drain().flat_map(|(_, v)| v).collect();
+        entries.sort_by_key(|e| e.section.timestamp);
+        for entry in entries {
+            self.check(entry);
+        }
+    }
+
+    pub fn count(&self, name: &str) -> usize {
+        self.issues.iter().filter(|i| i.name() == name).count()
+    }
+
+    pub fn write_issues_csv<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "issue, timestamp, eaxc, dir, frame_id, subframe_id, slot_id, symbol_id, section_id, rb, start_prb, num_prb, details")?;
+        for issue in self.issues.iter() {
+            match issue {
+                CorrelationIssue::NoCPlane(u) | CorrelationIssue::SymbolNotScheduled(u) | CorrelationIssue::NotServed(u) => {
+                    writeln!(w, "{}, {}, ", issue.name(), u)?;
+                },
+                CorrelationIssue::PrbMismatch { u_plane, c_plane } => {
+                    writeln!(w, "{}, {}, C-Plane rb {} PRBs {}..{}", issue.name(), u_plane,
+                             c_plane.rb, c_plane.start_prb, c_plane.start_prb + c_plane.num_prb)?;
+                },
+                CorrelationIssue::CoverageGap { c_plane, missing_symbols } => {
+                    writeln!(w, "{}, {}, missing symbols {:?}", issue.name(), c_plane, missing_symbols)?;
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for CuCorrelator {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(w, "matched U-Plane sections: {}", self.matched)?;
+        for name in ["no C-Plane", "symbol not scheduled", "PRB mismatch", "not served", "coverage gap"].iter() {
+            writeln!(w, "{:>22}: {}", name, self.count(name))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::test_support::{self, c_section, section_hdr, u_section};
+
+    // DL, frame 1, subframe 0, sections (sectionId, startPrbc, numPrbc, numSymbol) of every RE
+    fn c_plane(us: i64, slot_id: u8, sections: Vec<(u16, u16, u8, u8)>) -> EcpriData {
+        let sections = sections.into_iter()
+            .map(|(id, start, num, num_symbol)| c_section(section_hdr(id, start, num), 0xFFF, num_symbol))
+            .collect();
+        test_support::c_plane(us, 0, (1, 1, 0, slot_id), 0, sections)
+    }
+
+    // DL, frame 1, subframe 0, sections (sectionId, startPrbc, numPrbc) without PRB
+    fn u_plane(us: i64, slot_id: u8, symbol: u8, sections: Vec<(u16, u16, u8)>) -> EcpriData {
+        let sections = sections.into_iter()
+            .map(|(id, start, num)| u_section(section_hdr(id, start, num), Vec::new()))
+            .collect();
+        test_support::u_plane(us, 0, (1, 1, 0, slot_id), symbol, sections)
+    }
+
+    #[test]
+    fn matched_sections() {
+        let mut data = EcpriDataVec::new();
+        data.append(c_plane(0, 0, vec![(1, 0, 0, 2), (2, 0, 10, 1)]));
+        data.append(u_plane(10, 0, 0, vec![(1, 0, 100), (1, 100, 173), (2, 0, 10)]));
+        data.append(u_plane(20, 0, 1, vec![(1, 0, 0)]));
+        let correlator = CuCorrelator::from_data(&data, EaxcIdConfig::default(), 273);
+        assert_eq!(correlator.matched, 4);
+        assert!(correlator.issues.is_empty());
+    }
+
+    #[test]
+    fn issues() {
+        let mut data = EcpriDataVec::new();
+        data.append(c_plane(0, 0, vec![(1, 0, 50, 3), (2, 0, 10, 1), (3, 0, 10, 1)]));
+        data.append(u_plane(10, 0, 0, vec![(1, 40, 20), (2, 0, 10), (4, 0, 10)]));
+        data.append(u_plane(20, 0, 1, vec![(2, 0, 10)]));
+        data.append(u_plane(30, 1, 0, vec![(1, 0, 10)]));
+        let correlator = CuCorrelator::from_data(&data, EaxcIdConfig::default(), 273);
+        assert_eq!(correlator.matched, 2);
+        assert_eq!(correlator.count("PRB mismatch"), 1);
+        assert_eq!(correlator.count("no C-Plane"), 2);
+        assert_eq!(correlator.count("symbol not scheduled"), 1);
+        assert_eq!(correlator.count("not served"), 1);
+        match correlator.issues.iter().find(|i| i.name() == "coverage gap") {
+            Some(CorrelationIssue::CoverageGap { c_plane, missing_symbols }) => {
+                assert_eq!(c_plane.section_id, 1);
+                assert_eq!(missing_symbols, &vec![1, 2]);
+            },
+            _ => panic!("coverage gap not reported"),
+        }
+    }
+
+    #[test]
+    fn symbol_ids_beyond_the_masks() {
+        // symbolId is 6 bits, 32..63 aren't in any symbol mask
+        let mut data = EcpriDataVec::new();
+        data.append(c_plane(0, 0, vec![(1, 0, 10, 14)]));
+        data.append(u_plane(10, 0, 40, vec![(1, 0, 10)]));
+        data.append(u_plane(20, 0, 63, vec![(1, 0, 10)]));
+        let correlator = CuCorrelator::from_data(&data, EaxcIdConfig::default(), 273);
+        assert_eq!(correlator.matched, 0);
+        assert_eq!(correlator.count("symbol not scheduled"), 2);
+        assert_eq!(correlator.count("not served"), 1);
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use chrono::{DateTime, Utc};
+use crate::protocols::{DataDirection, EaxcId, EaxcIdConfig};
+use crate::utility::air_alignment::{AirAlignment, AlignedPacket};
+use crate::utility::timing_model::SymbolTime;
+
+// O-RAN WG4 CUS delay management (4.4): every message has to arrive within a reception
+// window relative to the air time of its first symbol. With advance = air time - arrival:
+//   :DL U-Plane at the O-RU    T2a_min_up <= advance <= T2a_max_up
+//   :DL C-Plane at the O-RU    T2a_min_cp_dl <= advance <= T2a_max_cp_dl, and at least
+//                              Tcp_adv_dl ahead of the U-Plane: advance >= T2a_min_up + Tcp_adv_dl
+//   :UL C-Plane at the O-RU    T2a_min_cp_ul <= advance <= T2a_max_cp_ul
+//   :UL U-Plane at the O-DU    Ta4_min <= -advance <= Ta4_max
+// A larger advance than allowed is early (the O-RU buffer overflows), a smaller one is late.
+
+// Reception windows in ns, the O-RU ru-delay-profile and the O-DU Ta4 window of the M-Plane
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DelayWindows {
+    pub t2a_min_up: i64,
+    pub t2a_max_up: i64,
+    pub t2a_min_cp_dl: i64,
+    pub t2a_max_cp_dl: i64,
+    pub tcp_adv_dl: i64,
+    pub t2a_min_cp_ul: i64,
+    pub t2a_max_cp_ul: i64,
+    pub ta4_min: i64,
+    pub ta4_max: i64,
+}
+
+impl Default for DelayWindows {
+    // Typical values of a category A O-RU, replace them with the values of the O-RU under test
+    fn default() -> Self {
+        Self {
+            t2a_min_up: 71_000,
+            t2a_max_up: 428_000,
+            t2a_min_cp_dl: 196_000,
+            t2a_max_cp_dl: 553_000,
+            tcp_adv_dl: 125_000,
+            t2a_min_cp_ul: 71_000,
+            t2a_max_cp_ul: 428_000,
+            ta4_min: 0,
+            ta4_max: 400_000,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WindowKind {
+    DlCPlane,
+    DlUPlane,
+    UlCPlane,
+    UlUPlane,
+}
+
+impl fmt::Display for WindowKind {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            WindowKind::DlCPlane => "DL C",
+            WindowKind::DlUPlane => "DL U",
+            WindowKind::UlCPlane => "UL C",
+            WindowKind::UlUPlane => "UL U",
+        };
+        write!(w, "{}", name)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WindowStatus {
+    Early,
+    OnTime,
+    Late,
+}
+
+impl DelayWindows {
+    // (minimum, maximum) advance of the window, in ns
+    pub fn advance_range(&self, kind: WindowKind) -> (i64, i64) {
+        match kind {
+            WindowKind::DlCPlane => (self.t2a_min_cp_dl.max(self.t2a_min_up + self.tcp_adv_dl), self.t2a_max_cp_dl),
+            WindowKind::DlUPlane => (self.t2a_min_up, self.t2a_max_up),
+            WindowKind::UlCPlane => (self.t2a_min_cp_ul, self.t2a_max_cp_ul),
+            WindowKind::UlUPlane => (-self.ta4_max, -self.ta4_min),
+        }
+    }
+
+    pub fn classify(&self, kind: WindowKind, advance_ns: f64) -> WindowStatus {
+        let (min, max) = self.advance_range(kind);
+        if advance_ns > max as f64 {
+            WindowStatus::Early
+        } else if advance_ns < min as f64 {
+            WindowStatus::Late
+        } else {
+            WindowStatus::OnTime
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DelayCheck {
+    pub timestamp: DateTime<Utc>,
+    pub pcid: u16,
+    pub eaxc_id: EaxcId,
+    pub kind: WindowKind,
+    pub air_time: SymbolTime,
+    pub advance_ns: f64,     // air time - arrival
+    pub status: WindowStatus,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WindowStats {
+    pub early: u64,
+    pub on_time: u64,
+    pub late: u64,
+    pub min_advance_ns: Option<f64>,
+    pub max_advance_ns: Option<f64>,
+    pub histogram: BTreeMap<i64, u64>,  // start of the advance bin in ns -> packets
+}
+
+pub struct DelayAnalysis {
+    pub windows: DelayWindows,
+    pub bin_ns: i64,
+    pub stats: BTreeMap<(EaxcId, WindowKind), WindowStats>,
+    pub violations: Vec<DelayCheck>,
+}
+
+impl DelayAnalysis {
+    pub fn new(windows: DelayWindows, bin_ns: i64) -> Self {
+        assert!(bin_ns > 0, "The histogram bin must be positive.");
+        Self {
+            windows,
+            bin_ns,
+            stats: BTreeMap::new(),
+            violations: Vec::new(),
+        }
+    }
+
+    pub fn from_alignment(alignment: &AirAlignment, eaxc_config: &EaxcIdConfig, windows: DelayWindows, bin_ns: i64) -> Self {
+        let mut analysis = DelayAnalysis::new(windows, bin_ns);
+        for packet in alignment.packets.iter() {
+            analysis.push(packet, eaxc_config);
+        }
+        analysis
+    }
+
+    pub fn push(&mut self, packet: &AlignedPacket, eaxc_config: &EaxcIdConfig) -> WindowStatus {
+        let kind = match (packet.dir, packet.message_type) {
+            (DataDirection::DL, 0) => WindowKind::DlUPlane,
+            (DataDirection::DL, _) => WindowKind::DlCPlane,
+            (DataDirection::UL, 0) => WindowKind::UlUPlane,
+            (DataDirection::UL, _) => WindowKind::UlCPlane,
+        };
+        let eaxc_id = eaxc_config.decode(packet.pcid);
+        let advance_ns = -packet.alignment.advance_to_aif_ns;
+        let status = self.windows.classify(kind, advance_ns);
+
+        let stats = self.stats.entry((eaxc_id, kind)).or_default();
+        match status {
+            WindowStatus::Early => stats.early += 1,
+            WindowStatus::OnTime => stats.on_time += 1,
+            WindowStatus::Late => stats.late += 1,
+        }
+        stats.min_advance_ns = Some(stats.min_advance_ns.map_or(advance_ns, |v| v.min(advance_ns)));
+        stats.max_advance_ns = Some(stats.max_advance_ns.map_or(advance_ns, |v| v.max(advance_ns)));
+        let bin = (advance_ns.floor() as i64).div_euclid(self.bin_ns) * self.bin_ns;
+        *stats.histogram.entry(bin).or_insert(0) += 1;
+
+        if status != WindowStatus::OnTime {
+            self.violations.push(DelayCheck {
+                timestamp: packet.alignment.timestamp,
+                pcid: packet.pcid,
+                eaxc_id,
+                kind,
+                air_time: packet.alignment.air_time,
+                advance_ns,
+                status,
+            });
+        }
+        status
+    }
+
+    pub fn write_histogram_csv<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "eaxc, window, advance_bin_ns, packets")?;
+        for ((eaxc_id, kind), stats) in self.stats.iter() {
+            for (bin, count) in stats.histogram.iter() {
+                writeln!(w, "{}, {}, {}, {}", eaxc_id, kind, bin, count)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn write_violations_csv<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "timestamp, pcid, eaxc, window, sfn, subframe_id, slot_id, symbol_id, advance_ns, min_ns, max_ns, status")?;
+        for v in self.violations.iter() {
+            let (min, max) = self.windows.advance_range(v.kind);
+            writeln!(
+                w,
+                "{}, {:04X}, {}, {}, {}, {}, {}, {}, {:.1}, {}, {}, {:?}",
+                v.timestamp, v.pcid, v.eaxc_id, v.kind,
+                v.air_time.sfn, v.air_time.subframe, v.air_time.slot, v.air_time.symbol,
+                v.advance_ns, min, max, v.status
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for DelayAnalysis {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(w, "{:>18} {:>6} {:>10} {:>8} {:>8} {:>14} {:>14}",
+                 "eAxC", "window", "on time", "early", "late", "min adv (us)", "max adv (us)")?;
+        for ((eaxc_id, kind), stats) in self.stats.iter() {
+            writeln!(
+                w,
+                "{:>18} {:>6} {:>10} {:>8} {:>8} {:>14.3} {:>14.3}",
+                eaxc_id.to_string(), kind.to_string(), stats.on_time, stats.early, stats.late,
+                stats.min_advance_ns.unwrap_or(0.0) / 1000.0, stats.max_advance_ns.unwrap_or(0.0) / 1000.0
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use crate::utility::air_alignment::PacketAlignment;
+
+    fn packet(pcid: u16, message_type: u8, dir: DataDirection, advance_ns: f64) -> AlignedPacket {
+        AlignedPacket {
+            pcid,
+            seqid: 0,
+            message_type,
+            dir,
+            alignment: PacketAlignment {
+                timestamp: Utc.timestamp(1_600_000_000, 0),
+                frame_count: 0,
+                sfn: 0,
+                offset_to_10ms_ns: 0,
+                air_time: SymbolTime { sfn: 0, subframe: 0, slot: 0, symbol: 0 },
+                advance_to_aif_ns: -advance_ns,
+            },
+        }
+    }
+
+    #[test]
+    fn windows() {
+        let windows = DelayWindows::default();
+        assert_eq!(windows.classify(WindowKind::DlUPlane, 100_000.0), WindowStatus::OnTime);
+        assert_eq!(windows.classify(WindowKind::DlUPlane, 500_000.0), WindowStatus::Early);
+        assert_eq!(windows.classify(WindowKind::DlUPlane, 50_000.0), WindowStatus::Late);
+        // the C-Plane has to be Tcp_adv_dl ahead of the earliest U-Plane
+        assert_eq!(windows.advance_range(WindowKind::DlCPlane), (196_000, 553_000));
+        let windows = DelayWindows { t2a_min_cp_dl: 100_000, ..windows };
+        assert_eq!(windows.classify(WindowKind::DlCPlane, 150_000.0), WindowStatus::Late);
+        // UL U-Plane arrives after the air time
+        assert_eq!(windows.classify(WindowKind::UlUPlane, -200_000.0), WindowStatus::OnTime);
+        assert_eq!(windows.classify(WindowKind::UlUPlane, 10_000.0), WindowStatus::Early);
+        assert_eq!(windows.classify(WindowKind::UlUPlane, -500_000.0), WindowStatus::Late);
+    }
+
+    #[test]
+    fn histograms_and_violations() {
+        let eaxc_config = EaxcIdConfig::default();
+        let mut analysis = DelayAnalysis::new(DelayWindows::default(), 10_000);
+        analysis.push(&packet(1, 0, DataDirection::DL, 255_839.8), &eaxc_config);
+        analysis.push(&packet(1, 0, DataDirection::DL, 251_000.0), &eaxc_config);
+        analysis.push(&packet(1, 0, DataDirection::DL, 20_000.0), &eaxc_config);
+        analysis.push(&packet(1, 2, DataDirection::DL, 300_000.0), &eaxc_config);
+        analysis.push(&packet(2, 0, DataDirection::UL, -100_000.0), &eaxc_config);
+
+        let dl_u = &analysis.stats[&(eaxc_config.decode(1), WindowKind::DlUPlane)];
+        assert_eq!((dl_u.on_time, dl_u.early, dl_u.late), (2, 0, 1));
+        assert_eq!(dl_u.histogram.get(&250_000), Some(&2));
+        assert_eq!(dl_u.histogram.get(&20_000), Some(&1));
+        assert_eq!(analysis.stats.len(), 3);
+        assert_eq!(analysis.violations.len(), 1);
+        assert_eq!(analysis.violations[0].status, WindowStatus::Late);
+        assert_eq!(analysis.violations[0].kind, WindowKind::DlUPlane);
+    }
+}
@@ -1,8 +1,6 @@
-use std::collections::BTreeMap;
-use crate::protocols::{EcpriType, IQPrbuData};
-use chrono::{DateTime, TimeZone, NaiveDateTime, Utc};
-use ecpri_pcap_parser::{SectionHeader, CommonHeader};
-use std::cmp;
+use std::collections::{BTreeMap, HashMap};
+use crate::protocols::{EcpriType, IQPrbuData, CommonHeader, decompressor, BlockFloatingPoint, UdCompMeth, EaxcId, EaxcIdConfig, ModCompParams, SectionExtension};
+use chrono::{DateTime, Utc};
 
 #[derive(Debug)]
 pub struct EcpriData {
@@ -14,6 +12,7 @@ pub struct EcpriData {
 #[derive(Debug)]
 pub struct Frame {
     pub pcid: u16,              // ecpriPCID
+    pub eaxc_id: EaxcId,        // ecpriPCID split into DU port, band sector, CC and RU port
     pub frame_id: u8,
     pub subframe_id: u8,
     pub slot_id: u8,           // slot id
@@ -25,6 +24,17 @@ pub struct Frame {
 #[derive(Debug)]
 pub struct EcpriDataVec (pub Vec<EcpriData>);
 
+// (eAxC, dataDirection, frameId, subframeId, slotId, sectionId)
+pub type SectionKey = (EaxcId, u8, u8, u8, u8, u16);
+
+// What a C-Plane section tells about the U-Plane sections with the same sectionId
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CPlaneSection {
+    pub symbols: u32,                       // bit n = symbol n
+    pub re_mask: u16,
+    pub mod_comp: Option<ModCompParams>,    // section extension 4 or 5
+}
+
 impl Default for EcpriDataVec {
     fn default() -> Self {
         let v = Vec::new();
@@ -41,65 +51,96 @@ impl EcpriDataVec {
         self.0.push(data);
     }
 
-    pub fn parse_iq_data(self, mantissa: u16) -> BTreeMap<(u16, u8, u8, u8, u8), Frame> {
+    // IQ samples of every symbol, grouped by antenna port and carrier.
+    pub fn parse_iq_data(self, eaxc_config: &EaxcIdConfig) -> BTreeMap<(EaxcId, u8, u8, u8, u8), Frame> {
+        let c_plane = self.c_plane_sections(eaxc_config);
         let mut frame_data = BTreeMap::new();
         for v in self.0.iter() {
             if let EcpriType::IQData(iq_data) = &v.data {
                 let pcid = v.header.pcid;
+                let eaxc_id = v.header.eaxc_id(eaxc_config);
                 let frame_id = iq_data.frame_id;
                 let subframe_id = iq_data.subframe_id;
                 let slot_id = iq_data.slot_id;
                 let symb_id = iq_data.start_symbol_id;
-                let mut iq = EcpriDataVec::get_prbu_data(&iq_data.iq_prbu, mantissa);
-
-                // frame_data.entry((frame_id, subframe_id, slot_id))
-                //             .or_insert(Frame {
-                //                 frame_id,
-                //                 subframe_id,
-                //                 slot_id,
-                //                 slot_dir: iq_data.dir as u8,
-                //                 iq: iq,
-                //             });
-                
-                if !frame_data.contains_key(&(pcid, frame_id, subframe_id, slot_id, symb_id)) {
-                    frame_data.insert((pcid, frame_id, subframe_id, slot_id, symb_id), 
-                                    Frame {
-                                                pcid,
-                                                frame_id,
-                                                subframe_id,
-                                                slot_id,
-                                                slot_dir: iq_data.dir as u8,
-                                                symb_id,
-                                                iq: iq,
-                    });
-                    println!("frame id: {}, subframe id: {}, slot id: {}, slot dir: {}, symb id: {}, one frame data len: {}", 
-                              frame_id, subframe_id, slot_id, iq_data.dir as u8, symb_id, iq_data.iq_prbu.len());
-                } else if let Some(frame) = frame_data.get_mut(&(pcid, frame_id, subframe_id, slot_id, symb_id)) {
-                    frame.iq.append(&mut iq);
-                    println!("frame id: {}, subframe id: {}, slot id: {}, slot dir: {}, symb id: {}, one frame data len: {}, (append)", 
-                              frame_id, subframe_id, slot_id, iq_data.dir as u8, symb_id, iq_data.iq_prbu.len());
+                let mut iq = Vec::new();
+                for section in iq_data.sections.iter() {
+                    let section_key = (eaxc_id, iq_data.dir as u8, frame_id, subframe_id, slot_id, section.section_hdr.section_id);
+                    let mod_comp = EcpriDataVec::find_c_plane_section(&c_plane, &section_key, symb_id).and_then(|c| c.mod_comp);
+                    iq.append(&mut EcpriDataVec::get_prbu_data(&section.iq_prbu, mod_comp));
                 }
+
+                frame_data.entry((eaxc_id, frame_id, subframe_id, slot_id, symb_id))
+                    .or_insert_with(|| Frame {
+                        pcid,
+                        eaxc_id,
+                        frame_id,
+                        subframe_id,
+                        slot_id,
+                        slot_dir: iq_data.dir as u8,
+                        symb_id,
+                        iq: Vec::new(),
+                    })
+                    .iq.append(&mut iq);
             }
         }
         frame_data
     }
 
-    fn get_prbu_data(prbu: &Vec<IQPrbuData>, mantissa: u16) -> Vec<(i32, i32)> {
-        use std::cmp;
+    // The C-Plane sections of every section key, in capture order
+    pub fn c_plane_sections(&self, eaxc_config: &EaxcIdConfig) -> HashMap<SectionKey, Vec<CPlaneSection>> {
+        let mut c_plane: HashMap<SectionKey, Vec<CPlaneSection>> = HashMap::new();
+        for d in self.0.iter() {
+            let sections: Vec<(u16, u8, u8, u16, &[SectionExtension])> = match &d.data {
+                EcpriType::FCPType1(c) => c.sections.iter()
+                    .map(|s| (s.section_hdr.section_id, s.section_hdr.si, s.num_symbol, s.re_mask, &s.extensions[..])).collect(),
+                EcpriType::FCPType3(c) => c.sections.iter()
+                    .map(|s| (s.section_hdr.section_id, s.section_hdr.si, s.num_symbol, s.re_mask, &s.extensions[..])).collect(),
+                EcpriType::FCPType5(c) => c.sections.iter()
+                    .map(|s| (s.section_hdr.section_id, s.section_hdr.si, s.num_symbol, s.re_mask, &s.extensions[..])).collect(),
+                _ => continue,
+            };
+            let (dir, frame_id, subframe_id, slot_id, mut symbol) = d.data.timing();
+            let eaxc_id = d.header.eaxc_id(eaxc_config);
+            for (section_id, si, num_symbol, re_mask, extensions) in sections {
+                symbol = symbol.saturating_add(si);
+                let end = (symbol as u32 + num_symbol as u32).min(32);
+                let symbols = (symbol as u32..end).fold(0, |m, l| m | (1 << l));
+                let mod_comp = extensions.iter().find_map(|e| e.ext.mod_comp_params());
+                c_plane.entry((eaxc_id, dir as u8, frame_id, subframe_id, slot_id, section_id))
+                    .or_default()
+                    .push(CPlaneSection { symbols, re_mask, mod_comp });
+            }
+        }
+        c_plane
+    }
+
+    // The C-Plane section of the key which covers the symbol, none for the symbolIds beyond 31
+    pub fn find_c_plane_section<'a>(
+        c_plane: &'a HashMap<SectionKey, Vec<CPlaneSection>>,
+        key: &SectionKey,
+        symbol: u8,
+    ) -> Option<&'a CPlaneSection> {
+        let bit = 1u32.checked_shl(symbol as u32)?;
+        c_plane.get(key)?.iter().find(|c| c.symbols & bit != 0)
+    }
+
+    // Decompress the PRBs according to their udCompHdr, samples are in 16 bits LSBs.
+    // BFP samples are exact, the other methods are rounded to the nearest integer.
+    // The PRBs which can't be decompressed (reserved udCompMeth) are left out.
+    fn get_prbu_data(prbu: &[IQPrbuData], mod_comp: Option<ModCompParams>) -> Vec<(i32, i32)> {
         let mut iq_data = Vec::with_capacity(50);  // Should not exceed 50 in one eth packet.
         for iq in prbu.iter() {
-            // let exp = 2i16.pow(iq.exponent as u32) - 1;
-            // let scale_exp = cmp::max((iq.exponent as i16) - mantissa as i16 + 1, 0_i16);
-            let scale_exp = iq.exponent;
-            let scaler = 2i16.pow(scale_exp as u32) - 1;
-            let (_, _iq) = iq.get_iq_data(mantissa).expect("Failed to get prbu data.");
-            // println!("exp: {}, IQ len: {}", scaler, _iq.len());
-            let real_iq = _iq.iter()
-                                               .map(|&d| ((d.0 * scaler) as i32, (d.1 * scaler) as i32))
-                                               .collect::<Vec<_>>();
-            iq_data.extend(real_iq.iter());
+            let real_iq = match iq.ud_comp_hdr.ud_comp_meth {
+                UdCompMeth::BlockFloatingPoint | UdCompMeth::BfpSelectiveRe => {
+                    BlockFloatingPoint { iq_width: iq.ud_comp_hdr.iq_width() }.decode_prb(iq)
+                },
+                _ => decompressor(&iq.ud_comp_hdr, mod_comp)
+                    .and_then(|d| d.decompress_prb(iq))
+                    .map(|samples| samples.iter().map(|d| (d.re.round() as i32, d.im.round() as i32)).collect()),
+            };
+            iq_data.extend(real_iq.unwrap_or_default());
         }
-        println!("prbu data len: {}", iq_data.len());
         iq_data
     }
-}
\ No newline at end of file
+}
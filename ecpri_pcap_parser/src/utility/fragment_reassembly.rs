@@ -0,0 +1,217 @@
+use std::collections::{BTreeMap, HashMap};
+use chrono::{DateTime, Duration, Utc};
+use crate::protocols::{CommonHeader, EcpriSeqId};
+
+// Reassembly of fragmented eCPRI messages.
+// The fragments of one message share the SequenceID, count SubsequenceID from 0 and the
+// last one has E = 1, their payloads are concatenated in SubsequenceID order before the
+// sections are parsed. Fragments of different eAxC/message types are independent.
+
+// Messages are identified by (ecpriPcid/ecpriRtcid, message type, SequenceID)
+type FragmentKey = (u16, u8, u8);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FragmentEvent {
+    // A fragment arrived after a fragment with a higher SubsequenceID
+    OutOfOrder { pcid: u16, sequence_id: u8, subsequence_id: u8, expected: u8 },
+    // The same SubsequenceID was received twice, the first one is kept
+    Duplicate { pcid: u16, sequence_id: u8, subsequence_id: u8 },
+    // The message was not complete within the timeout, its fragments are dropped
+    Timeout { pcid: u16, sequence_id: u8, received: usize, last_subsequence_id: Option<u8> },
+}
+
+struct PendingMessage {
+    header: CommonHeader,                // of the first received fragment
+    first_seen: DateTime<Utc>,
+    fragments: BTreeMap<u8, Vec<u8>>,    // SubsequenceID -> payload
+    last_subsequence_id: Option<u8>,     // SubsequenceID of the E = 1 fragment
+    next_expected: u8,
+}
+
+impl PendingMessage {
+    fn is_complete(&self) -> bool {
+        match self.last_subsequence_id {
+            Some(last) => self.fragments.len() == last as usize + 1,
+            None => false,
+        }
+    }
+}
+
+pub struct FragmentReassembler {
+    pub timeout: Duration,
+    pub events: Vec<(DateTime<Utc>, FragmentEvent)>,
+    pub reassembled: usize,  // number of messages built from more than one fragment
+    pending: HashMap<FragmentKey, PendingMessage>,
+}
+
+impl FragmentReassembler {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            events: Vec::new(),
+            reassembled: 0,
+            pending: HashMap::new(),
+        }
+    }
+
+    // Feed one received message, returns the complete message once all its fragments arrived.
+    // The header of the complete message has the total payload_size and E = 1, SubsequenceID = 0.
+    pub fn push(&mut self, timestamp: DateTime<Utc>, header: &CommonHeader, payload: &[u8]) -> Option<(CommonHeader, Vec<u8>)> {
+        self.expire(timestamp);
+
+        let seq_id = header.seq_id();
+        let key = (header.pcid, header.message_type, seq_id.sequence_id);
+        if seq_id.is_unfragmented() && !self.pending.contains_key(&key) {
+            return Some((header.clone(), payload.to_vec()));
+        }
+
+        let pending = self.pending.entry(key).or_insert_with(|| PendingMessage {
+            header: header.clone(),
+            first_seen: timestamp,
+            fragments: BTreeMap::new(),
+            last_subsequence_id: None,
+            next_expected: 0,
+        });
+        if pending.fragments.contains_key(&seq_id.subsequence_id) {
+            self.events.push((timestamp, FragmentEvent::Duplicate {
+                pcid: header.pcid,
+                sequence_id: seq_id.sequence_id,
+                subsequence_id: seq_id.subsequence_id,
+            }));
+            return None;
+        }
+        if seq_id.subsequence_id != pending.next_expected {
+            self.events.push((timestamp, FragmentEvent::OutOfOrder {
+                pcid: header.pcid,
+                sequence_id: seq_id.sequence_id,
+                subsequence_id: seq_id.subsequence_id,
+                expected: pending.next_expected,
+            }));
+        }
+        pending.next_expected = pending.next_expected.max(seq_id.subsequence_id + 1);
+        pending.fragments.insert(seq_id.subsequence_id, payload.to_vec());
+        if seq_id.e_bit == 1 {
+            pending.last_subsequence_id = Some(seq_id.subsequence_id);
+        }
+
+        if pending.is_complete() {
+            let pending = self.pending.remove(&key).unwrap();
+            let payload: Vec<u8> = pending.fragments.into_values().flatten().collect();
+            let mut header = pending.header;
+            header.payload_size = (payload.len() + 4) as u16;
+            header.seqid = u16::from(EcpriSeqId { sequence_id: seq_id.sequence_id, e_bit: 1, subsequence_id: 0 });
+            self.reassembled += 1;
+            Some((header, payload))
+        } else {
+            None
+        }
+    }
+
+    // Drop the messages whose first fragment is older than the timeout.
+    pub fn expire(&mut self, now: DateTime<Utc>) {
+        let timeout = self.timeout;
+        let expired: Vec<FragmentKey> = self.pending.iter()
+            .filter(|(_, p)| now - p.first_seen > timeout)
+            .map(|(k, _)| *k)
+            .collect();
+        self.drop_pending(now, expired);
+    }
+
+    // End of the capture, whatever is still pending will never be complete.
+    pub fn flush(&mut self, now: DateTime<Utc>) {
+        let keys: Vec<FragmentKey> = self.pending.keys().cloned().collect();
+        self.drop_pending(now, keys);
+    }
+
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    fn drop_pending(&mut self, now: DateTime<Utc>, mut keys: Vec<FragmentKey>) {
+        keys.sort();
+        for key in keys {
+            if let Some(pending) = self.pending.remove(&key) {
+                self.events.push((now, FragmentEvent::Timeout {
+                    pcid: key.0,
+                    sequence_id: key.2,
+                    received: pending.fragments.len(),
+                    last_subsequence_id: pending.last_subsequence_id,
+                }));
+            }
+        }
+    }
+}
+
+impl Default for FragmentReassembler {
+    fn default() -> Self {
+        FragmentReassembler::new(Duration::milliseconds(10))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::test_support::{self, at};
+
+    fn header(pcid: u16, sequence_id: u8, e_bit: u8, subsequence_id: u8, payload_len: usize) -> CommonHeader {
+        CommonHeader {
+            payload_size: (payload_len + 4) as u16,
+            seqid: u16::from(EcpriSeqId { sequence_id, e_bit, subsequence_id }),
+            ..test_support::header(pcid, 0)
+        }
+    }
+
+    #[test]
+    fn seq_id_split() {
+        let seq_id = EcpriSeqId::from(0xA583);
+        assert_eq!(seq_id, EcpriSeqId { sequence_id: 0xA5, e_bit: 1, subsequence_id: 3 });
+        assert_eq!(u16::from(seq_id), 0xA583);
+        assert!(EcpriSeqId::from(0x1280).is_unfragmented());
+        assert!(!EcpriSeqId::from(0x1200).is_unfragmented());
+    }
+
+    #[test]
+    fn unfragmented_pass_through() {
+        let mut reassembler = FragmentReassembler::default();
+        let h = header(1, 7, 1, 0, 3);
+        assert_eq!(reassembler.push(at(0), &h, &[1, 2, 3]), Some((h, vec![1, 2, 3])));
+        assert!(reassembler.events.is_empty());
+    }
+
+    #[test]
+    fn in_order_and_out_of_order_fragments() {
+        let mut reassembler = FragmentReassembler::default();
+        assert_eq!(reassembler.push(at(0), &header(1, 7, 0, 0, 2), &[1, 2]), None);
+        assert_eq!(reassembler.push(at(1), &header(1, 7, 0, 1, 2), &[3, 4]), None);
+        let (h, payload) = reassembler.push(at(2), &header(1, 7, 1, 2, 1), &[5]).unwrap();
+        assert_eq!(payload, vec![1, 2, 3, 4, 5]);
+        assert_eq!(h.payload_size, 9);
+        assert!(h.seq_id().is_unfragmented());
+        assert!(reassembler.events.is_empty());
+
+        // the last fragment first, another eAxC in between
+        assert_eq!(reassembler.push(at(3), &header(2, 8, 1, 1, 1), &[6]), None);
+        assert!(reassembler.push(at(4), &header(3, 8, 1, 0, 1), &[9]).is_some());
+        let (_, payload) = reassembler.push(at(5), &header(2, 8, 0, 0, 1), &[5]).unwrap();
+        assert_eq!(payload, vec![5, 6]);
+        assert_eq!(reassembler.events.len(), 2);
+        assert_eq!(reassembler.events[0].1, FragmentEvent::OutOfOrder { pcid: 2, sequence_id: 8, subsequence_id: 1, expected: 0 });
+        assert_eq!(reassembler.events[1].1, FragmentEvent::OutOfOrder { pcid: 2, sequence_id: 8, subsequence_id: 0, expected: 2 });
+        assert_eq!(reassembler.reassembled, 2);
+    }
+
+    #[test]
+    fn duplicate_and_timeout() {
+        let mut reassembler = FragmentReassembler::new(Duration::milliseconds(1));
+        assert_eq!(reassembler.push(at(0), &header(1, 9, 0, 0, 1), &[1]), None);
+        assert_eq!(reassembler.push(at(10), &header(1, 9, 0, 0, 1), &[1]), None);
+        assert_eq!(reassembler.events[0].1, FragmentEvent::Duplicate { pcid: 1, sequence_id: 9, subsequence_id: 0 });
+        // the E = 1 fragment comes too late, it starts a new incomplete message
+        assert_eq!(reassembler.push(at(2000), &header(1, 9, 1, 1, 1), &[2]), None);
+        assert_eq!(reassembler.events[1].1, FragmentEvent::Timeout { pcid: 1, sequence_id: 9, received: 1, last_subsequence_id: None });
+        assert_eq!(reassembler.pending_count(), 1);
+        reassembler.flush(at(3000));
+        assert_eq!(reassembler.pending_count(), 0);
+        assert_eq!(reassembler.events.len(), 4);
+    }
+}
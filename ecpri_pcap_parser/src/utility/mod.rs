@@ -1 +1,20 @@
-pub mod ecpri_analysis;
\ No newline at end of file
+pub mod ecpri_analysis;
+pub mod fragment_reassembly;
+pub mod sequence_analysis;
+pub mod timing_model;
+pub mod air_alignment;
+pub mod delay_management;
+pub mod cu_correlation;
+pub mod resource_grid;
+pub mod carrier_config;
+pub mod power_measurement;
+pub mod bfp_statistics;
+pub mod modulation_analysis;
+pub mod tbs;
+pub mod tbs_estimation;
+pub mod ofdm;
+pub mod ssb_detection;
+pub mod polar_code;
+pub mod pbch_decoding;
+#[cfg(test)]
+pub(crate) mod test_support;
@@ -0,0 +1,319 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use num::complex::Complex;
+use crate::protocols::RE_PER_PRB;
+use crate::utility::resource_grid::SlotGrid;
+
+// Modulation order and EVM of the REs of an allocation.
+// The square QAM constellations of TS 38.211 5.1 have unit average power, levels per axis
+// (2i + 1 - sqrt(M)) / sqrt(2 (M - 1) / 3). The samples are brought to unit power and the
+// phase is estimated from the 4th power of the samples first, then for every candidate order
+// the complex gain is refined by least squares against the nearest ideal points:
+//   EVM = sqrt(sum |x / g - ref|^2 / sum |ref|^2)
+// A denser constellation always fits better, so the lowest order whose RMS EVM is below a
+// quarter of its minimum distance is taken, the samples of a higher order are at least
+// ~1.4 times further from the points of a lower one.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Modulation {
+    Qpsk,
+    Qam16,
+    Qam64,
+    Qam256,
+    Qam1024,
+}
+
+pub const MODULATIONS: [Modulation; 5] = [
+    Modulation::Qpsk, Modulation::Qam16, Modulation::Qam64, Modulation::Qam256, Modulation::Qam1024,
+];
+
+impl Modulation {
+    pub fn bits_per_symbol(&self) -> u8 {
+        match self {
+            Modulation::Qpsk => 2,
+            Modulation::Qam16 => 4,
+            Modulation::Qam64 => 6,
+            Modulation::Qam256 => 8,
+            Modulation::Qam1024 => 10,
+        }
+    }
+
+    pub fn order(&self) -> u32 {
+        1 << self.bits_per_symbol()
+    }
+
+    // Levels per axis
+    fn levels(&self) -> u32 {
+        1 << (self.bits_per_symbol() / 2)
+    }
+
+    fn norm(&self) -> f32 {
+        (2.0 * (self.order() - 1) as f32 / 3.0).sqrt()
+    }
+
+    // Minimum distance between two points
+    pub fn d_min(&self) -> f32 {
+        2.0 / self.norm()
+    }
+
+    fn slice_axis(&self, v: f32) -> f32 {
+        let levels = self.levels() as f32;
+        let i = ((v * self.norm() + levels - 1.0) / 2.0).round().max(0.0).min(levels - 1.0);
+        (2.0 * i + 1.0 - levels) / self.norm()
+    }
+
+    // Nearest ideal point
+    pub fn slice(&self, x: Complex<f32>) -> Complex<f32> {
+        Complex::new(self.slice_axis(x.re), self.slice_axis(x.im))
+    }
+
+    pub fn points(&self) -> Vec<Complex<f32>> {
+        let levels = self.levels();
+        let level = |i: u32| (2.0 * i as f32 + 1.0 - levels as f32) / self.norm();
+        (0..levels).flat_map(|i| (0..levels).map(move |q| Complex::new(level(i), level(q)))).collect()
+    }
+}
+
+impl fmt::Display for Modulation {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Modulation::Qpsk => "QPSK",
+            Modulation::Qam16 => "16QAM",
+            Modulation::Qam64 => "64QAM",
+            Modulation::Qam256 => "256QAM",
+            Modulation::Qam1024 => "1024QAM",
+        };
+        write!(w, "{}", name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvmMeasurement {
+    pub gain: Complex<f32>,   // received = gain * ideal
+    pub evm_rms: f64,         // ratio, 0.01 = 1 %
+    pub per_re: Vec<f32>,     // |x / g - ref| / rms(ref) of every sample
+}
+
+impl EvmMeasurement {
+    pub fn evm_percent(&self) -> f64 {
+        self.evm_rms * 100.0
+    }
+
+    pub fn evm_db(&self) -> f64 {
+        20.0 * self.evm_rms.log10()
+    }
+}
+
+fn rms(samples: &[Complex<f32>]) -> f32 {
+    (samples.iter().map(|x| x.norm_sqr() as f64).sum::<f64>() / samples.len() as f64).sqrt() as f32
+}
+
+// EVM of the samples against the constellation, with the gain estimated from the samples
+pub fn measure_evm(samples: &[Complex<f32>], modulation: Modulation) -> Option<EvmMeasurement> {
+    let scale = rms(samples);
+    if samples.is_empty() || scale == 0.0 {
+        return None;
+    }
+    // square QAM: E[s^4] is negative real, arg(E[x^4]) = 4 phase + pi (modulo the pi/2 symmetry)
+    let fourth = samples.iter().fold(Complex::new(0.0f64, 0.0), |sum, x| {
+        let x = Complex::new(x.re as f64, x.im as f64) / scale as f64;
+        sum + x * x * x * x
+    });
+    let phase = if fourth.norm() > 1e-6 { ((-fourth).arg() / 4.0) as f32 } else { 0.0 };
+    let mut gain = Complex::from_polar(scale, phase);
+    for _ in 0..3 {
+        let (num, den) = samples.iter().fold((Complex::new(0.0f64, 0.0), 0.0f64), |(num, den), x| {
+            let r = modulation.slice(x / gain);
+            let (x, r) = (Complex::new(x.re as f64, x.im as f64), Complex::new(r.re as f64, r.im as f64));
+            (num + x * r.conj(), den + r.norm_sqr())
+        });
+        gain = Complex::new((num.re / den) as f32, (num.im / den) as f32);
+    }
+    let mut error = 0.0f64;
+    let mut reference = 0.0f64;
+    let mut per_re = Vec::with_capacity(samples.len());
+    for x in samples.iter() {
+        let y = x / gain;
+        let r = modulation.slice(y);
+        error += (y - r).norm_sqr() as f64;
+        reference += r.norm_sqr() as f64;
+        per_re.push((y - r).norm());
+    }
+    let ref_rms = (reference / samples.len() as f64).sqrt() as f32;
+    per_re.iter_mut().for_each(|e| *e /= ref_rms);
+    Some(EvmMeasurement { gain, evm_rms: (error / reference).sqrt(), per_re })
+}
+
+// The lowest order the samples fit, with its EVM
+pub fn classify(samples: &[Complex<f32>]) -> Option<(Modulation, EvmMeasurement)> {
+    MODULATIONS.iter()
+        .filter_map(|&m| measure_evm(samples, m).map(|evm| (m, evm)))
+        .find(|(m, evm)| evm.evm_rms < (m.d_min() / 4.0) as f64)
+}
+
+// PRBs start_prb..start_prb + num_prb of the symbols, on one antenna of the grid
+#[derive(Clone, Debug, PartialEq)]
+pub struct Allocation {
+    pub antenna: usize,
+    pub symbols: Vec<u8>,
+    pub start_prb: u16,
+    pub num_prb: u16,
+}
+
+impl Allocation {
+    pub fn num_re(&self) -> usize {
+        self.symbols.len() * self.num_prb as usize * RE_PER_PRB
+    }
+
+    // (symbol, subcarrier, sample) of the REs
+    pub fn samples(&self, grid: &SlotGrid) -> Vec<(u8, usize, Complex<f32>)> {
+        let first = self.start_prb as usize * RE_PER_PRB;
+        let last = first + self.num_prb as usize * RE_PER_PRB;
+        self.symbols.iter()
+            .flat_map(|&l| (first..last).map(move |k| (l, k)))
+            .filter(|&(l, k)| grid.filled[[l as usize, k, self.antenna]])
+            .map(|(l, k)| (l, k, grid.grid[[l as usize, k, self.antenna]]))
+            .collect()
+    }
+}
+
+// Runs of completely filled PRBs of every symbol and antenna, the symbols with the same
+// run are one allocation. DMRS, PDCCH or SSB mixed into the runs show up as a high EVM.
+pub fn allocations(grid: &SlotGrid) -> Vec<Allocation> {
+    let num_prb = grid.num_subcarriers() / RE_PER_PRB;
+    let mut runs: BTreeMap<(usize, u16, u16), Vec<u8>> = BTreeMap::new();
+    for antenna in 0..grid.eaxc_ids.len() {
+        for l in 0..grid.num_symbols() {
+            let full = |prb: usize| (prb * RE_PER_PRB..(prb + 1) * RE_PER_PRB).all(|k| grid.filled[[l, k, antenna]]);
+            let mut prb = 0;
+            while prb < num_prb {
+                if !full(prb) {
+                    prb += 1;
+                    continue;
+                }
+                let start = prb;
+                while prb < num_prb && full(prb) {
+                    prb += 1;
+                }
+                runs.entry((antenna, start as u16, (prb - start) as u16)).or_default().push(l as u8);
+            }
+        }
+    }
+    runs.into_iter()
+        .map(|((antenna, start_prb, num_prb), symbols)| Allocation { antenna, symbols, start_prb, num_prb })
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AllocationEvm {
+    pub allocation: Allocation,
+    pub modulation: Option<Modulation>,     // None: no constellation fits
+    pub evm: Option<EvmMeasurement>,        // of the classified modulation, or 1024QAM
+    pub res: Vec<(u8, usize)>,              // (symbol, subcarrier) of evm.per_re
+}
+
+pub fn analyse_allocation(grid: &SlotGrid, allocation: &Allocation) -> AllocationEvm {
+    let samples = allocation.samples(grid);
+    let values: Vec<Complex<f32>> = samples.iter().map(|s| s.2).collect();
+    let (modulation, evm) = match classify(&values) {
+        Some((m, evm)) => (Some(m), Some(evm)),
+        None => (None, measure_evm(&values, Modulation::Qam1024)),
+    };
+    AllocationEvm {
+        allocation: allocation.clone(),
+        modulation,
+        evm,
+        res: samples.iter().map(|s| (s.0, s.1)).collect(),
+    }
+}
+
+pub fn analyse_grid(grid: &SlotGrid) -> Vec<AllocationEvm> {
+    allocations(grid).iter().map(|a| analyse_allocation(grid, a)).collect()
+}
+
+// One line per allocation of every slot
+pub fn write_evm_csv<W: Write>(w: &mut W, results: &[(&SlotGrid, Vec<AllocationEvm>)]) -> io::Result<()> {
+    writeln!(w, "frame_id, subframe_id, slot_id, eaxc, symbols, start_prb, num_prb, modulation, evm_percent, evm_db")?;
+    for (grid, allocations) in results.iter() {
+        for r in allocations.iter() {
+            let a = &r.allocation;
+            let modulation = r.modulation.map_or("unknown".to_string(), |m| m.to_string());
+            let (percent, db) = r.evm.as_ref().map_or((f64::NAN, f64::NAN), |e| (e.evm_percent(), e.evm_db()));
+            writeln!(w, "{}, {}, {}, {}, {:?}, {}, {}, {}, {:.3}, {:.2}",
+                     grid.frame_id, grid.subframe_id, grid.slot_id, grid.eaxc_ids[a.antenna],
+                     a.symbols, a.start_prb, a.num_prb, modulation, percent, db)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::EaxcIdConfig;
+
+    // Deterministic pseudo random points of the constellation with a little noise
+    fn symbols(modulation: Modulation, count: usize, gain: Complex<f32>, noise: f32) -> Vec<Complex<f32>> {
+        let points = modulation.points();
+        let mut state = 12345u32;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 8) as usize
+        };
+        (0..count)
+            .map(|_| {
+                let p = points[next() % points.len()];
+                let n = Complex::new((next() % 2001) as f32 / 1000.0 - 1.0, (next() % 2001) as f32 / 1000.0 - 1.0);
+                (p + n * noise) * gain
+            })
+            .collect()
+    }
+
+    #[test]
+    fn constellations() {
+        for m in MODULATIONS.iter() {
+            let points = m.points();
+            assert_eq!(points.len() as u32, m.order());
+            let power: f32 = points.iter().map(|p| p.norm_sqr()).sum::<f32>() / points.len() as f32;
+            assert!((power - 1.0).abs() < 1e-5);
+            assert!(points.iter().all(|p| m.slice(*p) == *p));
+        }
+        assert!((Modulation::Qam16.d_min() - 2.0 / 10f32.sqrt()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn classification_and_evm() {
+        let gain = Complex::new(3000.0, 800.0);
+        for m in MODULATIONS.iter() {
+            let samples = symbols(*m, 3000, gain, 0.0);
+            let (found, evm) = classify(&samples).unwrap();
+            assert_eq!(found, *m);
+            assert!(evm.evm_rms < 1e-4);
+            assert!((evm.gain - gain).norm() < 1.0);
+        }
+        // 1 % EVM on 64QAM
+        let samples = symbols(Modulation::Qam64, 3000, gain, 0.017);
+        let (found, evm) = classify(&samples).unwrap();
+        assert_eq!(found, Modulation::Qam64);
+        assert!(evm.evm_percent() > 0.5 && evm.evm_percent() < 1.5, "{}", evm.evm_percent());
+        assert_eq!(evm.per_re.len(), 3000);
+    }
+
+    #[test]
+    fn grid_allocations() {
+        let mut grid = SlotGrid::new((1, 0, 0, 0), vec![EaxcIdConfig::default().decode(0)], 14, 10);
+        let samples = symbols(Modulation::Qam256, 12 * 4 * 2, Complex::new(1000.0, 0.0), 0.0);
+        for (n, l) in [2usize, 3].iter().enumerate() {
+            for k in 24..72 {
+                grid.grid[[*l, k, 0]] = samples[n * 48 + k - 24];
+                grid.filled[[*l, k, 0]] = true;
+            }
+        }
+        let results = analyse_grid(&grid);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].allocation, Allocation { antenna: 0, symbols: vec![2, 3], start_prb: 2, num_prb: 4 });
+        assert_eq!(results[0].modulation, Some(Modulation::Qam256));
+        assert_eq!(results[0].res.len(), 96);
+    }
+}
@@ -0,0 +1,273 @@
+use std::f64::consts::PI;
+use std::io::{self, Write};
+use ndarray::{s, Array2, ArrayView1};
+use num::complex::Complex;
+use crate::protocols::{DataDirection, EaxcId, FFTSize};
+use crate::utility::carrier_config::{CarrierConfig, CarrierConfigError};
+use crate::utility::resource_grid::{SlotGrid, SlotKey};
+
+// OFDM baseband signal of a slot grid, TS 38.211 5.3.1:
+//   s_l(t) = sum_k a_k,l * exp(j2π (k + k_0 - N_sc / 2) Δf (t - N_CP,l Tc - t_start,l))
+// Subcarrier k of the grid goes to bin (k - N_sc / 2) mod N_FFT of the IFFT, the CP is the
+// end of the symbol, the first symbol of every 0.5 ms is 16κ Tc longer. The transforms are
+// unitary (1 / sqrt(N_FFT)) so the samples keep the 16 bits scale of the REs, the full scale
+// of a time sample is 2^15 as for the REs.
+// With the half subcarrier shift (UL, frequencyShift7p5khz of TS 38.211 6.3.1.6 / LTE UL) the
+// subcarriers sit at (k - N_sc / 2 + 1/2) Δf, i.e. the samples are multiplied by
+// exp(jπ t / N_FFT), t counted from the start of the useful part. The upconversion phase
+// term of 5.4 is left out, it is a constant per symbol.
+
+// In place radix 2 FFT, exp(-j2π kn / N) (exp(+j2π kn / N) if inverse), not normalised.
+// The length must be a power of 2.
+pub fn fft_in_place(buf: &mut [Complex<f32>], inverse: bool) {
+    let n = buf.len();
+    assert!(n.is_power_of_two(), "FFT length {} isn't a power of 2.", n);
+    let bits = n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits().checked_shr(usize::BITS - bits).unwrap_or(0);
+        if i < j {
+            buf.swap(i, j);
+        }
+    }
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut len = 2;
+    while len <= n {
+        let angle = sign * 2.0 * PI / len as f64;
+        for start in (0..n).step_by(len) {
+            for i in 0..len / 2 {
+                let (sin, cos) = (angle * i as f64).sin_cos();
+                let w = Complex::new(cos as f32, sin as f32);
+                let a = buf[start + i];
+                let b = buf[start + i + len / 2] * w;
+                buf[start + i] = a + b;
+                buf[start + i + len / 2] = a - b;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+pub struct OfdmModulator {
+    pub carrier: CarrierConfig,
+    pub half_subcarrier_shift: bool,     // applied to UL only
+}
+
+impl OfdmModulator {
+    // The FFT size of the carrier has to be a power of 2 holding every subcarrier
+    pub fn new(carrier: &CarrierConfig) -> Result<Self, CarrierConfigError> {
+        carrier.check_fft_size()?;
+        Ok(Self { carrier: carrier.clone(), half_subcarrier_shift: false })
+    }
+
+    // The FFT size of the frameStructure instead of the one of the carrier,
+    // None if reserved or smaller than the subcarriers of the carrier
+    pub fn with_fft_size(mut self, fft_size: FFTSize) -> Option<Self> {
+        let size = fft_size.size().filter(|&size| size >= self.carrier.num_subcarriers())?;
+        self.carrier.fft_size = size;
+        Some(self)
+    }
+
+    pub fn with_half_subcarrier_shift(mut self, shift: bool) -> Self {
+        self.half_subcarrier_shift = shift;
+        self
+    }
+
+    pub fn fft_size(&self) -> usize {
+        self.carrier.fft_size
+    }
+
+    // Samples of the slot, CPs included
+    pub fn slot_length(&self, slot: u8) -> usize {
+        self.carrier.cp_lengths(slot).iter().map(|cp| cp + self.fft_size()).sum()
+    }
+
+    // Slots since slot 0 of subframe 0 of frame 0
+    pub fn slot_number(&self, key: SlotKey) -> u32 {
+        let slots_per_subframe = self.carrier.numerology.slots_per_subframe() as u32;
+        (key.1 as u32 * 10 + key.2 as u32) * slots_per_subframe + key.3 as u32
+    }
+
+    // Zeros for the slots between two slot numbers (both excluded), the gap of a stream
+    // without U-Plane data
+    pub fn silence(&self, from: u32, to: u32) -> Vec<Complex<f32>> {
+        let slots_per_subframe = self.carrier.numerology.slots_per_subframe() as u32;
+        let length: usize = (from.saturating_add(1)..to).map(|n| self.slot_length((n % slots_per_subframe) as u8)).sum();
+        vec![Complex::new(0.0, 0.0); length]
+    }
+
+    fn shifted(&self, dir: DataDirection) -> bool {
+        self.half_subcarrier_shift && dir == DataDirection::UL
+    }
+
+    // IFFT bin of subcarrier k of the grid
+    fn bin(&self, k: usize) -> usize {
+        let n = self.fft_size();
+        (k + n - self.carrier.num_subcarriers() / 2) % n
+    }
+
+    fn modulate_symbol(&self, subcarriers: ArrayView1<Complex<f32>>, cp: usize, shift: bool, out: &mut Vec<Complex<f32>>) {
+        let n = self.fft_size();
+        let mut buf = vec![Complex::new(0.0, 0.0); n];
+        for (k, x) in subcarriers.iter().enumerate() {
+            buf[self.bin(k)] = *x;
+        }
+        fft_in_place(&mut buf, true);
+        let scale = 1.0 / (n as f32).sqrt();
+        for t in -(cp as i64)..n as i64 {
+            let mut x = buf[t.rem_euclid(n as i64) as usize] * scale;
+            if shift {
+                let (sin, cos) = (PI * t as f64 / n as f64).sin_cos();
+                x *= Complex::new(cos as f32, sin as f32);
+            }
+            out.push(x);
+        }
+    }
+
+    // Time domain samples of one antenna of the grid
+    pub fn modulate(&self, grid: &SlotGrid, antenna: usize) -> Vec<Complex<f32>> {
+        let shift = self.shifted(grid.dir);
+        let mut samples = Vec::with_capacity(self.slot_length(grid.slot_id));
+        for (symbol, cp) in self.carrier.cp_lengths(grid.slot_id).into_iter().enumerate().take(grid.num_symbols()) {
+            self.modulate_symbol(grid.grid.slice(s![symbol, .., antenna]), cp, shift, &mut samples);
+        }
+        samples
+    }
+
+    // Symbol x subcarrier of the samples of one slot, the CPs are dropped
+    pub fn demodulate(&self, samples: &[Complex<f32>], dir: DataDirection, slot: u8) -> Array2<Complex<f32>> {
+        let n = self.fft_size();
+        let num_subcarriers = self.carrier.num_subcarriers();
+        let cp_lengths = self.carrier.cp_lengths(slot);
+        let shift = self.shifted(dir);
+        let scale = 1.0 / (n as f32).sqrt();
+        let mut grid = Array2::from_elem((cp_lengths.len(), num_subcarriers), Complex::new(0.0, 0.0));
+        let mut start = 0;
+        for (symbol, cp) in cp_lengths.into_iter().enumerate() {
+            start += cp;
+            if start + n > samples.len() {
+                break;
+            }
+            let mut buf: Vec<Complex<f32>> = samples[start..start + n].to_vec();
+            if shift {
+                for (t, x) in buf.iter_mut().enumerate() {
+                    let (sin, cos) = (PI * t as f64 / n as f64).sin_cos();
+                    *x *= Complex::new(cos as f32, -sin as f32);
+                }
+            }
+            fft_in_place(&mut buf, false);
+            for k in 0..num_subcarriers {
+                grid[[symbol, k]] = buf[self.bin(k)] * scale;
+            }
+            start += n;
+        }
+        grid
+    }
+
+    // Grid of the waveforms of every eAxC, every RE is `filled`
+    pub fn demodulate_grid(&self, key: SlotKey, eaxc_ids: Vec<EaxcId>, waveforms: &[Vec<Complex<f32>>]) -> SlotGrid {
+        let mut grid = SlotGrid::new(key, eaxc_ids, self.carrier.symbols_per_slot() as usize, self.carrier.n_rb);
+        for (antenna, samples) in waveforms.iter().enumerate().take(grid.eaxc_ids.len()) {
+            let symbols = self.demodulate(samples, grid.dir, grid.slot_id);
+            grid.grid.slice_mut(s![.., .., antenna]).assign(&symbols);
+        }
+        grid.filled.fill(true);
+        grid
+    }
+}
+
+// Interleaved I/Q, 16 bits little endian, saturated: the raw format of most signal analysers
+pub fn write_iq_i16<W: Write>(w: &mut W, samples: &[Complex<f32>]) -> io::Result<()> {
+    let to_i16 = |x: f32| x.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+    for x in samples.iter() {
+        w.write_all(&to_i16(x.re).to_le_bytes())?;
+        w.write_all(&to_i16(x.im).to_le_bytes())?;
+    }
+    Ok(())
+}
+
+pub fn write_waveform_csv<W: Write>(w: &mut W, samples: &[Complex<f32>]) -> io::Result<()> {
+    writeln!(w, "i, q")?;
+    for x in samples.iter() {
+        writeln!(w, "{}, {}", x.re, x.im)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::EaxcIdConfig;
+    use crate::utility::timing_model::{CpType, Numerology};
+
+    #[test]
+    fn fft_against_dft() {
+        let x: Vec<Complex<f32>> = (0..16).map(|i| Complex::new((i * 7 % 5) as f32, (i * 3 % 4) as f32 - 1.5)).collect();
+        let mut y = x.clone();
+        fft_in_place(&mut y, false);
+        for (k, yk) in y.iter().enumerate() {
+            let dft: Complex<f32> = x.iter().enumerate()
+                .map(|(n, xn)| xn * Complex::from_polar(1.0, -2.0 * std::f32::consts::PI * (k * n) as f32 / 16.0))
+                .sum();
+            assert!((yk - dft).norm() < 1e-3);
+        }
+        fft_in_place(&mut y, true);
+        assert!(x.iter().zip(y.iter()).all(|(a, b)| (a * 16.0 - b).norm() < 1e-3));
+    }
+
+    fn grid(dir: u8, slot: u8) -> SlotGrid {
+        let config = EaxcIdConfig::default();
+        let mut grid = SlotGrid::new((dir, 0, 0, slot), vec![config.decode(0)], 14, 25);
+        for l in 0..14 {
+            for k in 0..300 {
+                grid.grid[[l, k, 0]] = Complex::new(if (k + l) % 3 == 0 { 1000.0 } else { -1000.0 }, (k as f32 - 150.0) * 10.0);
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn cp_and_round_trip() {
+        // 10 MHz at 15 kHz: 52 PRBs don't fit in 512 points
+        let carrier = CarrierConfig::new(None, 10, Numerology::new(0, CpType::Normal)).unwrap();
+        let modulator = OfdmModulator::new(&carrier).unwrap().with_fft_size(FFTSize::I_512);
+        assert!(modulator.is_none());
+        let carrier = CarrierConfig::new(None, 5, Numerology::new(0, CpType::Normal)).unwrap();
+        let modulator = OfdmModulator::new(&carrier).unwrap().with_fft_size(FFTSize::I_512).unwrap();
+        // 15 kHz: symbols 0 and 7 start a 0.5 ms, 1 ms at 7.68 MHz
+        assert_eq!(modulator.slot_length(0), 14 * 512 + 2 * 40 + 12 * 36);
+        assert_eq!(modulator.slot_length(0), 7680);
+
+        for (dir, shift) in [(1, false), (0, true)] {
+            let modulator = OfdmModulator::new(&carrier).unwrap().with_fft_size(FFTSize::I_512).unwrap().with_half_subcarrier_shift(shift);
+            let slot = grid(dir, 0);
+            let samples = modulator.modulate(&slot, 0);
+            assert_eq!(samples.len(), modulator.slot_length(0));
+            // the CP repeats the end of the first symbol, up to the half subcarrier rotation (-1)
+            let sign = if shift { -1.0 } else { 1.0 };
+            assert!((samples[0] - samples[512] * sign).norm() < 1e-2);
+            // unitary: energy of the symbol = energy of the REs
+            let energy: f32 = samples[40..552].iter().map(|x| x.norm_sqr()).sum();
+            let re_energy: f32 = slot.grid.slice(s![0, .., 0]).iter().map(|x| x.norm_sqr()).sum();
+            assert!((energy / re_energy - 1.0).abs() < 1e-3);
+
+            let back = modulator.demodulate_grid((dir, 0, 0, 0), slot.eaxc_ids.clone(), &[samples]);
+            let error = (&back.grid - &slot.grid).iter().map(|x| x.norm()).fold(0.0, f32::max);
+            assert!(error < 0.5, "max error {}", error);
+        }
+    }
+
+    #[test]
+    fn fft_size_and_gaps() {
+        // 273 PRBs: 3276 subcarriers
+        for &fft_size in [3072, 2048].iter() {
+            let carrier = CarrierConfig { fft_size, ..CarrierConfig::default() };
+            assert!(OfdmModulator::new(&carrier).is_err());
+        }
+
+        // 30 kHz: 2 slots per subframe
+        let modulator = OfdmModulator::new(&CarrierConfig::default()).unwrap();
+        assert_eq!(modulator.slot_number((1, 1, 2, 1)), 25);
+        assert!(modulator.silence(25, 26).is_empty());
+        assert_eq!(modulator.silence(25, 28).len(), 2 * 61440);
+    }
+}
@@ -0,0 +1,369 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use num::complex::Complex;
+use crate::protocols::EaxcId;
+use crate::utility::carrier_config::CarrierConfig;
+use crate::utility::polar_code::PolarCode;
+use crate::utility::resource_grid::{SlotGrid, SlotKey};
+use crate::utility::ssb_detection::{gold_sequence, pbch_data_res, pbch_dmrs, pbch_dmrs_res, SsbDetection, SsbSearch};
+
+// PBCH of a detected SSB down to the MIB, TS 38.211 7.3.3 / TS 38.212 7.1:
+//   - channel estimate at the DMRS REs (y / r), linearly interpolated over the 4 subcarriers
+//     between them, QPSK LLRs of the 432 data REs (first k then l)
+//   - descrambling by c(i + ν 864), c_init = PCI, ν = 3 LSBs of the SSB index (2 if L_max = 4)
+//   - polar decoding, K = 56, E = 864, N = 512, I_IL = 1, then the CRC24C
+//   - first descrambling of the 32 bits payload by c(j + v M), v = 3rd and 2nd LSBs of the SFN,
+//     M = 29 (26 if L_max = 64), skipping those SFN bits, the half frame bit (and the SSB
+//     index bits if L_max = 64), then deinterleaving by G(j)
+// The payload is the BCCH-BCH-Message (choice bit + 23 bits MIB of TS 38.331) followed by the
+// 4 LSBs of the SFN, the half frame bit and the SSB index bits 5..3 (L_max = 64) or the MSB
+// of k_SSB. i_SSB_bar of the DMRS is i_SSB + 4 n_hf if L_max = 4, both ν are tried when the
+// carrier has L_max = 8. The eCPRI frameId is the SFN mod 256.
+
+pub const PBCH_PAYLOAD_BITS: usize = 32;
+pub const PBCH_BITS: usize = 864;
+const MIB_BITS: usize = 24;
+
+// Table 7.1.1-1: PBCH payload interleaver pattern G(j)
+const PAYLOAD_INTERLEAVER: [usize; PBCH_PAYLOAD_BITS] = [
+    16, 23, 18, 17, 8, 30, 10, 6, 24, 7, 0, 5, 3, 2, 1, 4, 9, 11, 12, 13, 14, 15, 19, 20, 21, 22, 25, 26, 27, 28, 29, 31,
+];
+
+// gCRC24C(D) = D^24 + D^23 + D^21 + D^20 + D^17 + D^15 + D^13 + D^12 + D^8 + D^4 + D^2 + D + 1
+const CRC24C: u32 = 0xB2B117;
+
+pub fn crc24c(bits: &[u8]) -> Vec<u8> {
+    let mut crc = 0u32;
+    for &bit in bits {
+        let feedback = ((crc >> 23) & 1) as u8 ^ bit;
+        crc = (crc << 1) & 0xFF_FFFF;
+        if feedback == 1 {
+            crc ^= CRC24C;
+        }
+    }
+    (0..24).rev().map(|i| ((crc >> i) & 1) as u8).collect()
+}
+
+fn to_bits(value: u32, width: usize) -> impl Iterator<Item = u8> {
+    (0..width).rev().map(move |i| ((value >> i) & 1) as u8)
+}
+
+fn from_bits(bits: &[u8]) -> u32 {
+    bits.iter().fold(0, |v, &b| (v << 1) | b as u32)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mib {
+    pub sfn: u16,                        // 10 bits, 6 MSBs in the MIB
+    pub half_frame: u8,
+    pub sub_carrier_spacing_common: u8,  // 0: scs15or60, 1: scs30or120
+    pub ssb_subcarrier_offset: u8,       // k_SSB, the MSB from the payload if L_max != 64
+    pub dmrs_type_a_position: u8,        // 2 or 3
+    pub pdcch_config_sib1: u8,           // controlResourceSetZero (4 bits), searchSpaceZero (4 bits)
+    pub cell_barred: bool,
+    pub intra_freq_reselection: bool,    // allowed
+    pub ssb_index_msbs: Option<u8>,      // bits 5..3 of the SSB index if L_max = 64
+}
+
+impl Mib {
+    // ā_0..ā_31
+    pub fn payload(&self) -> Vec<u8> {
+        let mut bits = vec![0];
+        bits.extend(to_bits(self.sfn as u32 >> 4, 6));
+        bits.push(self.sub_carrier_spacing_common);
+        bits.extend(to_bits(self.ssb_subcarrier_offset as u32 & 0xF, 4));
+        bits.push((self.dmrs_type_a_position == 3) as u8);
+        bits.extend(to_bits(self.pdcch_config_sib1 as u32, 8));
+        bits.push(!self.cell_barred as u8);
+        bits.push(!self.intra_freq_reselection as u8);
+        bits.push(0);
+        bits.extend(to_bits(self.sfn as u32 & 0xF, 4));
+        bits.push(self.half_frame);
+        match self.ssb_index_msbs {
+            Some(msbs) => bits.extend(to_bits(msbs as u32, 3)),
+            None => bits.extend([self.ssb_subcarrier_offset >> 4, 0, 0].iter()),
+        }
+        bits
+    }
+
+    pub fn from_payload(bits: &[u8], l_max: u8) -> Self {
+        let sfn = ((from_bits(&bits[1..7]) << 4) | from_bits(&bits[MIB_BITS..MIB_BITS + 4])) as u16;
+        let k_ssb = from_bits(&bits[8..12]) as u8;
+        Self {
+            sfn,
+            half_frame: bits[MIB_BITS + 4],
+            sub_carrier_spacing_common: bits[7],
+            ssb_subcarrier_offset: if l_max == 64 { k_ssb } else { (bits[MIB_BITS + 5] << 4) | k_ssb },
+            dmrs_type_a_position: 2 + bits[12],
+            pdcch_config_sib1: from_bits(&bits[13..21]) as u8,
+            cell_barred: bits[21] == 0,
+            intra_freq_reselection: bits[22] == 0,
+            ssb_index_msbs: if l_max == 64 { Some(from_bits(&bits[MIB_BITS + 5..]) as u8) } else { None },
+        }
+    }
+}
+
+impl fmt::Display for Mib {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            w,
+            "SFN: {}, half frame: {}, subCarrierSpacingCommon: {}, ssb-SubcarrierOffset: {}, dmrs-TypeA-Position: pos{}, \
+             pdcch-ConfigSIB1: {}/{}, cellBarred: {}, intraFreqReselection: {}",
+            self.sfn, self.half_frame,
+            if self.sub_carrier_spacing_common == 0 { "scs15or60" } else { "scs30or120" },
+            self.ssb_subcarrier_offset, self.dmrs_type_a_position,
+            self.pdcch_config_sib1 >> 4, self.pdcch_config_sib1 & 0xF,
+            if self.cell_barred { "barred" } else { "notBarred" },
+            if self.intra_freq_reselection { "allowed" } else { "notAllowed" },
+        )
+    }
+}
+
+// Payload bit i goes to a_G(j), 7.1.1
+fn interleaver_positions() -> Vec<usize> {
+    let (mut j_sfn, mut j_ssb, mut j_other) = (0, 11, 14);
+    (0..PBCH_PAYLOAD_BITS).map(|i| {
+        let j = if (1..7).contains(&i) || (MIB_BITS..MIB_BITS + 4).contains(&i) {
+            j_sfn += 1;
+            j_sfn - 1
+        } else if i == MIB_BITS + 4 {
+            10
+        } else if i > MIB_BITS + 4 {
+            j_ssb += 1;
+            j_ssb - 1
+        } else {
+            j_other += 1;
+            j_other - 1
+        };
+        PAYLOAD_INTERLEAVER[j]
+    }).collect()
+}
+
+// First scrambling of the interleaved payload, 7.1.2. v from the SFN bits, which aren't scrambled.
+fn scramble_payload(a: &mut [u8], pci: u16, l_max: u8) {
+    // 3rd and 2nd LSBs of the SFN: j_SFN 7 and 8, the half frame bit: j 10, SSB index: j 11..13
+    let v = (2 * a[PAYLOAD_INTERLEAVER[7]] + a[PAYLOAD_INTERLEAVER[8]]) as usize;
+    let mut skipped: Vec<usize> = [7, 8, 10].iter().map(|&j| PAYLOAD_INTERLEAVER[j]).collect();
+    if l_max == 64 {
+        skipped.extend(PAYLOAD_INTERLEAVER[11..14].iter());
+    }
+    let m = PBCH_PAYLOAD_BITS - skipped.len();
+    let c = gold_sequence(pci as u32, 4 * m);
+    let mut j = 0;
+    for (i, bit) in a.iter_mut().enumerate() {
+        if !skipped.contains(&i) {
+            *bit ^= c[j + v * m];
+            j += 1;
+        }
+    }
+}
+
+// PBCH bits of the MIB, before modulation: b̃(0..863) of TS 38.211 7.3.3.1
+pub fn pbch_encode(mib: &Mib, pci: u16, ssb_index: u8, l_max: u8) -> Vec<u8> {
+    let mut a = vec![0u8; PBCH_PAYLOAD_BITS];
+    for (bit, pos) in mib.payload().into_iter().zip(interleaver_positions()) {
+        a[pos] = bit;
+    }
+    scramble_payload(&mut a, pci, l_max);
+    let crc = crc24c(&a);
+    a.extend(crc);
+    let code = PolarCode::new(a.len(), PBCH_BITS, 9, true);
+    let nu = ssb_index as usize & if l_max == 4 { 3 } else { 7 };
+    let c = gold_sequence(pci as u32, (nu + 1) * PBCH_BITS);
+    code.encode(&a).into_iter().enumerate().map(|(i, b)| b ^ c[i + nu * PBCH_BITS]).collect()
+}
+
+// QPSK LLRs of the PBCH data REs, > 0: bit 0
+pub fn pbch_llr(detection: &SsbDetection, grid: &SlotGrid) -> Vec<f32> {
+    let pci = detection.pci();
+    let dmrs_res = pbch_dmrs_res(pci);
+    let channel: BTreeMap<(usize, usize), Complex<f32>> = dmrs_res.iter().cloned()
+        .zip(detection.res(grid, &dmrs_res).into_iter().zip(pbch_dmrs(pci, detection.ssb_index)))
+        .map(|(pos, (y, r))| (pos, y * r.conj()))
+        .collect();
+    let v = pci as usize % 4;
+    let data_res = pbch_data_res(pci);
+    let mut llr = Vec::with_capacity(2 * data_res.len());
+    for (&(l, k), y) in data_res.iter().zip(detection.res(grid, &data_res)) {
+        let below = (k >= v).then(|| k - (k - v) % 4).and_then(|kk| channel.get(&(l, kk)).map(|h| (kk, *h)));
+        let above = channel.range((l, k)..(l, k + 4)).next().map(|(&(_, kk), h)| (kk, *h));
+        let h = match (below, above) {
+            (Some((k0, h0)), Some((k1, h1))) => h0 + (h1 - h0) * ((k - k0) as f32 / (k1 - k0) as f32),
+            (Some((_, h)), None) | (None, Some((_, h))) => h,
+            (None, None) => Complex::new(1.0, 0.0),
+        };
+        let x = y * h.conj();
+        llr.push(x.re);
+        llr.push(x.im);
+    }
+    llr
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PbchDecode {
+    pub slot: SlotKey,
+    pub eaxc_id: EaxcId,
+    pub pci: u16,
+    pub ssb_index: u8,
+    pub mib: Option<Mib>,     // None: CRC error
+}
+
+impl PbchDecode {
+    // SFN mod 256 and half frame against frameId and subframeId of the slot
+    pub fn sfn_matches(&self) -> Option<bool> {
+        self.mib.as_ref().map(|m| m.sfn % 256 == self.slot.1 as u16 && m.half_frame == (self.slot.2 >= 5) as u8)
+    }
+}
+
+pub fn decode_pbch(detection: &SsbDetection, grid: &SlotGrid, l_max: u8) -> PbchDecode {
+    let pci = detection.pci();
+    let llr = pbch_llr(detection, grid);
+    let code = PolarCode::new(PBCH_PAYLOAD_BITS + 24, PBCH_BITS, 9, true);
+    let i_ssb_bar = detection.ssb_index;
+    // (ν, L_max)
+    let mut hypotheses = vec![(i_ssb_bar, l_max)];
+    if l_max == 8 {
+        hypotheses.push((i_ssb_bar & 3, 4));
+    }
+    let c = gold_sequence(pci as u32, 8 * PBCH_BITS);
+    let mut result = PbchDecode {
+        slot: detection.slot,
+        eaxc_id: detection.eaxc_id,
+        pci,
+        ssb_index: i_ssb_bar,
+        mib: None,
+    };
+    for (nu, l_max) in hypotheses {
+        let offset = nu as usize * PBCH_BITS;
+        let descrambled: Vec<f32> = llr.iter().enumerate()
+            .map(|(i, &l)| if c[i + offset] == 0 { l } else { -l })
+            .collect();
+        let bits = code.decode(&descrambled);
+        let (a, crc) = bits.split_at(PBCH_PAYLOAD_BITS);
+        if crc24c(a) != crc {
+            continue;
+        }
+        let mut a = a.to_vec();
+        scramble_payload(&mut a, pci, l_max);
+        let payload: Vec<u8> = interleaver_positions().into_iter().map(|pos| a[pos]).collect();
+        let mib = Mib::from_payload(&payload, l_max);
+        result.ssb_index = match (l_max, mib.ssb_index_msbs) {
+            (64, Some(msbs)) => (msbs << 3) | i_ssb_bar,
+            (4, _) => i_ssb_bar & 3,
+            _ => i_ssb_bar,
+        };
+        result.mib = Some(mib);
+        break;
+    }
+    result
+}
+
+// L_max of the carrier: 64 in FR2, 8 in FR1 (the 4 of the bands below 3 GHz is tried as well)
+pub fn l_max(carrier: &CarrierConfig) -> u8 {
+    if carrier.numerology.mu >= 3 { 64 } else { 8 }
+}
+
+#[derive(Default)]
+pub struct MibDecoding {
+    pub results: Vec<PbchDecode>,
+}
+
+impl MibDecoding {
+    pub fn from_search(search: &SsbSearch, grids: &BTreeMap<SlotKey, SlotGrid>, l_max: u8) -> Self {
+        Self {
+            results: search.detections.iter()
+                .filter_map(|d| grids.get(&d.slot).map(|grid| decode_pbch(d, grid, l_max)))
+                .collect(),
+        }
+    }
+
+    pub fn write_csv<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "frame_id, subframe_id, slot_id, eaxc, pci, ssb_index, crc, sfn, half_frame, scs_common, ssb_subcarrier_offset, dmrs_type_a_position, pdcch_config_sib1, cell_barred, intra_freq_reselection, sfn_matches")?;
+        for r in self.results.iter() {
+            write!(w, "{}, {}, {}, {}, {}, {}", r.slot.1, r.slot.2, r.slot.3, r.eaxc_id, r.pci, r.ssb_index)?;
+            match &r.mib {
+                Some(m) => writeln!(w, ", ok, {}, {}, {}, {}, {}, {}, {}, {}, {}",
+                                    m.sfn, m.half_frame, m.sub_carrier_spacing_common, m.ssb_subcarrier_offset,
+                                    m.dmrs_type_a_position, m.pdcch_config_sib1, m.cell_barred as u8,
+                                    m.intra_freq_reselection as u8, r.sfn_matches() == Some(true))?,
+                None => writeln!(w, ", failed, , , , , , , , , ")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for MibDecoding {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        let decoded: Vec<&PbchDecode> = self.results.iter().filter(|r| r.mib.is_some()).collect();
+        let mismatches = decoded.iter().filter(|r| r.sfn_matches() == Some(false)).count();
+        writeln!(w, "PBCH decoded: {}/{}, SFN not matching frameId: {}", decoded.len(), self.results.len(), mismatches)?;
+        if let Some(r) = decoded.first() {
+            writeln!(w, "PCI {}, SSB {}: {}", r.pci, r.ssb_index, r.mib.as_ref().unwrap())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::EaxcIdConfig;
+    use crate::utility::ssb_detection::{search_grid, DETECTION_THRESHOLD};
+    use crate::utility::ssb_detection::tests::place_ssb;
+
+    fn mib(sfn: u16, half_frame: u8) -> Mib {
+        Mib {
+            sfn,
+            half_frame,
+            sub_carrier_spacing_common: 1,
+            ssb_subcarrier_offset: 18,
+            dmrs_type_a_position: 3,
+            pdcch_config_sib1: 0x42,
+            cell_barred: false,
+            intra_freq_reselection: true,
+            ssb_index_msbs: None,
+        }
+    }
+
+    #[test]
+    fn payload_and_crc() {
+        let m = mib(0x2A5, 1);
+        let payload = m.payload();
+        assert_eq!(payload.len(), PBCH_PAYLOAD_BITS);
+        assert_eq!(Mib::from_payload(&payload, 8), m);
+        let mut positions = interleaver_positions();
+        positions.sort_unstable();
+        assert_eq!(positions, (0..32).collect::<Vec<usize>>());
+        // appending the CRC leaves no remainder
+        let mut bits: Vec<u8> = payload.clone();
+        bits.extend(crc24c(&payload));
+        assert!(crc24c(&bits).iter().all(|&b| b == 0));
+    }
+
+    fn decode(sfn: u16, half_frame: u8, frame_id: u8, i_ssb_bar: u8, l_max: u8, gain: Complex<f32>) -> PbchDecode {
+        let pci = 241;
+        let mut grid = SlotGrid::new((1, frame_id, 5 * half_frame, 0), vec![EaxcIdConfig::default().decode(0)], 14, 30);
+        let bits = pbch_encode(&mib(sfn, half_frame), pci, i_ssb_bar, l_max);
+        place_ssb(&mut grid, 2, 60, pci, i_ssb_bar, gain, &bits);
+        let detections = search_grid(&grid, DETECTION_THRESHOLD);
+        assert_eq!(detections.len(), 1);
+        decode_pbch(&detections[0], &grid, 8)
+    }
+
+    #[test]
+    fn decode_mib() {
+        let r = decode(260, 0, 4, 5, 8, Complex::new(-900.0, 2500.0));
+        assert_eq!((r.pci, r.ssb_index), (241, 5));
+        assert_eq!(r.mib, Some(mib(260, 0)));
+        assert_eq!(r.sfn_matches(), Some(true));
+
+        // L_max = 4: i_SSB_bar = i_SSB + 4 n_hf, ν = i_SSB
+        let r = decode(1023, 1, 0, 6, 4, Complex::new(3000.0, 0.0));
+        assert_eq!(r.ssb_index, 2);
+        assert_eq!(r.mib.as_ref().map(|m| (m.sfn, m.half_frame)), Some((1023, 1)));
+        // 1023 mod 256 = 255
+        assert_eq!(r.sfn_matches(), Some(false));
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use ndarray::{s, ArrayView1};
+use num::complex::Complex;
+use crate::protocols::{DataDirection, EaxcId, FULL_SCALE_16, RE_PER_PRB};
+use crate::utility::resource_grid::{SlotGrid, SlotKey};
+
+// Power of the decoded IQ in dBFS. The decompressors put every udIqWidth at the scale of a
+// 16 bits sample, so full scale is |x| = 2^15 whatever the compression:
+//   dBFS = 10 * log10(|x|^2 / 2^30)
+// Powers are averaged over the REs carried by the U-Plane (the `filled` REs of the grid).
+// The UL RSSI of a symbol is the total power over the occupied REs of the carrier, the same
+// as the RSSI measurement of TS 38.215 5.1.3 on the fronthaul samples.
+
+const FULL_SCALE_POWER: f64 = FULL_SCALE_16 as f64 * FULL_SCALE_16 as f64;
+
+pub fn power_dbfs(linear: f64) -> f64 {
+    10.0 * (linear / FULL_SCALE_POWER).log10()
+}
+
+pub fn re_power_dbfs(sample: Complex<f32>) -> f64 {
+    power_dbfs(sample.norm_sqr() as f64)
+}
+
+// (sum of |x|^2, number of REs) of the filled REs
+fn power_sum(samples: ArrayView1<Complex<f32>>, filled: ArrayView1<bool>) -> (f64, usize) {
+    samples.iter().zip(filled.iter())
+        .filter(|(_, &f)| f)
+        .fold((0.0, 0), |(sum, n), (x, _)| (sum + x.norm_sqr() as f64, n + 1))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrbPower {
+    pub slot: SlotKey,
+    pub eaxc_id: EaxcId,
+    pub symbol: u8,
+    pub prb: u16,
+    pub power_dbfs: f64,     // mean over the REs of the PRB
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SymbolPower {
+    pub slot: SlotKey,
+    pub eaxc_id: EaxcId,
+    pub symbol: u8,
+    pub num_re: usize,
+    pub power_dbfs: f64,      // mean per RE
+    pub rssi_dbfs: f64,       // total over the occupied REs
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlotPower {
+    pub slot: SlotKey,
+    pub eaxc_id: EaxcId,
+    pub num_re: usize,
+    pub power_dbfs: f64,      // mean per RE
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PowerStats {
+    pub slots: usize,
+    pub mean_dbfs: f64,       // linear average of the slot powers
+    pub min_dbfs: f64,
+    pub max_dbfs: f64,
+    linear_sum: f64,
+}
+
+impl PowerStats {
+    fn new() -> Self {
+        Self {
+            slots: 0,
+            mean_dbfs: f64::NEG_INFINITY,
+            min_dbfs: f64::INFINITY,
+            max_dbfs: f64::NEG_INFINITY,
+            linear_sum: 0.0,
+        }
+    }
+
+    fn add(&mut self, power_dbfs: f64) {
+        self.slots += 1;
+        self.linear_sum += 10f64.powf(power_dbfs / 10.0);
+        self.mean_dbfs = 10.0 * (self.linear_sum / self.slots as f64).log10();
+        self.min_dbfs = self.min_dbfs.min(power_dbfs);
+        self.max_dbfs = self.max_dbfs.max(power_dbfs);
+    }
+}
+
+#[derive(Default)]
+pub struct PowerAnalysis {
+    pub prbs: Vec<PrbPower>,
+    pub symbols: Vec<SymbolPower>,
+    pub slots: Vec<SlotPower>,
+    pub summary: BTreeMap<(u8, EaxcId), PowerStats>,    // (dataDirection, eAxC)
+}
+
+impl PowerAnalysis {
+    pub fn new() -> Self {
+        PowerAnalysis::default()
+    }
+
+    pub fn from_grids(grids: &BTreeMap<SlotKey, SlotGrid>) -> Self {
+        let mut analysis = PowerAnalysis::new();
+        for (key, grid) in grids.iter() {
+            analysis.push(*key, grid);
+        }
+        analysis
+    }
+
+    pub fn push(&mut self, key: SlotKey, grid: &SlotGrid) {
+        let num_prb = grid.num_subcarriers() / RE_PER_PRB;
+        for (antenna, eaxc_id) in grid.eaxc_ids.iter().enumerate() {
+            let (mut slot_sum, mut slot_re) = (0.0, 0);
+            for symbol in 0..grid.num_symbols() {
+                let samples = grid.grid.slice(s![symbol, .., antenna]);
+                let filled = grid.filled.slice(s![symbol, .., antenna]);
+                for prb in 0..num_prb {
+                    let res = prb * RE_PER_PRB..(prb + 1) * RE_PER_PRB;
+                    let (sum, n) = power_sum(samples.slice(s![res.clone()]), filled.slice(s![res]));
+                    if n > 0 {
+                        self.prbs.push(PrbPower {
+                            slot: key,
+                            eaxc_id: *eaxc_id,
+                            symbol: symbol as u8,
+                            prb: prb as u16,
+                            power_dbfs: power_dbfs(sum / n as f64),
+                        });
+                    }
+                }
+                let (sum, n) = power_sum(samples, filled);
+                if n > 0 {
+                    self.symbols.push(SymbolPower {
+                        slot: key,
+                        eaxc_id: *eaxc_id,
+                        symbol: symbol as u8,
+                        num_re: n,
+                        power_dbfs: power_dbfs(sum / n as f64),
+                        rssi_dbfs: power_dbfs(sum),
+                    });
+                }
+                slot_sum += sum;
+                slot_re += n;
+            }
+            if slot_re > 0 {
+                let power = power_dbfs(slot_sum / slot_re as f64);
+                self.slots.push(SlotPower { slot: key, eaxc_id: *eaxc_id, num_re: slot_re, power_dbfs: power });
+                self.summary.entry((key.0, *eaxc_id)).or_insert_with(PowerStats::new).add(power);
+            }
+        }
+    }
+
+    // Difference between the strongest and the weakest antenna port of the direction, in dB
+    pub fn power_imbalance_db(&self, dir: DataDirection) -> Option<f64> {
+        let means: Vec<f64> = self.summary.iter()
+            .filter(|((d, _), _)| *d == dir as u8)
+            .map(|(_, stats)| stats.mean_dbfs)
+            .collect();
+        if means.is_empty() {
+            return None;
+        }
+        let max = means.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let min = means.iter().cloned().fold(f64::INFINITY, f64::min);
+        Some(max - min)
+    }
+
+    pub fn write_prb_csv<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "dir, frame_id, subframe_id, slot_id, eaxc, symbol_id, prb, power_dbfs")?;
+        for p in self.prbs.iter() {
+            writeln!(w, "{}, {}, {}, {}, {}, {}, {}, {:.2}",
+                     p.slot.0, p.slot.1, p.slot.2, p.slot.3, p.eaxc_id, p.symbol, p.prb, p.power_dbfs)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_symbol_csv<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "dir, frame_id, subframe_id, slot_id, eaxc, symbol_id, num_re, power_dbfs, rssi_dbfs")?;
+        for p in self.symbols.iter() {
+            writeln!(w, "{}, {}, {}, {}, {}, {}, {}, {:.2}, {:.2}",
+                     p.slot.0, p.slot.1, p.slot.2, p.slot.3, p.eaxc_id, p.symbol, p.num_re, p.power_dbfs, p.rssi_dbfs)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_slot_csv<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "dir, frame_id, subframe_id, slot_id, eaxc, num_re, power_dbfs")?;
+        for p in self.slots.iter() {
+            writeln!(w, "{}, {}, {}, {}, {}, {}, {:.2}",
+                     p.slot.0, p.slot.1, p.slot.2, p.slot.3, p.eaxc_id, p.num_re, p.power_dbfs)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for PowerAnalysis {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(w, "{:>4} {:>18} {:>6} {:>10} {:>10} {:>10}", "dir", "eAxC", "slots", "mean dBFS", "min dBFS", "max dBFS")?;
+        for ((dir, eaxc_id), stats) in self.summary.iter() {
+            writeln!(
+                w,
+                "{:>4} {:>18} {:>6} {:>10.2} {:>10.2} {:>10.2}",
+                DataDirection::from(*dir).to_string(), eaxc_id.to_string(), stats.slots,
+                stats.mean_dbfs, stats.min_dbfs, stats.max_dbfs
+            )?;
+        }
+        if let Some(imbalance) = self.power_imbalance_db(DataDirection::DL) {
+            writeln!(w, "DL power imbalance across eAxCs: {:.2} dB", imbalance)?;
+        }
+        if let Some(rssi) = self.symbols.iter().filter(|p| p.slot.0 == DataDirection::UL as u8).map(|p| p.rssi_dbfs).reduce(f64::max) {
+            writeln!(w, "UL max RSSI: {:.2} dBFS", rssi)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::EaxcIdConfig;
+
+    #[test]
+    fn full_scale() {
+        assert!(re_power_dbfs(Complex::new(32768.0, 0.0)).abs() < 1e-9);
+        assert!((re_power_dbfs(Complex::new(16384.0, 0.0)) - -6.0206).abs() < 1e-3);
+        assert!((re_power_dbfs(Complex::new(3276.8, 0.0)) - -20.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn prb_symbol_and_slot_power() {
+        let config = EaxcIdConfig::default();
+        let mut grid = SlotGrid::new((0, 1, 2, 0), vec![config.decode(0), config.decode(1)], 14, 2);
+        // eAxC 0: PRB 0 of symbol 0 at -20 dBFS, PRB 1 empty
+        for k in 0..12 {
+            grid.grid[[0, k, 0]] = Complex::new(0.0, 3276.8);
+            grid.filled[[0, k, 0]] = true;
+        }
+        // eAxC 1: both PRBs of symbol 1 at -26 dBFS
+        for k in 0..24 {
+            grid.grid[[1, k, 1]] = Complex::new(1638.4, 0.0);
+            grid.filled[[1, k, 1]] = true;
+        }
+        let mut grids = BTreeMap::new();
+        grids.insert((0, 1, 2, 0), grid);
+        let analysis = PowerAnalysis::from_grids(&grids);
+        assert_eq!(analysis.prbs.len(), 3);
+        assert!((analysis.prbs[0].power_dbfs - -20.0).abs() < 1e-6);
+        assert_eq!(analysis.symbols.len(), 2);
+        let s = &analysis.symbols[1];
+        assert_eq!((s.symbol, s.num_re), (1, 24));
+        assert!((s.power_dbfs - -26.0206).abs() < 1e-3);
+        // 24 REs: +13.8 dB
+        assert!((s.rssi_dbfs - (-26.0206 + 13.8021)).abs() < 1e-3);
+        assert_eq!(analysis.slots.len(), 2);
+        assert!((analysis.power_imbalance_db(DataDirection::UL).unwrap() - 6.0206).abs() < 1e-3);
+        assert_eq!(analysis.power_imbalance_db(DataDirection::DL), None);
+    }
+}
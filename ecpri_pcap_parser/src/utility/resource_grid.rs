@@ -0,0 +1,224 @@
+use std::collections::{BTreeMap, BTreeSet};
+use ndarray::Array3;
+use num::complex::Complex;
+use crate::protocols::{DataDirection, EaxcId, EaxcIdConfig, EcpriType, ModCompParams, UPlaneSection, decompressor, RE_PER_PRB};
+use crate::utility::carrier_config::CarrierConfig;
+use crate::utility::ecpri_analysis::EcpriDataVec;
+
+// Resource grid of one slot rebuilt from the U-Plane messages: symbol x subcarrier x eAxC,
+// subcarrier k = 12 * PRB + RE of the carrier. A U-Plane section carries numPrbu PRBs from
+// startPrbu (numPrbu = 0: up to the end of the carrier), every other PRB if rb = 1. Only the
+// REs of the reMask of the C-Plane section with the same sectionId are taken (all REs if
+// there is no C-Plane), reMask MSB = RE 0, and modulation compression takes the parameters
+// of its section extension 4 or 5. REs without data stay 0 and are not `filled`.
+// The grids are keyed by frameId, so captures longer than 256 frames (2.56 s) fold onto
+// the same grids.
+
+// (dataDirection, frameId, subframeId, slotId)
+pub type SlotKey = (u8, u8, u8, u8);
+
+pub struct SlotGrid {
+    pub dir: DataDirection,
+    pub frame_id: u8,
+    pub subframe_id: u8,
+    pub slot_id: u8,
+    pub eaxc_ids: Vec<EaxcId>,           // eAxC of every index of the third axis
+    pub grid: Array3<Complex<f32>>,      // symbol x subcarrier x eAxC, in LSBs of 16 bits samples
+    pub filled: Array3<bool>,            // the RE was carried by a U-Plane section
+}
+
+impl SlotGrid {
+    pub fn new(key: SlotKey, eaxc_ids: Vec<EaxcId>, num_symbols: usize, num_prb: u16) -> Self {
+        let shape = (num_symbols, num_prb as usize * RE_PER_PRB, eaxc_ids.len());
+        Self {
+            dir: DataDirection::from(key.0),
+            frame_id: key.1,
+            subframe_id: key.2,
+            slot_id: key.3,
+            eaxc_ids,
+            grid: Array3::from_elem(shape, Complex::new(0.0, 0.0)),
+            filled: Array3::from_elem(shape, false),
+        }
+    }
+
+    pub fn num_symbols(&self) -> usize {
+        self.grid.shape()[0]
+    }
+
+    pub fn num_subcarriers(&self) -> usize {
+        self.grid.shape()[1]
+    }
+
+    pub fn eaxc_index(&self, eaxc_id: &EaxcId) -> Option<usize> {
+        self.eaxc_ids.iter().position(|e| e == eaxc_id)
+    }
+
+    pub fn filled_count(&self) -> usize {
+        self.filled.iter().filter(|&&f| f).count()
+    }
+
+    // Write the PRBs of one U-Plane section at symbol `symbol` of antenna `antenna`,
+    // the PRBs which can't be decompressed (reserved udCompMeth) are left out
+    pub fn insert_section(&mut self, symbol: usize, antenna: usize, section: &UPlaneSection, re_mask: u16, mod_comp: Option<ModCompParams>) {
+        if symbol >= self.num_symbols() {
+            return;
+        }
+        let num_prb = (self.num_subcarriers() / RE_PER_PRB) as u16;
+        let hdr = &section.section_hdr;
+        let step = if hdr.rb == 1 { 2 } else { 1 };
+        let count = if hdr.num_prbc == 0 {
+            (num_prb.saturating_sub(hdr.start_prbc) as usize).div_ceil(step)
+        } else {
+            hdr.num_prbc as usize
+        };
+        for (n, prb) in section.iq_prbu.iter().take(count).enumerate() {
+            let prb_index = hdr.start_prbc as usize + n * step;
+            if prb_index >= num_prb as usize {
+                break;
+            }
+            let samples = match decompressor(&prb.ud_comp_hdr, mod_comp).and_then(|d| d.decompress_prb(prb)) {
+                Some(samples) => samples,
+                None => continue,
+            };
+            for (re, sample) in samples.iter().enumerate().take(RE_PER_PRB) {
+                if re_mask & (0x800 >> re) == 0 {
+                    continue;
+                }
+                let k = prb_index * RE_PER_PRB + re;
+                self.grid[[symbol, k, antenna]] = *sample;
+                self.filled[[symbol, k, antenna]] = true;
+            }
+        }
+    }
+}
+
+pub struct GridAssembler {
+    pub eaxc_config: EaxcIdConfig,
+    pub num_prb: u16,          // PRBs of the carrier
+    pub num_symbols: u8,       // symbols per slot
+}
+
+impl GridAssembler {
+    pub fn new(eaxc_config: EaxcIdConfig, num_prb: u16, num_symbols: u8) -> Self {
+        Self { eaxc_config, num_prb, num_symbols }
+    }
+
+    pub fn from_carrier(eaxc_config: EaxcIdConfig, carrier: &CarrierConfig) -> Self {
+        GridAssembler::new(eaxc_config, carrier.n_rb, carrier.symbols_per_slot())
+    }
+
+    pub fn assemble(&self, data: &EcpriDataVec) -> BTreeMap<SlotKey, SlotGrid> {
+        let c_plane = data.c_plane_sections(&self.eaxc_config);
+
+        let mut eaxc_ids: BTreeMap<SlotKey, BTreeSet<EaxcId>> = BTreeMap::new();
+        for d in data.0.iter() {
+            if let EcpriType::IQData(iq_data) = &d.data {
+                let key = (iq_data.dir as u8, iq_data.frame_id, iq_data.subframe_id, iq_data.slot_id);
+                eaxc_ids.entry(key).or_default().insert(d.header.eaxc_id(&self.eaxc_config));
+            }
+        }
+        let mut grids: BTreeMap<SlotKey, SlotGrid> = eaxc_ids.into_iter()
+            .map(|(key, ids)| (key, SlotGrid::new(key, ids.into_iter().collect(), self.num_symbols as usize, self.num_prb)))
+            .collect();
+
+        for d in data.0.iter() {
+            if let EcpriType::IQData(iq_data) = &d.data {
+                let key = (iq_data.dir as u8, iq_data.frame_id, iq_data.subframe_id, iq_data.slot_id);
+                let eaxc_id = d.header.eaxc_id(&self.eaxc_config);
+                let grid = grids.get_mut(&key).unwrap();
+                let antenna = grid.eaxc_index(&eaxc_id).unwrap();
+                let symbol = iq_data.start_symbol_id;
+                for section in iq_data.sections.iter() {
+                    let section_key = (eaxc_id, key.0, key.1, key.2, key.3, section.section_hdr.section_id);
+                    let (re_mask, mod_comp) = EcpriDataVec::find_c_plane_section(&c_plane, &section_key, symbol)
+                        .map_or((0xFFF, None), |c| (c.re_mask, c.mod_comp));
+                    grid.insert_section(symbol as usize, antenna, section, re_mask, mod_comp);
+                }
+            }
+        }
+        grids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::{IQPrbuData, SectionExtension, SectionHeader, UdCompHdr, UdCompMeth, _SectionType1Data};
+    use crate::utility::ecpri_analysis::EcpriData;
+    use crate::utility::test_support::{self, c_section, section_hdr, u_section};
+
+    fn prb(value: i32) -> IQPrbuData {
+        test_support::prb(UdCompHdr::new(16, UdCompMeth::NoCompression), 0, (value, -value))
+    }
+
+    // DL, frame 3, subframe 1, slot 0, section 1
+    fn u_plane(pcid: u16, symbol: u8, rb: u8, start_prbc: u16, num_prbc: u8, prbs: Vec<i32>) -> EcpriData {
+        let section_hdr = SectionHeader { rb, ..section_hdr(1, start_prbc, num_prbc) };
+        let section = u_section(section_hdr, prbs.into_iter().map(prb).collect());
+        test_support::u_plane(0, pcid, (1, 3, 1, 0), symbol, vec![section])
+    }
+
+    #[test]
+    fn grid_placement() {
+        let mut data = EcpriDataVec::new();
+        data.append(u_plane(0, 0, 0, 1, 2, vec![10, 20]));
+        data.append(u_plane(1, 2, 1, 0, 2, vec![30, 40]));
+        // numPrbu = 0: from startPrbu to the end of the carrier
+        data.append(u_plane(1, 3, 0, 2, 0, vec![50, 60]));
+        let grids = GridAssembler::new(EaxcIdConfig::default(), 4, 14).assemble(&data);
+        assert_eq!(grids.len(), 1);
+        let slot = &grids[&(1, 3, 1, 0)];
+        assert_eq!(slot.grid.shape(), &[14, 48, 2]);
+        assert_eq!(slot.grid[[0, 12, 0]], Complex::new(10.0, -10.0));
+        assert_eq!(slot.grid[[0, 35, 0]], Complex::new(20.0, -20.0));
+        assert!(!slot.filled[[0, 0, 0]] && !slot.filled[[0, 36, 0]]);
+        // every other PRB
+        assert_eq!(slot.grid[[2, 0, 1]], Complex::new(30.0, -30.0));
+        assert!(!slot.filled[[2, 12, 1]]);
+        assert_eq!(slot.grid[[2, 24, 1]], Complex::new(40.0, -40.0));
+        assert_eq!(slot.grid[[3, 47, 1]], Complex::new(60.0, -60.0));
+        assert_eq!(slot.filled_count(), 24 + 24 + 24);
+    }
+
+    // DL, frame 3, subframe 1, slot 0, section 1 of every PRB
+    fn c_plane(symbol: u8, num_symbol: u8, re_mask: u16, extensions: Vec<SectionExtension>) -> EcpriData {
+        let section = _SectionType1Data {
+            ef: if extensions.is_empty() { 0 } else { 1 },
+            extensions,
+            ..c_section(section_hdr(1, 0, 0), re_mask, num_symbol)
+        };
+        test_support::c_plane(0, 0, (1, 3, 1, 0), symbol, vec![section])
+    }
+
+    #[test]
+    fn mod_comp_params_from_c_plane() {
+        // 16QAM, every I/Q = 1, extType 4: csf = 0, modCompScaler = 0.5
+        let (_, ext4) = SectionExtension::parse(&[0x04, 0x01, 0x40, 0x00]).unwrap();
+        let mut data = EcpriDataVec::new();
+        data.append(c_plane(0, 14, 0xFFF, vec![ext4]));
+        let prbu = test_support::prb(UdCompHdr::new(2, UdCompMeth::ModulationCompression), 0, (1, 1));
+        data.append(test_support::u_plane(0, 0, (1, 3, 1, 0), 2, vec![u_section(section_hdr(1, 0, 1), vec![prbu])]));
+        let grids = GridAssembler::new(EaxcIdConfig::default(), 2, 14).assemble(&data);
+        let slot = &grids[&(1, 3, 1, 0)];
+        assert_eq!(slot.filled_count(), 12);
+        assert_eq!(slot.grid[[2, 11, 0]], Complex::new(8192.0, 8192.0));
+    }
+
+    #[test]
+    fn re_mask_from_c_plane() {
+        let mut data = EcpriDataVec::new();
+        // REs 0..3 of symbols 4..6
+        data.append(c_plane(4, 3, 0xF00, Vec::new()));
+        data.append(u_plane(0, 5, 0, 0, 1, vec![7]));
+        // outside of the symbols of the C-Plane section: every RE
+        data.append(u_plane(0, 7, 0, 0, 1, vec![8]));
+        // symbolId is 6 bits, beyond the slot
+        data.append(u_plane(0, 40, 0, 0, 1, vec![9]));
+        let grids = GridAssembler::new(EaxcIdConfig::default(), 2, 14).assemble(&data);
+        let slot = &grids[&(1, 3, 1, 0)];
+        assert_eq!(slot.filled_count(), 4 + 12);
+        assert!(slot.filled[[5, 3, 0]] && !slot.filled[[5, 4, 0]]);
+        assert_eq!(slot.grid[[5, 0, 0]], Complex::new(7.0, -7.0));
+        assert!(slot.filled[[7, 11, 0]]);
+    }
+}
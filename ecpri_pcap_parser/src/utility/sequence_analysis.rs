@@ -0,0 +1,320 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::io::{self, Write};
+use chrono::{DateTime, Duration, Utc};
+use crate::protocols::{CommonHeader, EaxcId, EaxcIdConfig};
+use crate::utility::ecpri_analysis::EcpriDataVec;
+
+// Continuity of the ecpriSeqid SequenceID per fronthaul stream, as the RTP stream analysis:
+// every stream (eAxC + plane) counts its own 8 bits SequenceID, a jump forward is counted
+// as lost packets, a SequenceID behind the expected one as a reorder (which recovers one
+// lost packet) or as a duplicate if it was already received.
+
+// SequenceIDs behind the highest one still remembered for duplicate/reorder detection
+const HISTORY_SIZE: usize = 128;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct StreamKey {
+    pub eaxc_id: EaxcId,
+    pub message_type: u8,   // 0: U-Plane (ecpriPcid), 2: C-Plane (ecpriRtcid)
+    pub pcid: u16,
+}
+
+impl StreamKey {
+    pub fn plane(&self) -> &'static str {
+        match self.message_type {
+            0 => "U",
+            2 => "C",
+            _ => "?",
+        }
+    }
+}
+
+impl fmt::Display for StreamKey {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        write!(w, "{:04X}, {}, {}", self.pcid, self.eaxc_id, self.plane())
+    }
+}
+
+impl fmt::Debug for StreamKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SeqEventKind {
+    Gap(u8),       // number of SequenceIDs skipped
+    Duplicate,
+    Reorder,
+    Wraparound,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SeqEvent {
+    pub timestamp: DateTime<Utc>,
+    pub stream: StreamKey,
+    pub kind: SeqEventKind,
+    pub sequence_id: u8,
+    pub expected: u8,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SeqCounters {
+    pub packets: u64,
+    pub lost: i64,       // reorders of missing packets make it go back down
+    pub duplicates: u64,
+    pub reordered: u64,
+    pub wraparounds: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct StreamStats {
+    pub first_timestamp: DateTime<Utc>,
+    pub last_timestamp: DateTime<Utc>,
+    pub counters: SeqCounters,
+    pub max_gap: u8,
+    highest: u8,               // highest SequenceID received, modulo 256
+    history: VecDeque<u8>,     // the last received SequenceIDs
+}
+
+impl StreamStats {
+    fn new(timestamp: DateTime<Utc>, sequence_id: u8) -> Self {
+        Self {
+            first_timestamp: timestamp,
+            last_timestamp: timestamp,
+            counters: SeqCounters { packets: 1, ..SeqCounters::default() },
+            max_gap: 0,
+            highest: sequence_id,
+            history: vec![sequence_id].into(),
+        }
+    }
+
+    // Account one packet, return what happened to the sequence
+    fn update(&mut self, timestamp: DateTime<Utc>, sequence_id: u8) -> (u8, Vec<SeqEventKind>) {
+        let expected = self.highest.wrapping_add(1);
+        let delta = sequence_id.wrapping_sub(expected);
+        let mut kinds = Vec::new();
+        self.last_timestamp = timestamp;
+        self.counters.packets += 1;
+        if self.history.contains(&sequence_id) {
+            self.counters.duplicates += 1;
+            kinds.push(SeqEventKind::Duplicate);
+            return (expected, kinds);
+        }
+        if (delta as usize) < 256 - HISTORY_SIZE {
+            // in order, or ahead of the expected one
+            if delta > 0 {
+                self.counters.lost += delta as i64;
+                self.max_gap = self.max_gap.max(delta);
+                kinds.push(SeqEventKind::Gap(delta));
+            }
+            if sequence_id < self.highest {
+                self.counters.wraparounds += 1;
+                kinds.push(SeqEventKind::Wraparound);
+            }
+            self.highest = sequence_id;
+        } else {
+            // behind the expected one, a packet counted as lost arrived late
+            self.counters.reordered += 1;
+            self.counters.lost -= 1;
+            kinds.push(SeqEventKind::Reorder);
+        }
+        self.history.push_back(sequence_id);
+        if self.history.len() > HISTORY_SIZE {
+            self.history.pop_front();
+        }
+        (expected, kinds)
+    }
+}
+
+pub struct SequenceAnalysis {
+    pub eaxc_config: EaxcIdConfig,
+    pub bin: Duration,                                          // time series resolution
+    pub streams: BTreeMap<StreamKey, StreamStats>,
+    pub events: Vec<SeqEvent>,
+    pub time_series: BTreeMap<(DateTime<Utc>, StreamKey), SeqCounters>,  // (bin start, stream)
+}
+
+impl SequenceAnalysis {
+    pub fn new(eaxc_config: EaxcIdConfig, bin: Duration) -> Self {
+        Self {
+            eaxc_config,
+            bin,
+            streams: BTreeMap::new(),
+            events: Vec::new(),
+            time_series: BTreeMap::new(),
+        }
+    }
+
+    pub fn from_data(data: &EcpriDataVec, eaxc_config: EaxcIdConfig, bin: Duration) -> Self {
+        let mut analysis = SequenceAnalysis::new(eaxc_config, bin);
+        for v in data.0.iter() {
+            analysis.push(v.timestamp, &v.header);
+        }
+        analysis
+    }
+
+    pub fn push(&mut self, timestamp: DateTime<Utc>, header: &CommonHeader) {
+        let stream = StreamKey {
+            eaxc_id: header.eaxc_id(&self.eaxc_config),
+            message_type: header.message_type,
+            pcid: header.pcid,
+        };
+        let sequence_id = header.seq_id().sequence_id;
+        let bin_nanos = self.bin.num_nanoseconds().unwrap_or(1).max(1);
+        let nanos = timestamp.timestamp_nanos();
+        let bin_start = timestamp - Duration::nanoseconds(nanos.rem_euclid(bin_nanos));
+        let counters = self.time_series.entry((bin_start, stream)).or_default();
+        counters.packets += 1;
+
+        let stats = match self.streams.get_mut(&stream) {
+            Some(stats) => stats,
+            None => {
+                self.streams.insert(stream, StreamStats::new(timestamp, sequence_id));
+                return;
+            }
+        };
+        let (expected, kinds) = stats.update(timestamp, sequence_id);
+        for kind in kinds {
+            match kind {
+                SeqEventKind::Gap(lost) => counters.lost += lost as i64,
+                SeqEventKind::Duplicate => counters.duplicates += 1,
+                SeqEventKind::Reorder => {
+                    counters.reordered += 1;
+                    counters.lost -= 1;
+                },
+                SeqEventKind::Wraparound => counters.wraparounds += 1,
+            }
+            self.events.push(SeqEvent { timestamp, stream, kind, sequence_id, expected });
+        }
+    }
+
+    pub fn write_summary_csv<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "pcid, eaxc, plane, packets, lost, duplicates, reordered, wraparounds, max_gap, first, last")?;
+        for (stream, stats) in self.streams.iter() {
+            let c = &stats.counters;
+            writeln!(
+                w,
+                "{}, {}, {}, {}, {}, {}, {}, {}, {}",
+                stream, c.packets, c.lost, c.duplicates, c.reordered, c.wraparounds,
+                stats.max_gap, stats.first_timestamp, stats.last_timestamp
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn write_time_series_csv<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "bin_start, pcid, eaxc, plane, packets, lost, duplicates, reordered, wraparounds")?;
+        for ((bin_start, stream), c) in self.time_series.iter() {
+            writeln!(
+                w,
+                "{}, {}, {}, {}, {}, {}, {}",
+                bin_start, stream, c.packets, c.lost, c.duplicates, c.reordered, c.wraparounds
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SequenceAnalysis {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(w, "{:>6} {:>18} {:>5} {:>10} {:>8} {:>10} {:>9} {:>5} {:>7}",
+                 "pcid", "eAxC", "plane", "packets", "lost", "duplicate", "reorder", "wrap", "max_gap")?;
+        for (stream, stats) in self.streams.iter() {
+            let c = &stats.counters;
+            writeln!(
+                w,
+                "{:>6} {:>18} {:>5} {:>10} {:>8} {:>10} {:>9} {:>5} {:>7}",
+                format!("{:04X}", stream.pcid),
+                stream.eaxc_id.to_string(),
+                stream.plane(),
+                c.packets, c.lost, c.duplicates, c.reordered, c.wraparounds, stats.max_gap
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::EcpriSeqId;
+    use crate::utility::test_support::{self, at};
+
+    fn header(pcid: u16, message_type: u8, sequence_id: u8) -> CommonHeader {
+        CommonHeader {
+            payload_size: 4,
+            seqid: u16::from(EcpriSeqId { sequence_id, e_bit: 1, subsequence_id: 0 }),
+            ..test_support::header(pcid, message_type)
+        }
+    }
+
+    fn analyse(sequence: &[u8]) -> SequenceAnalysis {
+        let mut analysis = SequenceAnalysis::new(EaxcIdConfig::default(), Duration::milliseconds(1));
+        for (i, &seq) in sequence.iter().enumerate() {
+            analysis.push(at(i as i64 * 100), &header(1, 0, seq));
+        }
+        analysis
+    }
+
+    #[test]
+    fn continuous_with_wraparound() {
+        let sequence: Vec<u8> = (250..=255).chain(0..10).collect();
+        let analysis = analyse(&sequence);
+        let stats = analysis.streams.values().next().unwrap();
+        assert_eq!(stats.counters, SeqCounters { packets: 16, lost: 0, duplicates: 0, reordered: 0, wraparounds: 1 });
+        assert_eq!(analysis.events.len(), 1);
+        assert_eq!(analysis.events[0].kind, SeqEventKind::Wraparound);
+    }
+
+    #[test]
+    fn gaps_duplicates_and_reorders() {
+        let analysis = analyse(&[1, 2, 5, 3, 6, 6, 8, 7, 100, 200, 10]);
+        let stats = analysis.streams.values().next().unwrap();
+        // 3, 4 and 7 missing, then 3 and 7 late: 1 lost; then jumps of 91, 99 and 65 (wrapping)
+        assert_eq!(stats.counters.packets, 11);
+        assert_eq!(stats.counters.duplicates, 1);
+        assert_eq!(stats.counters.reordered, 2);
+        assert_eq!(stats.counters.wraparounds, 1);
+        assert_eq!(stats.counters.lost, 1 + 91 + 99 + 65);
+        assert_eq!(stats.max_gap, 99);
+        assert_eq!(analysis.events[0], SeqEvent {
+            timestamp: at(200),
+            stream: *analysis.streams.keys().next().unwrap(),
+            kind: SeqEventKind::Gap(2),
+            sequence_id: 5,
+            expected: 3,
+        });
+    }
+
+    #[test]
+    fn streams_and_time_series() {
+        let mut analysis = SequenceAnalysis::new(EaxcIdConfig::default(), Duration::milliseconds(1));
+        for i in 0..20u8 {
+            // U-Plane and C-Plane of the same eAxC are separate streams, the C-Plane loses every 5th
+            analysis.push(at(i as i64 * 100), &header(0x0101, 0, i));
+            if i % 5 != 4 {
+                analysis.push(at(i as i64 * 100), &header(0x0101, 2, i));
+            }
+        }
+        assert_eq!(analysis.streams.len(), 2);
+        let c_plane = analysis.streams.values().nth(1).unwrap();
+        assert_eq!(c_plane.counters.lost, 3);
+        assert_eq!(analysis.time_series.len(), 4);
+        let lost_per_bin: Vec<i64> = analysis.time_series.iter()
+            .filter(|((_, s), _)| s.message_type == 2)
+            .map(|(_, c)| c.lost)
+            .collect();
+        assert_eq!(lost_per_bin, vec![1, 2]);
+        let mut csv = Vec::new();
+        analysis.write_summary_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), 3);
+        // the table and the CSV label the planes alike
+        let table = analysis.to_string();
+        assert!(csv.lines().nth(2).unwrap().contains(", C, ") && table.lines().nth(2).unwrap().contains("     C "));
+        analysis.push(at(0), &header(0x0101, 5, 0));
+        assert!(analysis.to_string().lines().nth(3).unwrap().contains("     ? "));
+    }
+}
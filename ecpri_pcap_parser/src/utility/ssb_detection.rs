@@ -0,0 +1,333 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io::{self, Write};
+use ndarray::s;
+use num::complex::Complex;
+use crate::protocols::{DataDirection, EaxcId};
+use crate::utility::power_measurement::power_dbfs;
+use crate::utility::resource_grid::{SlotGrid, SlotKey};
+
+// SS/PBCH block search on the DL grids, TS 38.211 7.4.2 and 7.4.3.
+// The SSB is 4 symbols x 240 subcarriers, relative to its first subcarrier k and symbol l:
+//   l + 0: PSS at k + 56..182
+//   l + 1: PBCH at k + 0..239
+//   l + 2: SSS at k + 56..182, PBCH at k + 0..47 and k + 192..239
+//   l + 3: PBCH at k + 0..239
+// The PBCH DMRS is every 4th RE of the PBCH from k + v, v = N_ID^cell mod 4.
+// The REs of the PSS symbol around the PSS are set to 0, so only the subcarrier offsets with
+// the 127 PSS REs filled and less than a tenth of their energy around them are candidates.
+// There the 3 PSS are correlated, normalised by the energy of the REs (1: the same sequence
+// up to a complex gain). Above the threshold, the 336 SSS of that N_ID^(2) give N_ID^(1),
+// PCI = 3 N_ID^(1) + N_ID^(2), then the 8 PBCH DMRS sequences give i_SSB_bar: the 3 LSBs of
+// the SSB index (i_SSB + 4 n_hf if L_max = 4).
+
+pub const SSB_SUBCARRIERS: usize = 240;
+pub const SSB_SYMBOLS: usize = 4;
+pub const SEQUENCE_LENGTH: usize = 127;
+pub const PSS_SSS_START: usize = 56;
+pub const DETECTION_THRESHOLD: f64 = 0.8;
+
+// m-sequence of TS 38.211 7.4.2.2.1 / 7.4.2.3.1, x(i + 7) = x(i + taps) + x(i) mod 2
+fn m_sequence(init: [u8; 7], taps: &[usize]) -> [u8; SEQUENCE_LENGTH] {
+    let mut x = [0u8; SEQUENCE_LENGTH + 7];
+    x[..7].copy_from_slice(&init);
+    for i in 0..SEQUENCE_LENGTH {
+        x[i + 7] = (taps.iter().map(|t| x[i + t]).sum::<u8>() + x[i]) % 2;
+    }
+    let mut out = [0u8; SEQUENCE_LENGTH];
+    out.copy_from_slice(&x[..SEQUENCE_LENGTH]);
+    out
+}
+
+// d_PSS(n) = 1 - 2 x((n + 43 N_ID^(2)) mod 127)
+pub fn pss(n_id_2: u8) -> Vec<f32> {
+    let x = m_sequence([0, 1, 1, 0, 1, 1, 1], &[4]);
+    (0..SEQUENCE_LENGTH).map(|n| 1.0 - 2.0 * x[(n + 43 * n_id_2 as usize) % SEQUENCE_LENGTH] as f32).collect()
+}
+
+// d_SSS(n) = [1 - 2 x0((n + m0) mod 127)] [1 - 2 x1((n + m1) mod 127)]
+pub fn sss(n_id_1: u16, n_id_2: u8) -> Vec<f32> {
+    let x0 = m_sequence([1, 0, 0, 0, 0, 0, 0], &[4]);
+    let x1 = m_sequence([1, 0, 0, 0, 0, 0, 0], &[1]);
+    let m0 = 15 * (n_id_1 as usize / 112) + 5 * n_id_2 as usize;
+    let m1 = n_id_1 as usize % 112;
+    (0..SEQUENCE_LENGTH)
+        .map(|n| (1.0 - 2.0 * x0[(n + m0) % SEQUENCE_LENGTH] as f32) * (1.0 - 2.0 * x1[(n + m1) % SEQUENCE_LENGTH] as f32))
+        .collect()
+}
+
+// Pseudo-random sequence c(n) of TS 38.211 5.2.1 (length-31 Gold sequence, N_c = 1600)
+pub fn gold_sequence(c_init: u32, len: usize) -> Vec<u8> {
+    const NC: usize = 1600;
+    let total = NC + len;
+    let mut x1 = vec![0u8; total + 31];
+    let mut x2 = vec![0u8; total + 31];
+    x1[0] = 1;
+    for (i, x) in x2.iter_mut().take(31).enumerate() {
+        *x = ((c_init >> i) & 1) as u8;
+    }
+    for n in 0..total {
+        x1[n + 31] = (x1[n + 3] + x1[n]) % 2;
+        x2[n + 31] = (x2[n + 3] + x2[n + 2] + x2[n + 1] + x2[n]) % 2;
+    }
+    (0..len).map(|n| x1[n + NC] ^ x2[n + NC]).collect()
+}
+
+// PBCH DMRS, TS 38.211 7.4.1.4.1:
+//   c_init = 2^11 (i_SSB_bar + 1) (floor(N_ID / 4) + 1) + 2^6 (i_SSB_bar + 1) + (N_ID mod 4)
+pub fn pbch_dmrs(pci: u16, i_ssb_bar: u8) -> Vec<Complex<f32>> {
+    let i = i_ssb_bar as u32 + 1;
+    let c_init = ((i * (pci as u32 / 4 + 1)) << 11) + (i << 6) + pci as u32 % 4;
+    let c = gold_sequence(c_init, 2 * 144);
+    let a = std::f32::consts::FRAC_1_SQRT_2;
+    (0..144).map(|m| Complex::new(a * (1.0 - 2.0 * c[2 * m] as f32), a * (1.0 - 2.0 * c[2 * m + 1] as f32))).collect()
+}
+
+// (symbol, subcarrier) in the SSB of the PBCH REs, first k then l, DMRS or data
+fn pbch_res(pci: u16, dmrs: bool) -> Vec<(usize, usize)> {
+    let v = pci as usize % 4;
+    (1..SSB_SYMBOLS)
+        .flat_map(|l| (0..SSB_SUBCARRIERS).map(move |k| (l, k)))
+        .filter(|&(l, k)| l != 2 || !(48..192).contains(&k))
+        .filter(|&(_, k)| (k % 4 == v) == dmrs)
+        .collect()
+}
+
+pub fn pbch_dmrs_res(pci: u16) -> Vec<(usize, usize)> {
+    pbch_res(pci, true)
+}
+
+pub fn pbch_data_res(pci: u16) -> Vec<(usize, usize)> {
+    pbch_res(pci, false)
+}
+
+// |sum conj(ref) * y| / sqrt(sum |ref|^2 * sum |y|^2)
+fn correlation<'a>(reference: impl Iterator<Item = Complex<f32>>, received: impl Iterator<Item = &'a Complex<f32>>) -> f64 {
+    let (mut corr, mut ref_energy, mut energy) = (Complex::new(0.0f64, 0.0), 0.0, 0.0);
+    for (r, y) in reference.zip(received) {
+        let (r, y) = (Complex::new(r.re as f64, r.im as f64), Complex::new(y.re as f64, y.im as f64));
+        corr += r.conj() * y;
+        ref_energy += r.norm_sqr();
+        energy += y.norm_sqr();
+    }
+    if energy == 0.0 { 0.0 } else { corr.norm() / (ref_energy * energy).sqrt() }
+}
+
+fn mean_power(res: &[Complex<f32>]) -> f64 {
+    power_dbfs(res.iter().map(|x| x.norm_sqr() as f64).sum::<f64>() / res.len() as f64)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SsbDetection {
+    pub slot: SlotKey,
+    pub eaxc_id: EaxcId,
+    pub antenna: usize,
+    pub symbol: u8,             // PSS symbol in the slot
+    pub subcarrier: usize,      // subcarrier 0 of the SSB in the grid
+    pub n_id_1: u16,
+    pub n_id_2: u8,
+    pub ssb_index: u8,          // i_SSB_bar
+    pub pss_metric: f64,
+    pub sss_metric: f64,
+    pub dmrs_metric: f64,
+    pub pss_dbfs: f64,
+    pub sss_dbfs: f64,
+    pub pbch_dbfs: f64,         // data and DMRS
+}
+
+impl SsbDetection {
+    pub fn pci(&self) -> u16 {
+        3 * self.n_id_1 + self.n_id_2 as u16
+    }
+
+    // REs of the SSB at the (symbol, subcarrier) offsets
+    pub fn res(&self, grid: &SlotGrid, positions: &[(usize, usize)]) -> Vec<Complex<f32>> {
+        positions.iter()
+            .map(|&(l, k)| grid.grid[[self.symbol as usize + l, self.subcarrier + k, self.antenna]])
+            .collect()
+    }
+}
+
+// SSBs of one antenna of the grid
+pub fn search_antenna(grid: &SlotGrid, antenna: usize, threshold: f64) -> Vec<SsbDetection> {
+    let mut detections = Vec::new();
+    if grid.dir != DataDirection::DL || grid.num_symbols() < SSB_SYMBOLS || grid.num_subcarriers() < SSB_SUBCARRIERS {
+        return detections;
+    }
+    let pss: Vec<Vec<f32>> = (0..3).map(pss).collect();
+    for l in 0..=grid.num_symbols() - SSB_SYMBOLS {
+        let symbol = grid.grid.slice(s![l, .., antenna]);
+        let filled = grid.filled.slice(s![l, .., antenna]);
+        if filled.iter().filter(|&&f| f).count() < SEQUENCE_LENGTH {
+            continue;
+        }
+        // prefix sums of the energy and of the filled REs
+        let (mut energy, mut count) = (vec![0.0f64], vec![0usize]);
+        for (x, f) in symbol.iter().zip(filled.iter()) {
+            energy.push(energy.last().unwrap() + x.norm_sqr() as f64);
+            count.push(count.last().unwrap() + *f as usize);
+        }
+        for k in 0..=grid.num_subcarriers() - SSB_SUBCARRIERS {
+            let range = k + PSS_SSS_START..k + PSS_SSS_START + SEQUENCE_LENGTH;
+            let pss_energy = energy[range.end] - energy[range.start];
+            let guard_energy = energy[k + SSB_SUBCARRIERS] - energy[k] - pss_energy;
+            if count[range.end] - count[range.start] < SEQUENCE_LENGTH || pss_energy == 0.0 || guard_energy > 0.1 * pss_energy {
+                continue;
+            }
+            let res = symbol.slice(s![range]);
+            let (n_id_2, pss_metric) = pss.iter().enumerate()
+                .map(|(n, d)| (n as u8, correlation(d.iter().map(|&x| Complex::new(x, 0.0)), res.iter())))
+                .fold((0, 0.0), |best, c| if c.1 > best.1 { c } else { best });
+            if pss_metric < threshold {
+                continue;
+            }
+
+            let sss_res: Vec<Complex<f32>> = grid.grid
+                .slice(s![l + 2, k + PSS_SSS_START..k + PSS_SSS_START + SEQUENCE_LENGTH, antenna])
+                .to_vec();
+            let (n_id_1, sss_metric) = (0..336u16)
+                .map(|n| (n, correlation(sss(n, n_id_2).into_iter().map(|x| Complex::new(x, 0.0)), sss_res.iter())))
+                .fold((0, 0.0), |best, c| if c.1 > best.1 { c } else { best });
+            if sss_metric < threshold {
+                continue;
+            }
+
+            let mut detection = SsbDetection {
+                slot: (grid.dir as u8, grid.frame_id, grid.subframe_id, grid.slot_id),
+                eaxc_id: grid.eaxc_ids[antenna],
+                antenna,
+                symbol: l as u8,
+                subcarrier: k,
+                n_id_1,
+                n_id_2,
+                ssb_index: 0,
+                pss_metric,
+                sss_metric,
+                dmrs_metric: 0.0,
+                pss_dbfs: mean_power(&res.to_vec()),
+                sss_dbfs: mean_power(&sss_res),
+                pbch_dbfs: 0.0,
+            };
+            let pci = detection.pci();
+            let dmrs_res = detection.res(grid, &pbch_dmrs_res(pci));
+            let (ssb_index, dmrs_metric) = (0..8u8)
+                .map(|i| (i, correlation(pbch_dmrs(pci, i).into_iter(), dmrs_res.iter())))
+                .fold((0, 0.0), |best, c| if c.1 > best.1 { c } else { best });
+            let mut pbch = dmrs_res;
+            pbch.extend(detection.res(grid, &pbch_data_res(pci)));
+            detection.ssb_index = ssb_index;
+            detection.dmrs_metric = dmrs_metric;
+            detection.pbch_dbfs = mean_power(&pbch);
+            detections.push(detection);
+        }
+    }
+    detections
+}
+
+pub fn search_grid(grid: &SlotGrid, threshold: f64) -> Vec<SsbDetection> {
+    (0..grid.eaxc_ids.len()).flat_map(|antenna| search_antenna(grid, antenna, threshold)).collect()
+}
+
+#[derive(Default)]
+pub struct SsbSearch {
+    pub detections: Vec<SsbDetection>,
+}
+
+impl SsbSearch {
+    pub fn from_grids(grids: &BTreeMap<SlotKey, SlotGrid>) -> Self {
+        Self {
+            detections: grids.values().flat_map(|grid| search_grid(grid, DETECTION_THRESHOLD)).collect(),
+        }
+    }
+
+    pub fn pcis(&self) -> BTreeSet<u16> {
+        self.detections.iter().map(|d| d.pci()).collect()
+    }
+
+    pub fn write_csv<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "frame_id, subframe_id, slot_id, eaxc, symbol_id, subcarrier, pci, n_id_1, n_id_2, ssb_index, pss_metric, sss_metric, dmrs_metric, pss_dbfs, sss_dbfs, pbch_dbfs")?;
+        for d in self.detections.iter() {
+            writeln!(w, "{}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {:.3}, {:.3}, {:.3}, {:.2}, {:.2}, {:.2}",
+                     d.slot.1, d.slot.2, d.slot.3, d.eaxc_id, d.symbol, d.subcarrier, d.pci(), d.n_id_1, d.n_id_2,
+                     d.ssb_index, d.pss_metric, d.sss_metric, d.dmrs_metric, d.pss_dbfs, d.sss_dbfs, d.pbch_dbfs)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SsbSearch {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(w, "SSBs found: {}, PCIs: {:?}", self.detections.len(), self.pcis())?;
+        let mut per_index: BTreeMap<(u16, u8, u8, usize), usize> = BTreeMap::new();
+        for d in self.detections.iter() {
+            *per_index.entry((d.pci(), d.ssb_index, d.symbol, d.subcarrier)).or_default() += 1;
+        }
+        writeln!(w, "{:>5} {:>10} {:>7} {:>11} {:>6}", "PCI", "SSB index", "symbol", "subcarrier", "count")?;
+        for ((pci, ssb_index, symbol, subcarrier), count) in per_index.iter() {
+            writeln!(w, "{:>5} {:>10} {:>7} {:>11} {:>6}", pci, ssb_index, symbol, subcarrier, count)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::protocols::EaxcIdConfig;
+
+    // SSB of the PCI and i_SSB_bar with a complex gain, PBCH data from the bits (QPSK)
+    pub(crate) fn place_ssb(grid: &mut SlotGrid, l: usize, k: usize, pci: u16, i_ssb_bar: u8, gain: Complex<f32>, bits: &[u8]) {
+        let (n_id_1, n_id_2) = (pci / 3, (pci % 3) as u8);
+        let mut put = |ll: usize, kk: usize, x: Complex<f32>| {
+            grid.grid[[l + ll, k + kk, 0]] = gain * x;
+            grid.filled[[l + ll, k + kk, 0]] = true;
+        };
+        for (n, (p, s)) in pss(n_id_2).into_iter().zip(sss(n_id_1, n_id_2)).enumerate() {
+            put(0, PSS_SSS_START + n, Complex::new(p, 0.0));
+            put(2, PSS_SSS_START + n, Complex::new(s, 0.0));
+        }
+        for ((ll, kk), r) in pbch_dmrs_res(pci).into_iter().zip(pbch_dmrs(pci, i_ssb_bar)) {
+            put(ll, kk, r);
+        }
+        let a = std::f32::consts::FRAC_1_SQRT_2;
+        for (n, (ll, kk)) in pbch_data_res(pci).into_iter().enumerate() {
+            let (b0, b1) = (bits[(2 * n) % bits.len()], bits[(2 * n + 1) % bits.len()]);
+            put(ll, kk, Complex::new(a * (1.0 - 2.0 * b0 as f32), a * (1.0 - 2.0 * b1 as f32)));
+        }
+    }
+
+    #[test]
+    fn sequences() {
+        // x(0..6) = 0 1 1 0 1 1 1, x(7) = 1
+        assert_eq!(pss(0)[..8], [1.0, -1.0, -1.0, 1.0, -1.0, -1.0, -1.0, -1.0]);
+        assert_eq!(sss(0, 0)[..8], [1.0; 8]);
+        assert_eq!(pbch_dmrs_res(1).len(), 144);
+        assert_eq!(pbch_data_res(1).len(), 432);
+        assert!(pbch_dmrs_res(2).iter().all(|&(_, k)| k % 4 == 2));
+        // cyclic shifts of an m-sequence
+        let (a, b) = (pss(0), pss(1));
+        assert_eq!(a.iter().zip(b.iter()).map(|(x, y)| x * y).sum::<f32>(), -1.0);
+    }
+
+    #[test]
+    fn detect_pci_and_ssb_index() {
+        let mut grid = SlotGrid::new((1, 4, 0, 0), vec![EaxcIdConfig::default().decode(0)], 14, 51);
+        let gain = Complex::new(2000.0, -1500.0);
+        place_ssb(&mut grid, 2, 100, 503, 5, gain, &[1, 0, 0, 1, 1]);
+        place_ssb(&mut grid, 8, 300, 17, 2, gain, &[0, 1]);
+        let detections = search_grid(&grid, DETECTION_THRESHOLD);
+        assert_eq!(detections.len(), 2);
+        let d = &detections[0];
+        assert_eq!((d.symbol, d.subcarrier, d.pci(), d.ssb_index), (2, 100, 503, 5));
+        assert!(d.pss_metric > 0.999 && d.sss_metric > 0.999 && d.dmrs_metric > 0.999);
+        // |gain| = 2500: 20 log10(2500 / 32768)
+        assert!((d.pss_dbfs - -22.35).abs() < 0.01);
+        assert!((d.pbch_dbfs - d.pss_dbfs).abs() < 0.01);
+        let d = &detections[1];
+        assert_eq!((d.symbol, d.subcarrier, d.n_id_1, d.n_id_2, d.ssb_index), (8, 300, 5, 2, 2));
+
+        // UL grids aren't searched
+        grid.dir = DataDirection::UL;
+        assert!(search_grid(&grid, DETECTION_THRESHOLD).is_empty());
+    }
+}
@@ -0,0 +1,162 @@
+use std::fmt;
+
+// PDSCH transport block size, TS 38.214 5.1.3 (port of five_g_nr_tbs_calc_pdsch.py).
+//   N'_RE = 12 * N_symb_sh - N_DMRS_PRB - N_oh_PRB
+//   N_RE = min(156, N'_RE) * n_PRB
+//   N_info = N_RE * R * Qm * v
+// N_info <= 3824: quantized to a multiple of 2^n and looked up in Table 5.1.3.2-1,
+// otherwise quantized and rounded up to a whole number of code blocks (LDPC base graph 1
+// above 8424 bits, or whatever the size for R <= 1/4, base graph 2).
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum McsTable {
+    Qam64,          // Table 5.1.3.1-1
+    Qam256,         // Table 5.1.3.1-2
+    Qam64LowSe,     // Table 5.1.3.1-3
+}
+
+pub const MCS_TABLES: [McsTable; 3] = [McsTable::Qam64, McsTable::Qam256, McsTable::Qam64LowSe];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct McsEntry {
+    pub qm: u8,                      // modulation order
+    pub code_rate_x1024: f64,        // target code rate R x 1024
+    pub spectral_efficiency: f64,
+}
+
+impl McsEntry {
+    pub fn code_rate(&self) -> f64 {
+        self.code_rate_x1024 / 1024.0
+    }
+}
+
+// (Qm, R x 1024, spectral efficiency), I_MCS 29..31 are reserved (retransmissions)
+const MCS_TABLE_1: [(u8, f64, f64); 29] = [
+    (2, 120.0, 0.2344), (2, 157.0, 0.3066), (2, 193.0, 0.3770), (2, 251.0, 0.4902), (2, 308.0, 0.6016),
+    (2, 379.0, 0.7402), (2, 449.0, 0.8770), (2, 526.0, 1.0273), (2, 602.0, 1.1758), (2, 679.0, 1.3262),
+    (4, 340.0, 1.3281), (4, 378.0, 1.4766), (4, 434.0, 1.6953), (4, 490.0, 1.9141), (4, 553.0, 2.1602),
+    (4, 616.0, 2.4063), (4, 658.0, 2.5703), (6, 438.0, 2.5664), (6, 466.0, 2.7305), (6, 517.0, 3.0293),
+    (6, 567.0, 3.3223), (6, 616.0, 3.6094), (6, 666.0, 3.9023), (6, 719.0, 4.2129), (6, 772.0, 4.5234),
+    (6, 822.0, 4.8164), (6, 873.0, 5.1152), (6, 910.0, 5.3320), (6, 948.0, 5.5547),
+];
+
+// I_MCS 28..31 are reserved
+const MCS_TABLE_2: [(u8, f64, f64); 28] = [
+    (2, 120.0, 0.2344), (2, 193.0, 0.3770), (2, 308.0, 0.6016), (2, 449.0, 0.8770), (2, 602.0, 1.1758),
+    (4, 378.0, 1.4766), (4, 434.0, 1.6953), (4, 490.0, 1.9141), (4, 553.0, 2.1602), (4, 616.0, 2.4063),
+    (4, 658.0, 2.5703), (6, 466.0, 2.7305), (6, 517.0, 3.0293), (6, 567.0, 3.3223), (6, 616.0, 3.6094),
+    (6, 666.0, 3.9023), (6, 719.0, 4.2129), (6, 772.0, 4.5234), (6, 822.0, 4.8164), (6, 873.0, 5.1152),
+    (8, 682.5, 5.3320), (8, 711.0, 5.5547), (8, 754.0, 5.8906), (8, 797.0, 6.2266), (8, 841.0, 6.5703),
+    (8, 885.0, 6.9141), (8, 916.5, 7.1602), (8, 948.0, 7.4063),
+];
+
+// I_MCS 29..31 are reserved
+const MCS_TABLE_3: [(u8, f64, f64); 29] = [
+    (2, 30.0, 0.0586), (2, 40.0, 0.0781), (2, 50.0, 0.0977), (2, 64.0, 0.1250), (2, 78.0, 0.1523),
+    (2, 99.0, 0.1934), (2, 120.0, 0.2344), (2, 157.0, 0.3066), (2, 193.0, 0.3770), (2, 251.0, 0.4902),
+    (2, 308.0, 0.6016), (2, 379.0, 0.7402), (2, 449.0, 0.8770), (2, 526.0, 1.0273), (2, 602.0, 1.1758),
+    (4, 340.0, 1.3281), (4, 378.0, 1.4766), (4, 434.0, 1.6953), (4, 490.0, 1.9141), (4, 553.0, 2.1602),
+    (4, 616.0, 2.4063), (6, 438.0, 2.5664), (6, 466.0, 2.7305), (6, 517.0, 3.0293), (6, 567.0, 3.3223),
+    (6, 616.0, 3.6094), (6, 666.0, 3.9023), (6, 719.0, 4.2129), (6, 772.0, 4.5234),
+];
+
+// Table 5.1.3.2-1: TBS for N_info <= 3824
+const TBS_TABLE: [u32; 93] = [
+    24, 32, 40, 48, 56, 64, 72, 80, 88, 96, 104, 112, 120, 128, 136, 144, 152, 160, 168, 176,
+    184, 192, 208, 224, 240, 256, 272, 288, 304, 320, 336, 352, 368, 384, 408, 432, 456, 480, 504, 528,
+    552, 576, 608, 640, 672, 704, 736, 768, 808, 848, 888, 928, 984, 1032, 1064, 1128, 1160, 1192, 1224, 1256,
+    1288, 1320, 1352, 1416, 1480, 1544, 1608, 1672, 1736, 1800, 1864, 1928, 2024, 2088, 2152, 2216, 2280, 2408, 2472, 2536,
+    2600, 2664, 2728, 2792, 2856, 2976, 3104, 3240, 3368, 3496, 3624, 3752, 3824,
+];
+
+impl McsTable {
+    fn rows(&self) -> &'static [(u8, f64, f64)] {
+        match self {
+            McsTable::Qam64 => &MCS_TABLE_1,
+            McsTable::Qam256 => &MCS_TABLE_2,
+            McsTable::Qam64LowSe => &MCS_TABLE_3,
+        }
+    }
+
+    // None for the reserved indexes
+    pub fn entry(&self, i_mcs: u8) -> Option<McsEntry> {
+        self.rows().get(i_mcs as usize).map(|&(qm, code_rate_x1024, spectral_efficiency)| McsEntry {
+            qm,
+            code_rate_x1024,
+            spectral_efficiency,
+        })
+    }
+
+    // (I_MCS, entry) of every index that isn't reserved
+    pub fn entries(&self) -> Vec<(u8, McsEntry)> {
+        (0..self.rows().len() as u8).filter_map(|i| self.entry(i).map(|e| (i, e))).collect()
+    }
+}
+
+impl fmt::Display for McsTable {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            McsTable::Qam64 => "qam64",
+            McsTable::Qam256 => "qam256",
+            McsTable::Qam64LowSe => "qam64LowSE",
+        };
+        write!(w, "{}", name)
+    }
+}
+
+// REs for the PDSCH, n_dmrs_prb: DMRS REs per PRB incl. the CDM groups without data,
+// n_oh_prb: xOverhead (0, 6, 12 or 18)
+pub fn n_re(n_symb_sh: u16, n_dmrs_prb: u16, n_oh_prb: u16, n_prb: u16) -> u32 {
+    let n_re_prb = (12 * n_symb_sh).saturating_sub(n_dmrs_prb + n_oh_prb).min(156);
+    n_re_prb as u32 * n_prb as u32
+}
+
+pub fn tbs(n_re: u32, code_rate: f64, qm: u8, layers: u8) -> u32 {
+    let n_info = n_re as f64 * code_rate * qm as f64 * layers as f64;
+    if n_info <= 0.0 {
+        return 0;
+    }
+    if n_info <= 3824.0 {
+        let n = (n_info.log2().floor() as i32 - 6).max(3);
+        let step = (1u32 << n) as f64;
+        let n_info_q = ((n_info / step).floor() * step).max(24.0) as u32;
+        TBS_TABLE.iter().cloned().find(|&t| t >= n_info_q).unwrap()
+    } else {
+        let n = (n_info - 24.0).log2().floor() as i32 - 5;
+        let step = (1u64 << n) as f64;
+        let n_info_q = (((n_info - 24.0) / step).round() * step).max(3840.0) as u64;
+        let c = if code_rate <= 0.25 {
+            (n_info_q + 24).div_ceil(3816)
+        } else if n_info_q > 8424 {
+            (n_info_q + 24).div_ceil(8424)
+        } else {
+            1
+        };
+        (8 * c * (n_info_q + 24).div_ceil(8 * c) - 24) as u32
+    }
+}
+
+pub fn tbs_for_mcs(table: McsTable, i_mcs: u8, n_re: u32, layers: u8) -> Option<u32> {
+    table.entry(i_mcs).map(|e| tbs(n_re, e.code_rate(), e.qm, layers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tbs_values() {
+        assert_eq!(n_re(12, 12, 0, 1), 132);
+        assert_eq!(n_re(14, 0, 0, 2), 312);
+        assert_eq!(tbs_for_mcs(McsTable::Qam64, 0, n_re(12, 12, 0, 1), 1), Some(24));
+        assert_eq!(tbs_for_mcs(McsTable::Qam64, 0, n_re(12, 12, 0, 100), 1), Some(3104));
+        assert_eq!(tbs_for_mcs(McsTable::Qam256, 5, n_re(12, 12, 0, 10), 1), Some(2024));
+        assert_eq!(tbs_for_mcs(McsTable::Qam64, 21, n_re(13, 12, 0, 51), 2), Some(53288));
+        assert_eq!(tbs_for_mcs(McsTable::Qam256, 27, n_re(13, 12, 0, 273), 1), Some(295176));
+        assert_eq!(tbs_for_mcs(McsTable::Qam256, 27, n_re(13, 12, 0, 273), 4), Some(1179864));
+        // R <= 1/4 above 3824 bits
+        assert_eq!(tbs(n_re(12, 12, 0, 273), 120.0 / 1024.0, 2, 1), 8448);
+        assert_eq!(tbs_for_mcs(McsTable::Qam256, 28, 1000, 1), None);
+        assert_eq!(McsTable::Qam64LowSe.entries().len(), 29);
+    }
+}
@@ -0,0 +1,314 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io::{self, Write};
+use crate::protocols::{EaxcId, EaxcIdConfig, EcpriType};
+use crate::utility::ecpri_analysis::EcpriDataVec;
+use crate::utility::modulation_analysis::{AllocationEvm, Modulation};
+use crate::utility::resource_grid::{SlotGrid, SlotKey};
+use crate::utility::tbs::{self, McsTable, MCS_TABLES};
+use crate::utility::timing_model::Numerology;
+
+// Transport block sizes implied by the allocations seen on the fronthaul.
+// Every section of a C-Plane section type 1 message gives n_PRB (numPrbc, every other PRB if
+// rb = 1, numPrbc = 0: up to the end of the carrier), N_symb (numSymbol) and the REs of each
+// PRB (reMask); the REs left out by the reMask are counted as overhead. Qm comes from the
+// modulation classified on the U-Plane grid of the same slot and eAxC (an allocation
+// overlapping the PRBs and symbols of the section). The DMRS and xOverhead can't be seen on
+// the fronthaul, so they are configured.
+// The TBS is computed for every MCS row with that Qm in the three MCS tables (every row if Qm
+// is unknown), which gives the candidate range of the allocation. Allocations with the same
+// PRBs and symbols on several eAxCs are the same transport block on several antennas, they
+// are counted once in the throughput of the slot: TBS / slot duration.
+// Grid allocations without a C-Plane section are estimated with a full reMask.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObservedAllocation {
+    pub slot: SlotKey,
+    pub eaxc_id: EaxcId,
+    pub section_id: Option<u16>,     // None: U-Plane grid only
+    pub start_symbol: u8,
+    pub n_symb: u8,
+    pub start_prb: u16,
+    pub n_prb: u16,
+    pub re_mask: u16,
+    pub modulation: Option<Modulation>,
+}
+
+impl ObservedAllocation {
+    pub fn qm(&self) -> Option<u8> {
+        self.modulation.map(|m| m.bits_per_symbol())
+    }
+
+    fn symbols(&self) -> std::ops::Range<u8> {
+        self.start_symbol..self.start_symbol.saturating_add(self.n_symb)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TbsCandidate {
+    pub table: McsTable,
+    pub i_mcs: u8,
+    pub code_rate_x1024: f64,
+    pub tbs: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TbsEstimate {
+    pub allocation: ObservedAllocation,
+    pub n_re: u32,
+    pub candidates: Vec<TbsCandidate>,
+}
+
+impl TbsEstimate {
+    // (min, max) TBS of the candidates
+    pub fn tbs_range(&self) -> Option<(u32, u32)> {
+        let min = self.candidates.iter().map(|c| c.tbs).min()?;
+        let max = self.candidates.iter().map(|c| c.tbs).max()?;
+        Some((min, max))
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SlotThroughput {
+    pub allocations: usize,
+    pub min_tbs: u64,        // bits in the slot
+    pub max_tbs: u64,
+}
+
+pub struct TbsEstimator {
+    pub eaxc_config: EaxcIdConfig,
+    pub num_prb: u16,              // PRBs of the carrier
+    pub numerology: Numerology,
+    pub dmrs_re_per_prb: u16,      // N_DMRS_PRB, 12: one symbol of DMRS type 1 without data
+    pub overhead_re_per_prb: u16,  // xOverhead
+    pub layers: u8,
+}
+
+impl TbsEstimator {
+    pub fn new(eaxc_config: EaxcIdConfig, num_prb: u16, numerology: Numerology) -> Self {
+        Self { eaxc_config, num_prb, numerology, dmrs_re_per_prb: 12, overhead_re_per_prb: 0, layers: 1 }
+    }
+
+    // Allocations of the C-Plane section type 1 messages, without modulation
+    fn c_plane_allocations(&self, data: &EcpriDataVec) -> Vec<ObservedAllocation> {
+        let mut allocations = Vec::new();
+        for d in data.0.iter() {
+            let c = match &d.data {
+                EcpriType::FCPType1(c) => c,
+                _ => continue,
+            };
+            let (dir, frame_id, subframe_id, slot_id, mut symbol) = d.data.timing();
+            let eaxc_id = d.header.eaxc_id(&self.eaxc_config);
+            for s in c.sections.iter() {
+                let hdr = &s.section_hdr;
+                symbol += hdr.si;
+                let n_prb = if hdr.num_prbc == 0 { self.num_prb.saturating_sub(hdr.start_prbc) } else { hdr.num_prbc as u16 };
+                allocations.push(ObservedAllocation {
+                    slot: (dir as u8, frame_id, subframe_id, slot_id),
+                    eaxc_id,
+                    section_id: Some(hdr.section_id),
+                    start_symbol: symbol,
+                    n_symb: s.num_symbol,
+                    start_prb: hdr.start_prbc,
+                    n_prb: if hdr.rb == 1 { n_prb.div_ceil(2) } else { n_prb },
+                    re_mask: s.re_mask,
+                    modulation: None,
+                });
+            }
+        }
+        allocations
+    }
+
+    pub fn estimate(&self, data: &EcpriDataVec, evm: &[(&SlotGrid, Vec<AllocationEvm>)]) -> TbsEstimation {
+        let grids: BTreeMap<SlotKey, &(&SlotGrid, Vec<AllocationEvm>)> = evm.iter()
+            .map(|r| ((r.0.dir as u8, r.0.frame_id, r.0.subframe_id, r.0.slot_id), r))
+            .collect();
+        let overlaps = |a: &ObservedAllocation, grid: &SlotGrid, e: &AllocationEvm| {
+            grid.eaxc_ids[e.allocation.antenna] == a.eaxc_id
+                && e.allocation.start_prb < a.start_prb + a.n_prb
+                && a.start_prb < e.allocation.start_prb + e.allocation.num_prb
+                && e.allocation.symbols.iter().any(|l| a.symbols().contains(l))
+        };
+
+        let mut allocations = self.c_plane_allocations(data);
+        for a in allocations.iter_mut() {
+            if let Some((grid, results)) = grids.get(&a.slot) {
+                a.modulation = results.iter().filter(|e| overlaps(a, grid, e)).find_map(|e| e.modulation);
+            }
+        }
+        // grid allocations no C-Plane section covers
+        for (key, (grid, results)) in grids.iter() {
+            for e in results.iter() {
+                let covered = allocations.iter()
+                    .any(|a| a.slot == *key && a.section_id.is_some() && overlaps(a, grid, e));
+                let symbols = &e.allocation.symbols;
+                if covered || symbols.is_empty() {
+                    continue;
+                }
+                allocations.push(ObservedAllocation {
+                    slot: *key,
+                    eaxc_id: grid.eaxc_ids[e.allocation.antenna],
+                    section_id: None,
+                    start_symbol: symbols[0],
+                    n_symb: symbols.len() as u8,
+                    start_prb: e.allocation.start_prb,
+                    n_prb: e.allocation.num_prb,
+                    re_mask: 0xFFF,
+                    modulation: e.modulation,
+                });
+            }
+        }
+
+        let mut estimation = TbsEstimation { slots_per_second: 1000 * self.numerology.slots_per_subframe() as u32, ..Default::default() };
+        for allocation in allocations {
+            estimation.push(self.estimate_allocation(allocation));
+        }
+        estimation
+    }
+
+    pub fn estimate_allocation(&self, allocation: ObservedAllocation) -> TbsEstimate {
+        let masked = (12 - (allocation.re_mask & 0xFFF).count_ones() as u16) * allocation.n_symb as u16;
+        let n_re = tbs::n_re(
+            allocation.n_symb as u16,
+            self.dmrs_re_per_prb + masked,
+            self.overhead_re_per_prb,
+            allocation.n_prb,
+        );
+        let qm = allocation.qm();
+        let candidates = MCS_TABLES.iter()
+            .flat_map(|&table| table.entries().into_iter().map(move |(i_mcs, e)| (table, i_mcs, e)))
+            .filter(|(_, _, e)| qm.map_or(true, |qm| e.qm == qm))
+            .map(|(table, i_mcs, e)| TbsCandidate {
+                table,
+                i_mcs,
+                code_rate_x1024: e.code_rate_x1024,
+                tbs: tbs::tbs(n_re, e.code_rate(), e.qm, self.layers),
+            })
+            .collect();
+        TbsEstimate { allocation, n_re, candidates }
+    }
+}
+
+#[derive(Default)]
+pub struct TbsEstimation {
+    pub slots_per_second: u32,
+    pub estimates: Vec<TbsEstimate>,
+    pub slots: BTreeMap<SlotKey, SlotThroughput>,
+    counted: BTreeSet<(SlotKey, u8, u8, u16, u16)>,     // transport blocks already in `slots`
+}
+
+impl TbsEstimation {
+    pub fn push(&mut self, estimate: TbsEstimate) {
+        let a = &estimate.allocation;
+        if let Some((min, max)) = estimate.tbs_range() {
+            if self.counted.insert((a.slot, a.start_symbol, a.n_symb, a.start_prb, a.n_prb)) {
+                let slot = self.slots.entry(a.slot).or_default();
+                slot.allocations += 1;
+                slot.min_tbs += min as u64;
+                slot.max_tbs += max as u64;
+            }
+        }
+        self.estimates.push(estimate);
+    }
+
+    // (min, max) implied throughput of the slot in bit/s
+    pub fn throughput_bps(&self, slot: &SlotThroughput) -> (f64, f64) {
+        let rate = self.slots_per_second as f64;
+        (slot.min_tbs as f64 * rate, slot.max_tbs as f64 * rate)
+    }
+
+    pub fn write_allocations_csv<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "dir, frame_id, subframe_id, slot_id, eaxc, section_id, start_symbol, n_symb, start_prb, n_prb, re_mask, modulation, n_re, candidates, min_tbs, max_tbs")?;
+        for e in self.estimates.iter() {
+            let a = &e.allocation;
+            let section_id = a.section_id.map_or("-".to_string(), |id| id.to_string());
+            let modulation = a.modulation.map_or("unknown".to_string(), |m| m.to_string());
+            let (min, max) = e.tbs_range().unwrap_or((0, 0));
+            writeln!(w, "{}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {:#05x}, {}, {}, {}, {}, {}",
+                     a.slot.0, a.slot.1, a.slot.2, a.slot.3, a.eaxc_id, section_id, a.start_symbol, a.n_symb,
+                     a.start_prb, a.n_prb, a.re_mask, modulation, e.n_re, e.candidates.len(), min, max)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_throughput_csv<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "dir, frame_id, subframe_id, slot_id, allocations, min_tbs, max_tbs, min_mbps, max_mbps")?;
+        for (key, slot) in self.slots.iter() {
+            let (min, max) = self.throughput_bps(slot);
+            writeln!(w, "{}, {}, {}, {}, {}, {}, {}, {:.3}, {:.3}",
+                     key.0, key.1, key.2, key.3, slot.allocations, slot.min_tbs, slot.max_tbs, min / 1e6, max / 1e6)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for TbsEstimation {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        let known = self.estimates.iter().filter(|e| e.allocation.modulation.is_some()).count();
+        writeln!(w, "allocations: {}, with modulation: {}", self.estimates.len(), known)?;
+        writeln!(w, "{:>4} {:>6} {:>6} {:>6} {:>6} {:>10} {:>10} {:>10} {:>10}",
+                 "dir", "frame", "sf", "slot", "alloc", "min TBS", "max TBS", "min Mbps", "max Mbps")?;
+        for (key, slot) in self.slots.iter() {
+            let (min, max) = self.throughput_bps(slot);
+            writeln!(w, "{:>4} {:>6} {:>6} {:>6} {:>6} {:>10} {:>10} {:>10.3} {:>10.3}",
+                     key.0, key.1, key.2, key.3, slot.allocations, slot.min_tbs, slot.max_tbs, min / 1e6, max / 1e6)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::ecpri_analysis::EcpriData;
+    use crate::utility::modulation_analysis::Allocation;
+    use crate::utility::test_support::{self, c_section, section_hdr};
+    use crate::utility::timing_model::CpType;
+
+    // DL, frame 1, subframe 0, slot 1 from symbol 2, sections (sectionId, startPrbc, numPrbc, numSymbol, reMask)
+    fn c_plane(sections: Vec<(u16, u16, u8, u8, u16)>) -> EcpriData {
+        let sections = sections.into_iter()
+            .map(|(id, start, num, num_symbol, re_mask)| c_section(section_hdr(id, start, num), re_mask, num_symbol))
+            .collect();
+        test_support::c_plane(0, 0, (1, 1, 0, 1), 2, sections)
+    }
+
+    #[test]
+    fn tbs_from_c_plane_and_grid() {
+        let config = EaxcIdConfig::default();
+        let mut data = EcpriDataVec::new();
+        data.append(c_plane(vec![(1, 0, 51, 12, 0xFFF), (2, 200, 0, 12, 0xFFF), (3, 100, 10, 12, 0xFF0)]));
+
+        let grid = SlotGrid::new((1, 1, 0, 1), vec![config.decode(0)], 14, 273);
+        let evm = |start_prb, num_prb, modulation| AllocationEvm {
+            allocation: Allocation { antenna: 0, symbols: vec![3, 4], start_prb, num_prb },
+            modulation: Some(modulation),
+            evm: None,
+            res: Vec::new(),
+        };
+        let results = vec![(&grid, vec![evm(0, 51, Modulation::Qam64), evm(200, 73, Modulation::Qpsk)])];
+        let estimator = TbsEstimator::new(config, 273, Numerology::new(1, CpType::Normal));
+        let estimation = estimator.estimate(&data, &results);
+        assert_eq!(estimation.estimates.len(), 3);
+
+        // 64QAM, 51 PRBs of 12 symbols: I_MCS 17..28 of table 1, 11..19 of table 2 and 21..28 of table 3
+        let e = &estimation.estimates[0];
+        assert_eq!((e.allocation.qm(), e.n_re), (Some(6), 132 * 51));
+        assert_eq!(e.candidates.len(), 12 + 9 + 8);
+        assert_eq!(e.tbs_range(), Some((tbs::tbs(132 * 51, 438.0 / 1024.0, 6, 1), tbs::tbs(132 * 51, 948.0 / 1024.0, 6, 1))));
+
+        // numPrbc = 0: PRBs 200..272
+        let e = &estimation.estimates[1];
+        assert_eq!((e.allocation.n_prb, e.allocation.qm()), (73, Some(2)));
+
+        // no U-Plane: every MCS row, 4 REs of every PRB masked out
+        let e = &estimation.estimates[2];
+        assert_eq!((e.allocation.modulation, e.n_re), (None, (132 - 48) * 10));
+        assert_eq!(e.candidates.len(), 29 + 28 + 29);
+
+        let slot = &estimation.slots[&(1, 1, 0, 1)];
+        assert_eq!(slot.allocations, 3);
+        let (min, _) = estimation.throughput_bps(slot);
+        assert_eq!(min, slot.min_tbs as f64 * 2000.0);
+    }
+}
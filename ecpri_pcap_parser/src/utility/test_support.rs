@@ -0,0 +1,85 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use crate::protocols::{CommonHeader, DataDirection, EcpriType, FCPSectionType1, FilterIndex, IQPrbuData, SectionHeader,
+                       TimingHeader, UPlaneIQData, UPlaneSection, UdCompHdr, _SectionType1Data, pack_samples, RE_PER_PRB};
+use crate::utility::ecpri_analysis::EcpriData;
+use crate::utility::resource_grid::SlotKey;
+
+// Messages of the analysis tests. The capture starts at 1_600_000_000 s, the slots are
+// given as SlotKey (dataDirection, frameId, subframeId, slotId).
+
+// Capture time, us after the start of the capture
+pub(crate) fn at(us: i64) -> DateTime<Utc> {
+    Utc.timestamp(1_600_000_000, 0) + Duration::microseconds(us)
+}
+
+// Common header of an unfragmented message (seqid 0), payload size left to 0
+pub(crate) fn header(pcid: u16, message_type: u8) -> CommonHeader {
+    CommonHeader { revision: 1, reserved: 0, concatenation: 0, message_type, payload_size: 0, pcid, seqid: 0 }
+}
+
+// Section header without udCompHdr, every PRB (rb = 0) and no symInc
+pub(crate) fn section_hdr(section_id: u16, start_prbc: u16, num_prbc: u8) -> SectionHeader {
+    SectionHeader { section_id, rb: 0, si: 0, start_prbc, num_prbc, ud_comp_hdr: None, reserved: None }
+}
+
+// The same I/Q on the 12 REs of the PRB
+pub(crate) fn prb(ud_comp_hdr: UdCompHdr, exponent: u8, iq: (i32, i32)) -> IQPrbuData {
+    IQPrbuData {
+        reserved: 0,
+        exponent,
+        iq_sample: pack_samples(&[iq; RE_PER_PRB], ud_comp_hdr.iq_width()),
+        ud_comp_hdr,
+    }
+}
+
+// U-Plane section with the udCompHdr of its PRBs, 16 bits uncompressed if it has none
+pub(crate) fn u_section(section_hdr: SectionHeader, iq_prbu: Vec<IQPrbuData>) -> UPlaneSection {
+    let ud_comp_hdr = iq_prbu.first().map_or(UdCompHdr::from(0), |p| p.ud_comp_hdr);
+    UPlaneSection { section_hdr, ud_comp_hdr, iq_prbu }
+}
+
+pub(crate) fn u_plane(us: i64, pcid: u16, slot: SlotKey, symbol: u8, sections: Vec<UPlaneSection>) -> EcpriData {
+    EcpriData {
+        timestamp: at(us),
+        header: header(pcid, 0),
+        data: EcpriType::IQData(Box::new(UPlaneIQData {
+            dir: DataDirection::from(slot.0),
+            payload_ver: 1,
+            filter_index: FilterIndex::from(0),
+            frame_id: slot.1,
+            subframe_id: slot.2,
+            slot_id: slot.3,
+            start_symbol_id: symbol,
+            sections,
+        })),
+    }
+}
+
+// Section type 1 section of every RE, without beamId nor extension
+pub(crate) fn c_section(section_hdr: SectionHeader, re_mask: u16, num_symbol: u8) -> _SectionType1Data {
+    _SectionType1Data { section_hdr, re_mask, num_symbol, ef: 0, beam_id: 0, extensions: Vec::new() }
+}
+
+// Section type 1 message
+pub(crate) fn c_plane(us: i64, pcid: u16, slot: SlotKey, symbol: u8, sections: Vec<_SectionType1Data>) -> EcpriData {
+    EcpriData {
+        timestamp: at(us),
+        header: header(pcid, 2),
+        data: EcpriType::FCPType1(Box::new(FCPSectionType1 {
+            comm_ctrl_info: TimingHeader {
+                dir: DataDirection::from(slot.0),
+                payload_ver: 1,
+                filter_index: FilterIndex::from(0),
+                frame_id: slot.1,
+                subframe_id: slot.2,
+                slot_id: slot.3,
+                start_symbol_id: symbol,
+                num_of_sections: sections.len() as u8,
+                section_type: 1,
+            },
+            ud_comp_hdr: 0,
+            reserved: 0,
+            sections,
+        })),
+    }
+}
@@ -0,0 +1,260 @@
+use crate::protocols::{MU, TimingHeader};
+
+// NR frame timing, TS 38.211 4.1 and 5.3.1.
+// Everything is counted in Tc = 1 / (480 kHz * 4096), the basic time unit of NR, so the
+// symbol boundaries are exact integers: Ts (LTE, 1 / 30.72 MHz) = 64 Tc.
+//   :a 10 ms frame has 10 subframes, a subframe has 2^μ slots
+//   :a slot has 14 symbols (normal CP) or 12 (extended CP, μ = 2 only)
+//   :symbol = (2048 + CP) * κ * 2^-μ Tc, κ = 64, CP = 144 (normal), 512 (extended)
+//   :the first symbol of every 0.5 ms has 16κ Tc more CP
+
+pub const TC_PER_SECOND: i64 = 480_000 * 4096;
+pub const TC_PER_FRAME: i64 = TC_PER_SECOND / 100;
+pub const TC_PER_SUBFRAME: i64 = TC_PER_SECOND / 1000;
+pub const TC_PER_TS: i64 = 64;
+pub const FRAME_NS: i64 = 10_000_000;
+// SFN counts 1024 frames (10 bits), the eCPRI frameId only its 8 LSBs
+pub const SFN_PERIOD: u16 = 1024;
+
+const KAPPA: i64 = 64;
+const TC_PER_HALF_SUBFRAME: i64 = TC_PER_SUBFRAME / 2;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CpType {
+    Normal,
+    Extended,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Numerology {
+    pub mu: u8,      // 0..5, subcarrier spacing = 15 kHz * 2^μ
+    pub cp: CpType,
+}
+
+// Position of a symbol on the air interface
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SymbolTime {
+    pub sfn: u16,         // 0..1023
+    pub subframe: u8,     // 0..9
+    pub slot: u8,         // 0..2^μ - 1
+    pub symbol: u8,       // 0..13 (normal CP), 0..11 (extended CP)
+}
+
+impl Numerology {
+    pub fn new(mu: u8, cp: CpType) -> Self {
+        assert!(mu <= 5, "Numerology {} doesn't exist.", mu);
+        Self { mu, cp }
+    }
+
+    // The numerology of the carrier, PRACH and the other special subcarrier spacings have none.
+    pub fn from_mu(mu: &MU, cp: CpType) -> Option<Self> {
+        match mu {
+            MU::KHZ_15 => Some(Numerology::new(0, cp)),
+            MU::KHZ_30 => Some(Numerology::new(1, cp)),
+            MU::KHZ_60 => Some(Numerology::new(2, cp)),
+            MU::KHZ_120 => Some(Numerology::new(3, cp)),
+            MU::KHZ_240 => Some(Numerology::new(4, cp)),
+            MU::KHZ_480 => Some(Numerology::new(5, cp)),
+            _ => None,
+        }
+    }
+
+    pub fn scs_hz(&self) -> u32 {
+        15_000 << self.mu
+    }
+
+    pub fn slots_per_subframe(&self) -> u8 {
+        1 << self.mu
+    }
+
+    pub fn slots_per_frame(&self) -> u16 {
+        10 << self.mu
+    }
+
+    pub fn symbols_per_slot(&self) -> u8 {
+        match self.cp {
+            CpType::Normal => 14,
+            CpType::Extended => 12,
+        }
+    }
+
+    pub fn symbols_per_subframe(&self) -> u16 {
+        self.symbols_per_slot() as u16 * self.slots_per_subframe() as u16
+    }
+
+    // Useful part of the symbol (the FFT length) in Tc
+    pub fn fft_length_tc(&self) -> i64 {
+        (2048 * KAPPA) >> self.mu
+    }
+
+    // CP of the symbol l within the subframe, in Tc
+    pub fn cp_length_tc(&self, l: u16) -> i64 {
+        match self.cp {
+            CpType::Extended => (512 * KAPPA) >> self.mu,
+            CpType::Normal => {
+                let cp = (144 * KAPPA) >> self.mu;
+                if l % (7 << self.mu) == 0 { cp + 16 * KAPPA } else { cp }
+            },
+        }
+    }
+
+    pub fn symbol_length_tc(&self, l: u16) -> i64 {
+        self.fft_length_tc() + self.cp_length_tc(l)
+    }
+
+    // Start of the symbol l (CP included) from the start of the subframe, in Tc
+    pub fn symbol_start_in_subframe_tc(&self, l: u16) -> i64 {
+        match self.cp {
+            CpType::Extended => l as i64 * self.symbol_length_tc(l),
+            CpType::Normal => {
+                // whole half subframes, then the symbols of the current half
+                let per_half = 7u16 << self.mu;
+                let half = (l / per_half) as i64;
+                let k = (l % per_half) as i64;
+                let first = if k > 0 { 16 * KAPPA } else { 0 };
+                half * TC_PER_HALF_SUBFRAME + k * (((2048 + 144) * KAPPA) >> self.mu) + first
+            },
+        }
+    }
+
+    // Start of (subframe, slot, symbol) from the start of the 10 ms frame, in Tc
+    pub fn symbol_start_tc(&self, subframe: u8, slot: u8, symbol: u8) -> i64 {
+        let l = slot as u16 * self.symbols_per_slot() as u16 + symbol as u16;
+        subframe as i64 * TC_PER_SUBFRAME + self.symbol_start_in_subframe_tc(l)
+    }
+
+    pub fn symbol_start_ns(&self, subframe: u8, slot: u8, symbol: u8) -> f64 {
+        tc_to_ns(self.symbol_start_tc(subframe, slot, symbol))
+    }
+
+    // (subframe, slot, symbol) the offset within the 10 ms frame falls in
+    pub fn symbol_at_tc(&self, offset_tc: i64) -> (u8, u8, u8) {
+        let offset_tc = offset_tc.rem_euclid(TC_PER_FRAME);
+        let subframe = (offset_tc / TC_PER_SUBFRAME) as u8;
+        let in_subframe = offset_tc % TC_PER_SUBFRAME;
+        // at most 14 * 32 symbols, the linear search is cheap enough
+        let l = (0..self.symbols_per_subframe())
+            .take_while(|&l| self.symbol_start_in_subframe_tc(l) <= in_subframe)
+            .last()
+            .unwrap_or(0);
+        let per_slot = self.symbols_per_slot() as u16;
+        (subframe, (l / per_slot) as u8, (l % per_slot) as u8)
+    }
+
+    pub fn symbol_at_ns(&self, offset_ns: f64) -> (u8, u8, u8) {
+        self.symbol_at_tc(ns_to_tc(offset_ns))
+    }
+
+    // Start of the symbol over the whole SFN period (10.24 s), in ns
+    pub fn air_time_ns(&self, time: &SymbolTime) -> f64 {
+        time.sfn as f64 * FRAME_NS as f64 + self.symbol_start_ns(time.subframe, time.slot, time.symbol)
+    }
+
+    // The symbol at an offset from the start of SFN 0, wrapping every 1024 frames
+    pub fn symbol_time_at_ns(&self, offset_ns: f64) -> SymbolTime {
+        let tc = ns_to_tc(offset_ns).rem_euclid(TC_PER_FRAME * SFN_PERIOD as i64);
+        let (subframe, slot, symbol) = self.symbol_at_tc(tc % TC_PER_FRAME);
+        SymbolTime { sfn: (tc / TC_PER_FRAME) as u16, subframe, slot, symbol }
+    }
+
+    // slotId and startSymbolId of the timing header are within the numerology range
+    pub fn is_valid(&self, timing_header: &TimingHeader) -> bool {
+        timing_header.subframe_id < 10
+            && timing_header.slot_id < self.slots_per_subframe()
+            && timing_header.start_symbol_id < self.symbols_per_slot()
+    }
+
+    // Start of the first symbol of the timing header within the 10 ms frame, in ns
+    pub fn timing_header_ns(&self, timing_header: &TimingHeader) -> f64 {
+        self.symbol_start_ns(timing_header.subframe_id, timing_header.slot_id, timing_header.start_symbol_id)
+    }
+}
+
+pub fn tc_to_ns(tc: i64) -> f64 {
+    tc as f64 * 1e9 / TC_PER_SECOND as f64
+}
+
+pub fn ns_to_tc(ns: f64) -> i64 {
+    // the tiny margin keeps exact symbol boundaries from rounding down into the previous symbol
+    (ns * TC_PER_SECOND as f64 / 1e9 + 1e-6).floor() as i64
+}
+
+// The 10 bits SFN of the 8 bits eCPRI frameId, the candidate closest to the reference SFN
+// (e.g. the one expected from the packet timestamp).
+pub fn sfn_from_frame_id(frame_id: u8, reference_sfn: u16) -> u16 {
+    let period = SFN_PERIOD as i32;
+    (0..4)
+        .map(|k| (k * 256 + frame_id as i32) as u16)
+        .min_by_key(|&sfn| {
+            let d = (sfn as i32 - reference_sfn as i32).rem_euclid(period);
+            d.min(period - d)
+        })
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_is_exactly_10_ms() {
+        for mu in 0..=5 {
+            let numerology = Numerology::new(mu, CpType::Normal);
+            let total: i64 = (0..numerology.symbols_per_subframe()).map(|l| numerology.symbol_length_tc(l)).sum();
+            assert_eq!(total, TC_PER_SUBFRAME);
+            assert_eq!(numerology.symbol_start_in_subframe_tc(7 << mu), TC_PER_SUBFRAME / 2);
+        }
+        let extended = Numerology::new(2, CpType::Extended);
+        let total: i64 = (0..extended.symbols_per_subframe()).map(|l| extended.symbol_length_tc(l)).sum();
+        assert_eq!(total, TC_PER_SUBFRAME);
+        assert_eq!(TC_PER_FRAME, 19_660_800);
+    }
+
+    #[test]
+    fn symbol_boundaries() {
+        // LTE like 15 kHz: 160 + 2048 Ts for the first symbol
+        let mu0 = Numerology::new(0, CpType::Normal);
+        assert_eq!(mu0.symbol_start_tc(0, 0, 1), (160 + 2048) * TC_PER_TS);
+        assert_eq!(mu0.symbol_start_ns(0, 0, 1), 71875.0);
+        let mu1 = Numerology::new(1, CpType::Normal);
+        assert_eq!(mu1.symbol_start_ns(3, 1, 0), 3_500_000.0);
+        assert_eq!(mu1.slots_per_frame(), 20);
+        assert_eq!(mu1.scs_hz(), 30_000);
+        // the slots of a half subframe are not equally long, the first one has the longer CP
+        let mu3 = Numerology::new(3, CpType::Normal);
+        assert_eq!(mu3.symbol_start_ns(9, 4, 0), 9_500_000.0);
+        assert_eq!(mu3.symbol_start_tc(9, 7, 0), 9 * TC_PER_SUBFRAME + TC_PER_SUBFRAME / 2 + 3 * 14 * ((2048 + 144) * KAPPA / 8) + 16 * KAPPA);
+    }
+
+    #[test]
+    fn symbol_round_trip() {
+        for &(mu, cp) in [(0, CpType::Normal), (1, CpType::Normal), (2, CpType::Extended), (3, CpType::Normal), (4, CpType::Normal)].iter() {
+            let numerology = Numerology::new(mu, cp);
+            for subframe in 0..10 {
+                for slot in 0..numerology.slots_per_subframe() {
+                    for symbol in 0..numerology.symbols_per_slot() {
+                        let start = numerology.symbol_start_ns(subframe, slot, symbol);
+                        assert_eq!(numerology.symbol_at_ns(start), (subframe, slot, symbol));
+                        assert_eq!(numerology.symbol_at_ns(start + 100.0), (subframe, slot, symbol));
+                        if start > 0.0 {
+                            assert_ne!(numerology.symbol_at_ns(start - 1.0), (subframe, slot, symbol));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sfn_wrap() {
+        assert_eq!(sfn_from_frame_id(10, 10), 10);
+        assert_eq!(sfn_from_frame_id(10, 260), 266);
+        assert_eq!(sfn_from_frame_id(255, 1), 1023);
+        assert_eq!(sfn_from_frame_id(0, 1020), 0);
+        let numerology = Numerology::new(1, CpType::Normal);
+        let time = SymbolTime { sfn: 1023, subframe: 9, slot: 1, symbol: 13 };
+        let ns = numerology.air_time_ns(&time);
+        assert_eq!(numerology.symbol_time_at_ns(ns), time);
+        assert_eq!(numerology.symbol_time_at_ns(ns + 40_000.0).sfn, 0);
+    }
+}